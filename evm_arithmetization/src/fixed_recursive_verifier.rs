@@ -1028,6 +1028,30 @@ where
             timing,
             abort_signal.clone(),
         )?;
+
+        self.prove_root_from_all_proof(all_proof, config, abort_signal)
+    }
+
+    /// From an initial set of STARK proofs, generate a recursive transaction
+    /// proof using the preprocessed table circuits of this prover state.
+    ///
+    /// This is the second half of [`Self::prove_root`], which allows callers
+    /// to inspect the [`AllProof`] (e.g. its [`AllProof::degree_bits`]) before
+    /// shrinking it.
+    ///
+    /// # Arguments
+    ///
+    /// - `all_proof`: the STARK proofs of all modules, as generated by
+    ///   [`prove`].
+    /// - `config`: the configuration used to generate `all_proof`.
+    /// - `abort_signal`: an optional [`AtomicBool`] wrapped behind an [`Arc`],
+    ///   to send a kill signal early.
+    pub fn prove_root_from_all_proof(
+        &self,
+        all_proof: AllProof<F, C, D>,
+        config: &StarkConfig,
+        abort_signal: Option<Arc<AtomicBool>>,
+    ) -> anyhow::Result<(ProofWithPublicInputs<F, C, D>, PublicValues)> {
         let mut root_inputs = PartialWitness::new();
 
         for table in 0..NUM_TABLES {
@@ -216,12 +216,15 @@ pub(crate) fn debug_inputs(inputs: &GenerationInputs) {
     log::debug!("Input contract_code: {:?}", &inputs.contract_code);
 }
 
+/// Generates the traces of every STARK table, along with the public values and
+/// the number of CPU cycles run (i.e. the length of the CPU trace before it is
+/// padded to a power of two).
 pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
     all_stark: &AllStark<F, D>,
     inputs: GenerationInputs,
     config: &StarkConfig,
     timing: &mut TimingTree,
-) -> anyhow::Result<([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues)> {
+) -> anyhow::Result<([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues, usize)> {
     debug_inputs(&inputs);
     let mut state = GenerationState::<F>::new(inputs.clone(), &KERNEL.code)
         .map_err(|err| anyhow!("Failed to parse all the initial prover inputs: {:?}", err))?;
@@ -229,11 +232,14 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
     apply_metadata_and_tries_memops(&mut state, &inputs);
 
     let cpu_res = timed!(timing, "simulate CPU", simulate_cpu(&mut state));
-    if cpu_res.is_err() {
-        let _ = output_debug_tries(&state);
+    let cpu_cycles = match cpu_res {
+        Ok(cpu_cycles) => cpu_cycles,
+        Err(err) => {
+            let _ = output_debug_tries(&state);
 
-        cpu_res?;
-    }
+            return Err(err);
+        }
+    };
 
     log::info!(
         "Trace lengths (before padding): {:?}",
@@ -276,11 +282,14 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
         "convert trace data to tables",
         state.traces.into_tables(all_stark, config, timing)
     );
-    Ok((tables, public_values))
+    Ok((tables, public_values, cpu_cycles))
 }
 
-fn simulate_cpu<F: Field>(state: &mut GenerationState<F>) -> anyhow::Result<()> {
+/// Runs the CPU, padding its trace to a power of two, and returns the number of
+/// cycles run (i.e. the length of the CPU trace before padding).
+fn simulate_cpu<F: Field>(state: &mut GenerationState<F>) -> anyhow::Result<usize> {
     state.run_cpu()?;
+    let cpu_cycles = state.traces.clock();
 
     let pc = state.registers.program_counter;
     // Setting the values of padding rows.
@@ -303,7 +312,7 @@ fn simulate_cpu<F: Field>(state: &mut GenerationState<F>) -> anyhow::Result<()>
 
     log::info!("CPU trace padded to {} cycles", state.traces.clock());
 
-    Ok(())
+    Ok(cpu_cycles)
 }
 
 /// Outputs the tries that have been obtained post transaction execution, as
@@ -38,7 +38,7 @@ where
     C: GenericConfig<D, F = F>,
 {
     timed!(timing, "build kernel", Lazy::force(&KERNEL));
-    let (traces, public_values) = timed!(
+    let (traces, public_values, _) = timed!(
         timing,
         "generate all traces",
         generate_traces(all_stark, inputs, config, timing)?
@@ -56,8 +56,9 @@ where
     Ok(proof)
 }
 
-/// Compute all STARK proofs.
-pub(crate) fn prove_with_traces<F, C, const D: usize>(
+/// Compute all STARK proofs from the traces returned by
+/// [`generate_traces`].
+pub fn prove_with_traces<F, C, const D: usize>(
     all_stark: &AllStark<F, D>,
     config: &StarkConfig,
     trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
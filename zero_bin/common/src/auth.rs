@@ -0,0 +1,367 @@
+//! Authentication of the requests received by the HTTP servers of the
+//! coordinator and the leader.
+//!
+//! A request is authenticated either by a bearer token, sent as
+//! `Authorization: Bearer <token>`, or by an HMAC-SHA256 signature, sent
+//! hex-encoded as `X-Signature-256: sha256=<signature>`.  The signature covers
+//! the method, the path, the [`TIMESTAMP_HEADER`] and the body of the request,
+//! as `<method>\n<path>\n<timestamp>\n<body>`, so that it cannot be reused for
+//! another request, nor replayed once [`MAX_SIGNATURE_AGE`] has passed.  When
+//! both are configured, either one is enough.  When neither is configured,
+//! every request is accepted.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// The header holding the HMAC-SHA256 signature of the request.
+pub const SIGNATURE_HEADER: &str = "x-signature-256";
+/// The header holding when the request was signed, in seconds since the Unix
+/// epoch.
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// How far the [`TIMESTAMP_HEADER`] of a signed request may be from the
+/// current time.
+pub const MAX_SIGNATURE_AGE: Duration = Duration::from_secs(5 * 60);
+/// The prefix of the signature within [`SIGNATURE_HEADER`].
+const SIGNATURE_PREFIX: &str = "sha256=";
+/// The scheme of the bearer token within the `Authorization` header.
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// The reason a request was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("missing bearer token or request signature")]
+    Missing,
+    #[error("invalid bearer token")]
+    InvalidToken,
+    #[error("invalid request signature")]
+    InvalidSignature,
+    #[error("missing or stale request timestamp")]
+    StaleTimestamp,
+}
+
+/// The parts of a request an [`AuthConfig`] checks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuthRequest<'a> {
+    pub method: &'a str,
+    /// The path of the request, with its query if any
+    pub path: &'a str,
+    /// The `Authorization` header
+    pub authorization: Option<&'a str>,
+    /// The [`SIGNATURE_HEADER`]
+    pub signature: Option<&'a str>,
+    /// The [`TIMESTAMP_HEADER`]
+    pub timestamp: Option<&'a str>,
+    pub body: &'a [u8],
+}
+
+/// The credentials the requests are checked against.
+#[derive(Clone, Default)]
+pub struct AuthConfig {
+    /// The SHA-256 digest of the bearer token, so that comparing tokens does
+    /// not leak the expected token through timing.
+    token_digest: Option<[u8; 32]>,
+    hmac_secret: Option<Vec<u8>>,
+}
+
+impl std::fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("bearer_token", &self.token_digest.is_some())
+            .field("hmac_secret", &self.hmac_secret.is_some())
+            .finish()
+    }
+}
+
+impl AuthConfig {
+    /// Returns the [`AuthConfig`] accepting the given bearer token and/or the
+    /// requests signed with the given HMAC secret.  Empty values are ignored.
+    pub fn new(bearer_token: Option<&str>, hmac_secret: Option<&str>) -> Self {
+        Self {
+            token_digest: bearer_token
+                .filter(|token| !token.is_empty())
+                .map(|token| Sha256::digest(token.as_bytes()).into()),
+            hmac_secret: hmac_secret
+                .filter(|secret| !secret.is_empty())
+                .map(|secret| secret.as_bytes().to_vec()),
+        }
+    }
+
+    /// Returns true if the requests need to be authenticated.
+    pub fn is_enabled(&self) -> bool {
+        self.token_digest.is_some() || self.hmac_secret.is_some()
+    }
+
+    /// Checks a request against the current time.
+    pub fn verify(&self, request: &AuthRequest<'_>) -> Result<(), AuthError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.verify_at(request, now)
+    }
+
+    /// Checks a request, `now` being the time since the Unix epoch.
+    pub fn verify_at(&self, request: &AuthRequest<'_>, now: Duration) -> Result<(), AuthError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let mut err = AuthError::Missing;
+        if let (Some(token_digest), Some(authorization)) =
+            (&self.token_digest, request.authorization)
+        {
+            match authorization.strip_prefix(BEARER_PREFIX) {
+                Some(token)
+                    if Sha256::digest(token.trim().as_bytes()).as_slice() == token_digest =>
+                {
+                    return Ok(())
+                }
+                _ => err = AuthError::InvalidToken,
+            }
+        }
+        if let (Some(secret), Some(signature)) = (&self.hmac_secret, request.signature) {
+            let timestamp = request
+                .timestamp
+                .and_then(|timestamp| timestamp.trim().parse::<u64>().ok())
+                .filter(|timestamp| {
+                    Duration::from_secs(*timestamp).abs_diff(now) <= MAX_SIGNATURE_AGE
+                });
+            let Some(timestamp) = timestamp else {
+                return Err(AuthError::StaleTimestamp);
+            };
+            let signature = signature
+                .trim()
+                .strip_prefix(SIGNATURE_PREFIX)
+                .and_then(|signature| hex::decode(signature).ok());
+            match signature {
+                Some(signature)
+                    if signed(
+                        secret,
+                        request.method,
+                        request.path,
+                        timestamp,
+                        request.body,
+                    )
+                    .verify_slice(&signature)
+                    .is_ok() =>
+                {
+                    return Ok(())
+                }
+                _ => err = AuthError::InvalidSignature,
+            }
+        }
+        Err(err)
+    }
+}
+
+/// Returns the value of the [`SIGNATURE_HEADER`] of a request signed with the
+/// given secret at `timestamp`, in seconds since the Unix epoch, to be sent
+/// along with it as its [`TIMESTAMP_HEADER`].
+pub fn sign(secret: &[u8], method: &str, path: &str, timestamp: u64, body: &[u8]) -> String {
+    let signature = signed(secret, method, path, timestamp, body)
+        .finalize()
+        .into_bytes();
+    format!("{SIGNATURE_PREFIX}{}", hex::encode(signature))
+}
+
+/// Returns the HMAC of the signed parts of a request.
+fn signed(secret: &[u8], method: &str, path: &str, timestamp: u64, body: &[u8]) -> Hmac<Sha256> {
+    hmac(secret)
+        .chain_update(format!(
+            "{}\n{}\n{}\n",
+            method.to_uppercase(),
+            path,
+            timestamp
+        ))
+        .chain_update(body)
+}
+
+fn hmac(secret: &[u8]) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: Duration = Duration::from_secs(1_700_000_000);
+
+    fn request<'a>(authorization: Option<&'a str>, body: &'a [u8]) -> AuthRequest<'a> {
+        AuthRequest {
+            method: "POST",
+            path: "/",
+            authorization,
+            body,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the request holding the given signature and timestamp
+    fn signed_request<'a>(
+        method: &'a str,
+        path: &'a str,
+        timestamp: &'a str,
+        signature: &'a str,
+        body: &'a [u8],
+    ) -> AuthRequest<'a> {
+        AuthRequest {
+            method,
+            path,
+            authorization: None,
+            signature: Some(signature),
+            timestamp: Some(timestamp),
+            body,
+        }
+    }
+
+    #[test]
+    fn accepts_everything_when_disabled() {
+        let auth = AuthConfig::new(None, Some(""));
+        assert!(!auth.is_enabled());
+        assert_eq!(auth.verify_at(&request(None, b"body"), NOW), Ok(()));
+    }
+
+    #[test]
+    fn checks_bearer_token() {
+        let auth = AuthConfig::new(Some("secret-token"), None);
+        assert_eq!(
+            auth.verify_at(&request(Some("Bearer secret-token"), b""), NOW),
+            Ok(())
+        );
+        assert_eq!(
+            auth.verify_at(&request(Some("Bearer other-token"), b""), NOW),
+            Err(AuthError::InvalidToken)
+        );
+        assert_eq!(
+            auth.verify_at(&request(Some("secret-token"), b""), NOW),
+            Err(AuthError::InvalidToken)
+        );
+        assert_eq!(
+            auth.verify_at(&request(None, b""), NOW),
+            Err(AuthError::Missing)
+        );
+    }
+
+    #[test]
+    fn checks_signature() {
+        let auth = AuthConfig::new(None, Some("hmac-secret"));
+        let body = b"{\"block_interval\":\"1..3\"}";
+        let timestamp = NOW.as_secs().to_string();
+        let signature = sign(b"hmac-secret", "POST", "/", NOW.as_secs(), body);
+        assert_eq!(
+            auth.verify_at(
+                &signed_request("POST", "/", &timestamp, &signature, body),
+                NOW
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            auth.verify_at(
+                &signed_request(
+                    "POST",
+                    "/",
+                    &timestamp,
+                    &signature,
+                    b"{\"block_interval\":\"1..4\"}"
+                ),
+                NOW
+            ),
+            Err(AuthError::InvalidSignature)
+        );
+        assert_eq!(
+            auth.verify_at(
+                &signed_request("POST", "/", &timestamp, "sha256=zz", b""),
+                NOW
+            ),
+            Err(AuthError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_signatures_of_other_requests() {
+        let auth = AuthConfig::new(None, Some("hmac-secret"));
+        let timestamp = NOW.as_secs().to_string();
+        let signature = sign(b"hmac-secret", "GET", "/jobs/1", NOW.as_secs(), b"");
+        assert_eq!(
+            auth.verify_at(
+                &signed_request("GET", "/jobs/1", &timestamp, &signature, b""),
+                NOW
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            auth.verify_at(
+                &signed_request("GET", "/jobs/2", &timestamp, &signature, b""),
+                NOW
+            ),
+            Err(AuthError::InvalidSignature)
+        );
+        assert_eq!(
+            auth.verify_at(
+                &signed_request("DELETE", "/jobs/1", &timestamp, &signature, b""),
+                NOW
+            ),
+            Err(AuthError::InvalidSignature)
+        );
+        // The timestamp is signed as well
+        let later = (NOW.as_secs() + 1).to_string();
+        assert_eq!(
+            auth.verify_at(
+                &signed_request("GET", "/jobs/1", &later, &signature, b""),
+                NOW
+            ),
+            Err(AuthError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_stale_signatures() {
+        let auth = AuthConfig::new(None, Some("hmac-secret"));
+        let timestamp = NOW.as_secs().to_string();
+        let signature = sign(b"hmac-secret", "GET", "/jobs", NOW.as_secs(), b"");
+        let request = signed_request("GET", "/jobs", &timestamp, &signature, b"");
+
+        assert_eq!(auth.verify_at(&request, NOW + MAX_SIGNATURE_AGE), Ok(()));
+        assert_eq!(
+            auth.verify_at(&request, NOW + MAX_SIGNATURE_AGE + Duration::from_secs(1)),
+            Err(AuthError::StaleTimestamp)
+        );
+        // Nor too far in the future
+        assert_eq!(
+            auth.verify_at(&request, NOW - MAX_SIGNATURE_AGE - Duration::from_secs(1)),
+            Err(AuthError::StaleTimestamp)
+        );
+        assert_eq!(
+            auth.verify_at(
+                &AuthRequest {
+                    timestamp: None,
+                    ..request
+                },
+                NOW
+            ),
+            Err(AuthError::StaleTimestamp)
+        );
+    }
+
+    #[test]
+    fn accepts_either_method() {
+        let auth = AuthConfig::new(Some("secret-token"), Some("hmac-secret"));
+        let timestamp = NOW.as_secs().to_string();
+        let signature = sign(b"hmac-secret", "POST", "/", NOW.as_secs(), b"body");
+        assert_eq!(
+            auth.verify_at(
+                &AuthRequest {
+                    authorization: Some("Bearer wrong"),
+                    ..signed_request("POST", "/", &timestamp, &signature, b"body")
+                },
+                NOW
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            auth.verify_at(&request(Some("Bearer secret-token"), b"body"), NOW),
+            Ok(())
+        );
+    }
+}
@@ -1,10 +1,12 @@
+use std::{future::Future, time::Duration};
+
 use alloy::primitives::B256;
 use alloy::rpc::types::eth::BlockId;
 use alloy::{hex, providers::Provider, transports::Transport};
 use anyhow::{anyhow, Result};
 use async_stream::try_stream;
-use futures::Stream;
-use tracing::info;
+use futures::{stream::BoxStream, Stream, StreamExt as _};
+use tracing::{info, warn};
 
 use crate::parsing;
 
@@ -109,10 +111,14 @@ impl BlockInterval {
     }
 
     /// Convert the block interval into an unbounded async stream of block
-    /// numbers. Query the blockchain node for the latest block number.
+    /// numbers, starting with the start block. Query the blockchain node for
+    /// the latest block number.
+    /// If the node notifies its `new_heads` (i.e. over WebSocket or IPC), wait
+    /// for them instead of polling the node every block time.
     pub async fn into_unbounded_stream<ProviderT, TransportT>(
         self,
         provider: ProviderT,
+        new_heads: Option<BoxStream<'static, ()>>,
     ) -> Result<impl Stream<Item = Result<u64, anyhow::Error>>, anyhow::Error>
     where
         ProviderT: Provider<TransportT>,
@@ -122,26 +128,22 @@ impl BlockInterval {
             BlockInterval::FollowFrom {
                 start_block,
                 block_time,
-            } => Ok(try_stream! {
-                let mut current = start_block;
-                 loop {
-                    let last_block_number = provider.get_block_number().await.map_err(|e: alloy::transports::RpcError<_>| {
-                        anyhow!("could not retrieve latest block number from the provider: {e}")
-                    })?;
-
-                    if current < last_block_number {
-                        current += 1;
-                        yield current;
-                    } else {
-                       info!("Waiting for the new blocks to be mined, requested block number: {current}, \
-                       latest block number: {last_block_number}");
-                        let block_time = block_time.unwrap_or(DEFAULT_BLOCK_TIME);
-                        // No need to poll the node too frequently, waiting
-                        // a block time interval for a block to be mined should be enough
-                       tokio::time::sleep(tokio::time::Duration::from_millis(block_time)).await;
+            } => {
+                let block_time = Duration::from_millis(block_time.unwrap_or(DEFAULT_BLOCK_TIME));
+                let latest_block_number = move || {
+                    let call = provider.get_block_number();
+                    async move {
+                        call.await.map_err(|e| {
+                            anyhow!("could not retrieve latest block number from the provider: {e}")
+                        })
                     }
-                }
-            }),
+                };
+                Ok(follow_from(
+                    start_block,
+                    latest_block_number,
+                    ticks(new_heads, block_time),
+                ))
+            }
             _ => Err(anyhow!(
                 "could not create unbounded follow-from stream from fixed bounded interval",
             )),
@@ -149,6 +151,63 @@ impl BlockInterval {
     }
 }
 
+/// Ticks on every new head, or after a block time without any in case the
+/// node missed one.  Without new heads, or once they end, ticks every block
+/// time: no need to poll the node too frequently, waiting a block time
+/// interval for a block to be mined should be enough
+fn ticks<S>(new_heads: Option<S>, block_time: Duration) -> impl Stream<Item = ()>
+where
+    S: Stream<Item = ()> + Unpin,
+{
+    futures::stream::unfold(new_heads, move |new_heads| async move {
+        let Some(mut heads) = new_heads else {
+            tokio::time::sleep(block_time).await;
+            return Some(((), None));
+        };
+        match tokio::time::timeout(block_time, heads.next()).await {
+            Ok(Some(())) | Err(_) => Some(((), Some(heads))),
+            Ok(None) => {
+                warn!("The subscription to new blocks was closed, polling for them instead");
+                tokio::time::sleep(block_time).await;
+                Some(((), None))
+            }
+        }
+    })
+}
+
+/// Yields the block numbers from `start_block` onwards as they are mined.
+/// Once every mined block was yielded, the next tick is awaited before checking
+/// the latest block number again; the stream ends along with the ticks.
+fn follow_from<F, Fut>(
+    start_block: u64,
+    mut latest_block_number: F,
+    ticks: impl Stream<Item = ()>,
+) -> impl Stream<Item = Result<u64, anyhow::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<u64, anyhow::Error>>,
+{
+    try_stream! {
+        let mut ticks = Box::pin(ticks);
+        let mut current = start_block;
+        loop {
+            let last_block_number = latest_block_number().await?;
+            while current <= last_block_number {
+                yield current;
+                current += 1;
+            }
+
+            info!("Waiting for the new blocks to be mined, requested block number: {current}, \
+            latest block number: {last_block_number}");
+            // Check the latest block number again on every tick, as the
+            // notifications of new heads may skip blocks
+            if ticks.next().await.is_none() {
+                break;
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for BlockInterval {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -177,6 +236,7 @@ impl std::str::FromStr for BlockInterval {
 #[cfg(test)]
 mod test {
     use alloy::primitives::B256;
+    use futures::StreamExt;
 
     use super::*;
 
@@ -261,6 +321,101 @@ mod test {
         assert_eq!(result, Vec::from_iter(1u64..10u64));
     }
 
+    /// Follows the chain from `start_block`, the chain tip being read from
+    /// `tips` and the ticks ending after `ticks` waits
+    async fn follow(start_block: u64, tips: Vec<u64>, ticks: usize) -> Vec<u64> {
+        let mut tips = tips.into_iter();
+        let latest_block_number = move || {
+            let tip = tips.next();
+            async move { tip.ok_or(anyhow!("no more chain tips")) }
+        };
+        follow_from(
+            start_block,
+            latest_block_number,
+            futures::stream::repeat(()).take(ticks),
+        )
+        .map(Result::unwrap)
+        .collect()
+        .await
+    }
+
+    #[tokio::test]
+    async fn follow_from_starts_with_the_start_block() {
+        assert_eq!(follow(5, vec![6, 6, 8, 8], 3).await, vec![5, 6, 7, 8]);
+    }
+
+    #[tokio::test]
+    async fn follow_from_waits_for_the_start_block() {
+        assert_eq!(follow(10, vec![8, 9, 10, 10], 2).await, vec![10]);
+    }
+
+    #[tokio::test]
+    async fn follow_from_fails_with_the_provider() {
+        let mut stream = Box::pin(follow_from(
+            5,
+            || async { Err(anyhow!("connection refused")) },
+            futures::stream::repeat(()),
+        ));
+        assert!(stream.next().await.unwrap().is_err());
+    }
+
+    /// Returns how many milliseconds each of the first `count` ticks took
+    async fn tick_times(ticks: impl Stream<Item = ()>, count: usize) -> Vec<u128> {
+        let mut ticks = Box::pin(ticks);
+        let mut times = Vec::new();
+        for _ in 0..count {
+            let start = tokio::time::Instant::now();
+            ticks.next().await.unwrap();
+            times.push(start.elapsed().as_millis());
+        }
+        times
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks_on_every_new_head() {
+        let new_heads = futures::stream::iter([(), ()]);
+        let times = tick_times(ticks(Some(new_heads), Duration::from_secs(2)), 4).await;
+        // Once the new heads end, the node is polled every block time
+        assert_eq!(times, vec![0, 0, 2000, 2000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks_after_a_block_time_without_new_heads() {
+        let new_heads = futures::stream::pending::<()>();
+        let times = tick_times(ticks(Some(new_heads), Duration::from_secs(2)), 3).await;
+        assert_eq!(times, vec![2000, 2000, 2000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks_every_block_time_without_subscription() {
+        let new_heads = None::<BoxStream<'static, ()>>;
+        let times = tick_times(ticks(new_heads, Duration::from_secs(2)), 3).await;
+        assert_eq!(times, vec![2000, 2000, 2000]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follow_from_checks_the_chain_tip_on_every_new_head() {
+        let (heads, new_heads) = futures::channel::mpsc::unbounded();
+        let mut tips = vec![5, 5, 7].into_iter();
+        let latest_block_number = move || {
+            let tip = tips.next();
+            async move { tip.ok_or(anyhow!("no more chain tips")) }
+        };
+        let mut stream = Box::pin(follow_from(
+            5,
+            latest_block_number,
+            ticks(Some(new_heads), Duration::from_secs(2)),
+        ));
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), 5);
+        heads.unbounded_send(()).unwrap();
+        heads.unbounded_send(()).unwrap();
+        let start = tokio::time::Instant::now();
+        assert_eq!(stream.next().await.unwrap().unwrap(), 6);
+        assert_eq!(stream.next().await.unwrap().unwrap(), 7);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
     #[test]
     fn can_create_from_string() {
         use std::str::FromStr;
@@ -0,0 +1,275 @@
+//! Classification of the failures to prove a block, and the policies deciding
+//! which of them are retried.
+//!
+//! Errors are tagged with their [`FailureClass`] where they are raised (see
+//! [`FailureClass::tag`]), as a message of their context, so that the class
+//! survives the errors of the remote workers being sent back as text. Errors
+//! without a tag were raised by the runtime rather than by an operation, i.e.
+//! when a worker was lost in the middle of a task.
+use std::{fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// The prefix of the message tagging an error with its [`FailureClass`].
+const TAG_PREFIX: &str = "failure class: ";
+/// The prefix of the message tagging an error with the index of the failing
+/// transaction within its block.
+const TXN_TAG_PREFIX: &str = "failing txn: ";
+
+/// Tags the error with the index of the failing transaction within its block,
+/// see [`txn_index_of`].
+pub fn tag_txn_index(err: anyhow::Error, txn_index: usize) -> anyhow::Error {
+    err.context(format!("{TXN_TAG_PREFIX}{txn_index}"))
+}
+
+/// Returns the index of the failing transaction the error was tagged with, if
+/// any.
+pub fn txn_index_of(err: &anyhow::Error) -> Option<usize> {
+    let message = format!("{err:#}");
+    let (_, tagged) = message.split_once(TXN_TAG_PREFIX)?;
+    let digits = tagged
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(tagged, |end| &tagged[..end]);
+    digits.parse().ok()
+}
+
+/// The class of a failure to prove a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The block could not be fetched (i.e. the RPC failed).
+    Fetch,
+    /// The trace of the block could not be decoded into the inputs of its
+    /// transactions.
+    TraceDecoding,
+    /// The witness of a transaction could not be generated, i.e. its execution
+    /// failed.
+    WitnessGeneration,
+    /// A transaction, aggregation or block proof could not be generated.
+    Proving,
+    /// The runtime failed rather than an operation, i.e. a worker was lost.
+    WorkerLost,
+}
+
+impl FailureClass {
+    /// Every [`FailureClass`].
+    pub const ALL: [FailureClass; 5] = [
+        FailureClass::Fetch,
+        FailureClass::TraceDecoding,
+        FailureClass::WitnessGeneration,
+        FailureClass::Proving,
+        FailureClass::WorkerLost,
+    ];
+
+    /// Get the class name as a str literal.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            FailureClass::Fetch => "fetch",
+            FailureClass::TraceDecoding => "trace_decoding",
+            FailureClass::WitnessGeneration => "witness_generation",
+            FailureClass::Proving => "proving",
+            FailureClass::WorkerLost => "worker_lost",
+        }
+    }
+
+    /// Tags the error with the class, see [`FailureClass::of`].
+    pub fn tag(self, err: anyhow::Error) -> anyhow::Error {
+        err.context(format!("{TAG_PREFIX}{}", self.as_str()))
+    }
+
+    /// Returns the class the error was tagged with, or
+    /// [`FailureClass::WorkerLost`] if it was not tagged. When tagged more
+    /// than once, the outermost tag wins.
+    pub fn of(err: &anyhow::Error) -> Self {
+        let message = format!("{err:#}");
+        Self::ALL
+            .into_iter()
+            .filter_map(|class| {
+                message
+                    .find(&format!("{TAG_PREFIX}{}", class.as_str()))
+                    .map(|position| (position, class))
+            })
+            .min_by_key(|(position, _)| *position)
+            .map(|(_, class)| class)
+            .unwrap_or(FailureClass::WorkerLost)
+    }
+}
+
+impl Display for FailureClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// What to do with a block failing with a given [`FailureClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// The number of times the block is attempted again.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// The delay before each retry, in milliseconds.
+    #[serde(default)]
+    pub backoff_ms: u64,
+    /// Whether the block is skipped once the retries are exhausted, the
+    /// following blocks still being proven, rather than failing the whole
+    /// run.
+    #[serde(default)]
+    pub skip: bool,
+}
+
+impl RetryPolicy {
+    /// Fails on the first failure.
+    pub const FAIL: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        backoff_ms: 0,
+        skip: false,
+    };
+
+    /// Skips the block on the first failure.
+    pub const SKIP: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        backoff_ms: 0,
+        skip: true,
+    };
+
+    /// Returns the delay before each retry.
+    pub const fn backoff(&self) -> Duration {
+        Duration::from_millis(self.backoff_ms)
+    }
+}
+
+/// The [`RetryPolicy`] of each [`FailureClass`].
+///
+/// By default, lost workers and failed proofs are retried, while the blocks
+/// failing deterministically (i.e. their trace cannot be decoded or a
+/// transaction cannot be executed) are skipped straight away, so that a bad
+/// block does not fail the whole run.  A block which could not be fetched
+/// fails the run, as the following blocks are likely to fail as well.  The
+/// requests of a block failing to be fetched
+/// are retried by the RPC provider rather than by the [`RetryPolicy`] of
+/// [`FailureClass::Fetch`], which only decides whether the block is skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicies {
+    pub fetch: RetryPolicy,
+    pub trace_decoding: RetryPolicy,
+    pub witness_generation: RetryPolicy,
+    pub proving: RetryPolicy,
+    pub worker_lost: RetryPolicy,
+}
+
+impl Default for RetryPolicies {
+    fn default() -> Self {
+        Self {
+            fetch: RetryPolicy::FAIL,
+            trace_decoding: RetryPolicy::SKIP,
+            witness_generation: RetryPolicy::SKIP,
+            proving: RetryPolicy {
+                max_retries: 1,
+                backoff_ms: 0,
+                skip: false,
+            },
+            worker_lost: RetryPolicy {
+                max_retries: 3,
+                backoff_ms: 1000,
+                skip: false,
+            },
+        }
+    }
+}
+
+impl RetryPolicies {
+    /// Fails on the first failure of any class.
+    pub const FAIL: RetryPolicies = RetryPolicies {
+        fetch: RetryPolicy::FAIL,
+        trace_decoding: RetryPolicy::FAIL,
+        witness_generation: RetryPolicy::FAIL,
+        proving: RetryPolicy::FAIL,
+        worker_lost: RetryPolicy::FAIL,
+    };
+
+    /// Returns the policy of the given class.
+    pub const fn get(&self, class: FailureClass) -> &RetryPolicy {
+        match class {
+            FailureClass::Fetch => &self.fetch,
+            FailureClass::TraceDecoding => &self.trace_decoding,
+            FailureClass::WitnessGeneration => &self.witness_generation,
+            FailureClass::Proving => &self.proving,
+            FailureClass::WorkerLost => &self.worker_lost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn classifies_tagged_errors() {
+        for class in FailureClass::ALL {
+            let err = class.tag(anyhow!("boom"));
+            assert_eq!(FailureClass::of(&err), class);
+        }
+    }
+
+    #[test]
+    fn untagged_errors_are_lost_workers() {
+        assert_eq!(
+            FailureClass::of(&anyhow!("channel closed")),
+            FailureClass::WorkerLost
+        );
+    }
+
+    #[test]
+    fn classifies_errors_sent_as_text() {
+        let remote = FailureClass::WitnessGeneration.tag(anyhow!("stack underflow"));
+        let err = anyhow!("task failed: {remote:#}").context("directive failed");
+        assert_eq!(FailureClass::of(&err), FailureClass::WitnessGeneration);
+    }
+
+    #[test]
+    fn outermost_tag_wins() {
+        let err = FailureClass::Proving.tag(FailureClass::TraceDecoding.tag(anyhow!("boom")));
+        assert_eq!(FailureClass::of(&err), FailureClass::Proving);
+    }
+
+    #[test]
+    fn finds_failing_txn_index() {
+        let remote = tag_txn_index(FailureClass::Proving.tag(anyhow!("boom")), 12);
+        let err = anyhow!("task failed: {remote:#}");
+        assert_eq!(txn_index_of(&err), Some(12));
+        assert_eq!(FailureClass::of(&err), FailureClass::Proving);
+        assert_eq!(txn_index_of(&anyhow!("boom")), None);
+    }
+
+    #[test]
+    fn policies_default_to_defaults() {
+        let policies: RetryPolicies =
+            serde_json::from_str(r#"{"proving": {"max_retries": 2}}"#).unwrap();
+        assert_eq!(
+            policies.proving,
+            RetryPolicy {
+                max_retries: 2,
+                backoff_ms: 0,
+                skip: false,
+            }
+        );
+        assert_eq!(policies.fetch, RetryPolicies::default().fetch);
+    }
+
+    #[test]
+    fn skips_the_deterministic_failures_by_default() {
+        let policies = RetryPolicies::default();
+        for class in FailureClass::ALL {
+            let deterministic = matches!(
+                class,
+                FailureClass::TraceDecoding | FailureClass::WitnessGeneration
+            );
+            assert_eq!(policies.get(class).skip, deterministic, "{class}");
+        }
+        assert_eq!(policies.trace_decoding.max_retries, 0);
+        assert_eq!(policies.witness_generation.max_retries, 0);
+    }
+}
@@ -1,5 +1,9 @@
+pub mod auth;
 pub mod block_interval;
 pub mod debug_utils;
+pub mod failure;
 pub mod fs;
+pub mod metrics;
 pub mod parsing;
+pub mod proof_format;
 pub mod prover_state;
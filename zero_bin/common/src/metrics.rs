@@ -0,0 +1,200 @@
+//! Prometheus metrics shared by the coordinator, the leader and the worker.
+//!
+//! Every metric is registered to the process-wide [`REGISTRY`], which is
+//! rendered in the Prometheus text format by [`encode`]. Processes which do not
+//! have an HTTP server of their own can expose the metrics with [`serve`].
+//!
+//! Metrics are recorded by the process doing the work: the transaction and
+//! aggregation proof latencies are recorded by the workers (or by the leader
+//! when running in-memory), while block and fetch metrics are recorded by the
+//! leader or coordinator.
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::Result;
+use axum::{routing::get, Router};
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use tracing::{debug, error};
+
+use crate::failure::FailureClass;
+
+/// The prefix of every metric name.
+const NAMESPACE: &str = "zero_bin";
+
+/// The registry holding every metric of the process.
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+/// The number of blocks proved.
+pub static BLOCKS_PROVED: Lazy<IntCounter> = Lazy::new(|| {
+    register(IntCounter::with_opts(
+        Opts::new("blocks_proved_total", "Number of blocks proved").namespace(NAMESPACE),
+    ))
+});
+
+/// The time taken to prove a block, from its preparation to its final
+/// aggregation.
+pub static BLOCK_PROOF_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register(Histogram::with_opts(
+        HistogramOpts::new(
+            "block_proof_duration_seconds",
+            "Time taken to prove a block, including the transaction proofs and aggregations",
+        )
+        .namespace(NAMESPACE)
+        .buckets(exponential_buckets(1.0, 2.0, 14).expect("valid buckets")),
+    ))
+});
+
+/// The time taken to prove a single transaction.
+pub static TXN_PROOF_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register(Histogram::with_opts(
+        HistogramOpts::new(
+            "txn_proof_duration_seconds",
+            "Time taken to prove a single transaction",
+        )
+        .namespace(NAMESPACE)
+        .buckets(exponential_buckets(0.5, 2.0, 12).expect("valid buckets")),
+    ))
+});
+
+/// The time taken by a single aggregation, labelled by the `kind` of
+/// aggregation: `agg` for the aggregation of two transaction or aggregation
+/// proofs and `block` for the block proof.
+pub static AGG_PROOF_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "agg_proof_duration_seconds",
+            "Time taken by a single aggregation proof",
+        )
+        .namespace(NAMESPACE)
+        .buckets(exponential_buckets(0.5, 2.0, 12).expect("valid buckets")),
+        &["kind"],
+    ))
+});
+
+/// The time taken to fetch the prover input of a block.
+pub static FETCH_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register(Histogram::with_opts(
+        HistogramOpts::new(
+            "fetch_duration_seconds",
+            "Time taken to fetch the prover input of a block",
+        )
+        .namespace(NAMESPACE)
+        .buckets(exponential_buckets(0.05, 2.0, 12).expect("valid buckets")),
+    ))
+});
+
+/// The number of failures, labelled by the `kind` of error.
+pub static FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("failures_total", "Number of failures by kind of error").namespace(NAMESPACE),
+        &["kind"],
+    ))
+});
+
+/// The number of times a block was attempted again, labelled by the `class` of
+/// the failure (see [`crate::failure::FailureClass`]).
+pub static BLOCK_RETRIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "block_retries_total",
+            "Number of block retries by class of failure",
+        )
+        .namespace(NAMESPACE),
+        &["class"],
+    ))
+});
+
+/// The number of jobs waiting to be started.
+pub static QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register(IntGauge::with_opts(
+        Opts::new("queue_depth", "Number of jobs waiting to be started").namespace(NAMESPACE),
+    ))
+});
+
+/// The time taken to load (or generate) the circuits, labelled by what was
+/// loaded: `prover_state`, `verifier_state` or `table_circuits`.
+pub static CIRCUIT_LOAD_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "circuit_load_duration_seconds",
+            "Time taken to load or generate the circuits",
+        )
+        .namespace(NAMESPACE)
+        .buckets(exponential_buckets(0.01, 4.0, 12).expect("valid buckets")),
+        &["kind"],
+    ))
+});
+
+/// Registers the metric to the [`REGISTRY`].
+///
+/// Panics if the metric options are invalid, or if a metric of the same name
+/// was already registered, both of which are programming errors.
+fn register<M>(metric: prometheus::Result<M>) -> M
+where
+    M: prometheus::core::Collector + Clone + 'static,
+{
+    let metric = metric.expect("valid metric");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric registered once");
+    metric
+}
+
+/// Registers every metric, so that they are exposed before first being
+/// recorded.
+pub fn init() {
+    Lazy::force(&BLOCKS_PROVED);
+    Lazy::force(&BLOCK_PROOF_DURATION);
+    Lazy::force(&TXN_PROOF_DURATION);
+    Lazy::force(&AGG_PROOF_DURATION);
+    Lazy::force(&FETCH_DURATION);
+    Lazy::force(&FAILURES);
+    Lazy::force(&BLOCK_RETRIES);
+    Lazy::force(&QUEUE_DEPTH);
+    Lazy::force(&CIRCUIT_LOAD_DURATION);
+}
+
+/// Records a failure of the given `kind`.
+pub fn record_failure(kind: &str) {
+    FAILURES.with_label_values(&[kind]).inc();
+}
+
+/// Records a block being attempted again after a failure of the given class.
+pub fn record_retry(class: FailureClass) {
+    BLOCK_RETRIES.with_label_values(&[class.as_str()]).inc();
+}
+
+/// Records the completion of a block proof which took `duration`.
+pub fn record_block_proved(duration: Duration) {
+    BLOCKS_PROVED.inc();
+    BLOCK_PROOF_DURATION.observe(duration.as_secs_f64());
+}
+
+/// Returns every metric of the [`REGISTRY`] in the Prometheus text format.
+pub fn encode() -> Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// The axum handler of the `/metrics` route.
+pub async fn metrics_handler() -> (axum::http::StatusCode, String) {
+    match encode() {
+        Ok(metrics) => (axum::http::StatusCode::OK, metrics),
+        Err(err) => {
+            error!("Failed to encode the metrics: {:?}", err);
+            (axum::http::StatusCode::INTERNAL_SERVER_ERROR, String::new())
+        }
+    }
+}
+
+/// Serves the metrics at `http://{addr}/metrics` until the process exits.
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    debug!("serving metrics on {}", addr);
+    let app = Router::new().route("/metrics", get(metrics_handler));
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    Ok(axum::serve(listener, app).await?)
+}
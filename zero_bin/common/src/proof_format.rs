@@ -0,0 +1,356 @@
+//! A versioned binary envelope for [`GeneratedBlockProof`]s and
+//! [`GeneratedAggProof`]s.
+//!
+//! JSON proofs are large and slow to parse, so proofs may instead be written
+//! as plonky2's proof bytes wrapped in an envelope:
+//!
+//! | bytes    | content                                                  |
+//! |----------|----------------------------------------------------------|
+//! | 4        | magic, `ZBPF`                                            |
+//! | 1        | format version                                           |
+//! | 1        | proof kind (1 for block proofs, 2 for agg proofs)        |
+//! | 1        | flags (bit 0 set if the proof bytes are zstd-compressed) |
+//! | 1        | reserved                                                 |
+//! | 8        | block height, little-endian                              |
+//! | 32       | SHA-256 digest of the circuit configuration              |
+//! | 4        | metadata length, little-endian                           |
+//! | 8        | proof bytes length, little-endian                        |
+//! | variable | metadata (JSON public values for agg proofs)             |
+//! | variable | proof bytes                                              |
+//!
+//! Several envelopes may be concatenated in a single file.  Readers detect
+//! whether a file holds JSON or binary proofs from its first bytes.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use clap::ValueEnum;
+use evm_arithmetization::proof::PublicValues;
+use plonky2::plonk::circuit_data::CommonCircuitData;
+use proof_gen::{
+    proof_types::{GeneratedAggProof, GeneratedBlockProof},
+    types::{Field, PlonkyProofIntern, EXTENSION_DEGREE},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::prover_state::circuit::CircuitConfig;
+
+/// The magic bytes starting every proof envelope.
+pub const PROOF_ENVELOPE_MAGIC: [u8; 4] = *b"ZBPF";
+/// The current version of the proof envelope.
+pub const PROOF_ENVELOPE_VERSION: u8 = 1;
+/// The zstd compression level used for compressed proofs.
+const ZSTD_LEVEL: i32 = 3;
+/// The flag marking zstd-compressed proof bytes.
+const FLAG_ZSTD: u8 = 0b1;
+/// The length of the fixed-size part of the envelope.
+const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 1 + 8 + 32 + 4 + 8;
+
+/// The serialization format of the proofs written to disk.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofFormat {
+    /// Proofs serialized as JSON.
+    #[default]
+    Json,
+    /// Proofs serialized as plonky2 proof bytes within a binary envelope.
+    Binary,
+    /// Like [`ProofFormat::Binary`], with zstd-compressed proof bytes.
+    BinaryZstd,
+}
+
+/// The kind of proof held by an envelope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ProofKind {
+    Block = 1,
+    Agg = 2,
+}
+
+impl TryFrom<u8> for ProofKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Block),
+            2 => Ok(Self::Agg),
+            _ => Err(anyhow!("unknown proof kind {value}")),
+        }
+    }
+}
+
+/// The header of a proof envelope.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofHeader {
+    pub version: u8,
+    pub kind: ProofKind,
+    pub compressed: bool,
+    pub block_height: u64,
+    pub circuit_config_digest: [u8; 32],
+}
+
+/// Returns the digest identifying the circuits a proof was generated with.
+pub fn circuit_config_digest(circuit_config: &CircuitConfig) -> [u8; 32] {
+    Sha256::digest(circuit_config.get_configuration_digest().as_bytes()).into()
+}
+
+/// Returns true if the bytes start with a proof envelope rather than JSON.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(&PROOF_ENVELOPE_MAGIC)
+}
+
+/// Encodes proofs in a given [`ProofFormat`] for a given circuit
+/// configuration.
+///
+/// The default encoder writes JSON.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProofEncoder {
+    pub format: ProofFormat,
+    pub circuit_config_digest: [u8; 32],
+}
+
+impl ProofEncoder {
+    pub fn new(format: ProofFormat, circuit_config: &CircuitConfig) -> Self {
+        Self {
+            format,
+            circuit_config_digest: circuit_config_digest(circuit_config),
+        }
+    }
+
+    /// Encodes a block proof.
+    pub fn encode_block_proof(&self, proof: &GeneratedBlockProof) -> Result<Vec<u8>> {
+        match self.format {
+            ProofFormat::Json => Ok(serde_json::to_vec(proof)?),
+            ProofFormat::Binary | ProofFormat::BinaryZstd => {
+                self.envelope(ProofKind::Block, proof.b_height, &[], &proof.intern)
+            }
+        }
+    }
+
+    /// Encodes an aggregation proof. Its public values are stored as metadata.
+    pub fn encode_agg_proof(&self, proof: &GeneratedAggProof) -> Result<Vec<u8>> {
+        match self.format {
+            ProofFormat::Json => Ok(serde_json::to_vec(proof)?),
+            ProofFormat::Binary | ProofFormat::BinaryZstd => self.envelope(
+                ProofKind::Agg,
+                proof.p_vals.block_metadata.block_number.low_u64(),
+                &serde_json::to_vec(&proof.p_vals)?,
+                &proof.intern,
+            ),
+        }
+    }
+
+    fn envelope(
+        &self,
+        kind: ProofKind,
+        block_height: u64,
+        metadata: &[u8],
+        intern: &PlonkyProofIntern,
+    ) -> Result<Vec<u8>> {
+        let header = ProofHeader {
+            version: PROOF_ENVELOPE_VERSION,
+            kind,
+            compressed: self.format == ProofFormat::BinaryZstd,
+            block_height,
+            circuit_config_digest: self.circuit_config_digest,
+        };
+        write_envelope(&header, metadata, &intern.to_bytes())
+    }
+}
+
+/// Decodes the block proofs in `bytes`, either a JSON proof, a JSON list of
+/// proofs or one or more binary envelopes.
+///
+/// Binary proofs are deserialized against the `common` circuit data of the
+/// block circuit. If `circuit_config` is provided, binary proofs generated
+/// with a different circuit configuration are rejected.
+pub fn decode_block_proofs(
+    bytes: &[u8],
+    common: &CommonCircuitData<Field, EXTENSION_DEGREE>,
+    circuit_config: Option<&CircuitConfig>,
+) -> Result<Vec<GeneratedBlockProof>> {
+    if !is_binary(bytes) {
+        return match serde_json::from_slice::<Vec<GeneratedBlockProof>>(bytes) {
+            Ok(proofs) => Ok(proofs),
+            Err(_) => Ok(vec![serde_json::from_slice::<GeneratedBlockProof>(bytes)
+                .context("invalid JSON block proof")?]),
+        };
+    }
+
+    let mut proofs = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (header, _, proof_bytes, remaining) = read_envelope(rest)?;
+        ensure!(
+            header.kind == ProofKind::Block,
+            "expected a block proof, found {:?} proof",
+            header.kind
+        );
+        check_circuit_config(&header, circuit_config)?;
+        proofs.push(GeneratedBlockProof {
+            b_height: header.block_height,
+            intern: PlonkyProofIntern::from_bytes(proof_bytes, common)?,
+        });
+        rest = remaining;
+    }
+    Ok(proofs)
+}
+
+/// Decodes an aggregation proof, either as JSON or as a binary envelope.
+///
+/// Binary proofs are deserialized against the `common` circuit data of the
+/// aggregation circuit.
+pub fn decode_agg_proof(
+    bytes: &[u8],
+    common: &CommonCircuitData<Field, EXTENSION_DEGREE>,
+    circuit_config: Option<&CircuitConfig>,
+) -> Result<GeneratedAggProof> {
+    if !is_binary(bytes) {
+        return serde_json::from_slice(bytes).context("invalid JSON aggregation proof");
+    }
+
+    let (header, metadata, proof_bytes, _) = read_envelope(bytes)?;
+    ensure!(
+        header.kind == ProofKind::Agg,
+        "expected an aggregation proof, found {:?} proof",
+        header.kind
+    );
+    check_circuit_config(&header, circuit_config)?;
+    let p_vals: PublicValues =
+        serde_json::from_slice(&metadata).context("invalid aggregation proof public values")?;
+    Ok(GeneratedAggProof {
+        p_vals,
+        intern: PlonkyProofIntern::from_bytes(proof_bytes, common)?,
+    })
+}
+
+fn check_circuit_config(
+    header: &ProofHeader,
+    circuit_config: Option<&CircuitConfig>,
+) -> Result<()> {
+    if let Some(circuit_config) = circuit_config {
+        ensure!(
+            header.circuit_config_digest == circuit_config_digest(circuit_config),
+            "proof for block {} was generated with a different circuit configuration",
+            header.block_height
+        );
+    }
+    Ok(())
+}
+
+fn write_envelope(header: &ProofHeader, metadata: &[u8], proof_bytes: &[u8]) -> Result<Vec<u8>> {
+    let proof_bytes = match header.compressed {
+        true => zstd::encode_all(proof_bytes, ZSTD_LEVEL)?,
+        false => proof_bytes.to_vec(),
+    };
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + metadata.len() + proof_bytes.len());
+    bytes.extend_from_slice(&PROOF_ENVELOPE_MAGIC);
+    bytes.push(header.version);
+    bytes.push(header.kind as u8);
+    bytes.push(if header.compressed { FLAG_ZSTD } else { 0 });
+    bytes.push(0);
+    bytes.extend_from_slice(&header.block_height.to_le_bytes());
+    bytes.extend_from_slice(&header.circuit_config_digest);
+    bytes.extend_from_slice(&u32::try_from(metadata.len())?.to_le_bytes());
+    bytes.extend_from_slice(&(proof_bytes.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(metadata);
+    bytes.extend_from_slice(&proof_bytes);
+    Ok(bytes)
+}
+
+/// Reads an envelope, returning its header, metadata, (decompressed) proof
+/// bytes and the bytes following the envelope.
+fn read_envelope(bytes: &[u8]) -> Result<(ProofHeader, Vec<u8>, Vec<u8>, &[u8])> {
+    ensure!(is_binary(bytes), "missing proof envelope magic bytes");
+    ensure!(bytes.len() >= HEADER_LEN, "truncated proof envelope header");
+
+    let version = bytes[4];
+    if version != PROOF_ENVELOPE_VERSION {
+        bail!("unsupported proof envelope version {version}");
+    }
+    let kind = ProofKind::try_from(bytes[5])?;
+    let compressed = bytes[6] & FLAG_ZSTD != 0;
+    let block_height = u64::from_le_bytes(bytes[8..16].try_into()?);
+    let circuit_config_digest: [u8; 32] = bytes[16..48].try_into()?;
+    let metadata_len = u32::from_le_bytes(bytes[48..52].try_into()?) as usize;
+    let proof_len = usize::try_from(u64::from_le_bytes(bytes[52..60].try_into()?))?;
+
+    let rest = &bytes[HEADER_LEN..];
+    ensure!(
+        rest.len() >= metadata_len.saturating_add(proof_len),
+        "truncated proof envelope for block {block_height}"
+    );
+    let (metadata, rest) = rest.split_at(metadata_len);
+    let (proof_bytes, rest) = rest.split_at(proof_len);
+
+    let proof_bytes = match compressed {
+        true => zstd::decode_all(proof_bytes)?,
+        false => proof_bytes.to_vec(),
+    };
+
+    Ok((
+        ProofHeader {
+            version,
+            kind,
+            compressed,
+            block_height,
+            circuit_config_digest,
+        },
+        metadata.to_vec(),
+        proof_bytes,
+        rest,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(compressed: bool) -> ProofHeader {
+        ProofHeader {
+            version: PROOF_ENVELOPE_VERSION,
+            kind: ProofKind::Agg,
+            compressed,
+            block_height: 42,
+            circuit_config_digest: [7; 32],
+        }
+    }
+
+    #[test]
+    fn can_read_written_envelope() {
+        for compressed in [false, true] {
+            let bytes = write_envelope(&header(compressed), b"meta", &[1, 2, 3, 3, 3]).unwrap();
+            assert!(is_binary(&bytes));
+
+            let (read_header, metadata, proof_bytes, rest) = read_envelope(&bytes).unwrap();
+            assert_eq!(read_header, header(compressed));
+            assert_eq!(metadata, b"meta");
+            assert_eq!(proof_bytes, vec![1, 2, 3, 3, 3]);
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn can_read_concatenated_envelopes() {
+        let mut bytes = write_envelope(&header(false), &[], &[1]).unwrap();
+        bytes.extend(write_envelope(&header(true), &[], &[2]).unwrap());
+
+        let (_, _, first, rest) = read_envelope(&bytes).unwrap();
+        let (_, _, second, rest) = read_envelope(rest).unwrap();
+        assert_eq!((first, second), (vec![1], vec![2]));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_envelopes() {
+        let bytes = write_envelope(&header(false), &[], &[1, 2, 3]).unwrap();
+        assert!(read_envelope(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read_envelope(&bytes[..HEADER_LEN - 1]).is_err());
+
+        let mut unsupported = bytes.clone();
+        unsupported[4] = PROOF_ENVELOPE_VERSION + 1;
+        assert!(read_envelope(&unsupported).is_err());
+
+        assert!(!is_binary(b"[{\"b_height\":1}]"));
+    }
+}
@@ -53,7 +53,7 @@ impl Display for CircuitPersistence {
 /// Macro for generating the [`CliCircuitConfig`] struct.
 macro_rules! gen_prover_state_config {
     ($($name:ident: $circuit:expr),*) => {
-        #[derive(Args, Debug)]
+        #[derive(Args, Debug, Clone)]
         pub struct CliProverStateConfig {
             #[clap(long, help_heading = HEADING, default_value_t = CircuitPersistence::Disk)]
             pub persistence: CircuitPersistence,
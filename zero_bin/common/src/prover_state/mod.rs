@@ -11,11 +11,12 @@
 //!   [`evm_arithmetization::fixed_recursive_verifier::AllRecursiveCircuits`].
 //! - Global prover state management via the [`P_STATE`] static and the
 //!   [`set_prover_state_from_config`] function.
-use std::{fmt::Display, sync::OnceLock};
+use std::{fmt::Display, sync::OnceLock, time::Instant};
 
 use clap::ValueEnum;
 use evm_arithmetization::{
-    proof::AllProof, prover::prove, AllStark, GenerationInputs, StarkConfig,
+    generation::generate_traces, proof::AllProof, prover::prove_with_traces, AllStark,
+    GenerationInputs, StarkConfig,
 };
 use plonky2::{
     field::goldilocks_field::GoldilocksField, plonk::config::PoseidonGoldilocksConfig,
@@ -25,6 +26,8 @@ use proof_gen::{proof_types::GeneratedTxnProof, prover_state::ProverState, Verif
 use tracing::info;
 
 use self::circuit::{CircuitConfig, NUM_TABLES};
+use crate::failure::FailureClass;
+use crate::metrics::CIRCUIT_LOAD_DURATION;
 use crate::prover_state::persistence::{
     BaseProverResource, DiskResource, MonolithicProverResource, RecursiveCircuitResource,
     VerifierResource,
@@ -76,6 +79,17 @@ pub fn p_manager() -> &'static ProverStateManager {
         .expect("Prover state manager is not initialized")
 }
 
+/// The sizes of the STARK traces of a transaction proof.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceSizes {
+    /// The degree bits (i.e. the log2 of the trace length) of each STARK
+    /// table, indexed by [`circuit::Circuit`]
+    pub degree_bits: [usize; NUM_TABLES],
+    /// The number of CPU cycles run by the transaction, i.e. the length of the
+    /// CPU trace before it is padded to a power of two
+    pub cpu_cycles: usize,
+}
+
 /// Specifies how to load the table circuits.
 #[derive(Debug, Clone, Copy, Default, ValueEnum)]
 pub enum TableLoadStrategy {
@@ -185,36 +199,73 @@ impl ProverStateManager {
         ])
     }
 
+    /// Generate the STARK proofs of a transaction along with the number of CPU
+    /// cycles it ran, tagging the failure to generate its witness with
+    /// [`FailureClass::WitnessGeneration`] and the failure to prove it with
+    /// [`FailureClass::Proving`].
+    fn prove_all_stark(
+        all_stark: &AllStark<Field, SIZE>,
+        config: &StarkConfig,
+        input: GenerationInputs,
+    ) -> anyhow::Result<(AllProof<Field, Config, SIZE>, usize)> {
+        let mut timing = TimingTree::default();
+        let (traces, public_values, cpu_cycles) =
+            generate_traces(all_stark, input, config, &mut timing)
+                .map_err(|err| FailureClass::WitnessGeneration.tag(err))?;
+        let all_proof =
+            prove_with_traces(all_stark, config, traces, public_values, &mut timing, None)
+                .map_err(|err| FailureClass::Proving.tag(err))?;
+        Ok((all_proof, cpu_cycles))
+    }
+
     /// Generate a transaction proof using the specified input, loading the
     /// circuit tables as needed to shrink the individual STARK proofs, and
     /// finally aggregating them to a final transaction proof.
-    fn txn_proof_on_demand(&self, input: GenerationInputs) -> anyhow::Result<GeneratedTxnProof> {
+    fn txn_proof_on_demand(
+        &self,
+        input: GenerationInputs,
+    ) -> anyhow::Result<(GeneratedTxnProof, TraceSizes)> {
         let config = StarkConfig::standard_fast_config();
         let all_stark = AllStark::default();
-        let all_proof = prove(&all_stark, &config, input, &mut TimingTree::default(), None)?;
+        let (all_proof, cpu_cycles) = Self::prove_all_stark(&all_stark, &config, input)?;
+        let trace_sizes = TraceSizes {
+            degree_bits: all_proof.degree_bits(&config),
+            cpu_cycles,
+        };
 
+        let load_start = Instant::now();
         let table_circuits = self.load_table_circuits(&config, &all_proof)?;
+        CIRCUIT_LOAD_DURATION
+            .with_label_values(&["table_circuits"])
+            .observe(load_start.elapsed().as_secs_f64());
 
         let (intern, p_vals) =
             p_state()
                 .state
                 .prove_root_after_initial_stark(all_proof, &table_circuits, None)?;
 
-        Ok(GeneratedTxnProof { intern, p_vals })
+        Ok((GeneratedTxnProof { intern, p_vals }, trace_sizes))
     }
 
     /// Generate a transaction proof using the specified input on the monolithic
     /// circuit.
-    fn txn_proof_monolithic(&self, input: GenerationInputs) -> anyhow::Result<GeneratedTxnProof> {
-        let (intern, p_vals) = p_state().state.prove_root(
-            &AllStark::default(),
-            &StarkConfig::standard_fast_config(),
-            input,
-            &mut TimingTree::default(),
-            None,
-        )?;
-
-        Ok(GeneratedTxnProof { p_vals, intern })
+    fn txn_proof_monolithic(
+        &self,
+        input: GenerationInputs,
+    ) -> anyhow::Result<(GeneratedTxnProof, TraceSizes)> {
+        let config = StarkConfig::standard_fast_config();
+        let all_stark = AllStark::default();
+        let (all_proof, cpu_cycles) = Self::prove_all_stark(&all_stark, &config, input)?;
+        let trace_sizes = TraceSizes {
+            degree_bits: all_proof.degree_bits(&config),
+            cpu_cycles,
+        };
+
+        let (intern, p_vals) = p_state()
+            .state
+            .prove_root_from_all_proof(all_proof, &config, None)?;
+
+        Ok((GeneratedTxnProof { p_vals, intern }, trace_sizes))
     }
 
     /// Generate a transaction proof using the specified input.
@@ -227,6 +278,16 @@ impl ProverStateManager {
     ///   [`TableLoadStrategy::OnDemand`], the table circuits are loaded as
     ///   needed.
     pub fn generate_txn_proof(&self, input: GenerationInputs) -> anyhow::Result<GeneratedTxnProof> {
+        self.generate_txn_proof_with_trace_sizes(input)
+            .map(|(proof, _)| proof)
+    }
+
+    /// Generate a transaction proof using the specified input, along with the
+    /// [`TraceSizes`] of its STARK tables.
+    pub fn generate_txn_proof_with_trace_sizes(
+        &self,
+        input: GenerationInputs,
+    ) -> anyhow::Result<(GeneratedTxnProof, TraceSizes)> {
         match self.persistence {
             CircuitPersistence::None | CircuitPersistence::Disk(TableLoadStrategy::Monolithic) => {
                 info!("using monolithic circuit {:?}", self);
@@ -242,6 +303,7 @@ impl ProverStateManager {
     /// Initialize global prover state from the configuration.
     pub fn initialize(&self) -> anyhow::Result<()> {
         info!("initializing prover state...");
+        let load_start = Instant::now();
 
         let state = match self.persistence {
             CircuitPersistence::None => {
@@ -282,6 +344,10 @@ impl ProverStateManager {
             }
         };
 
+        CIRCUIT_LOAD_DURATION
+            .with_label_values(&["prover_state"])
+            .observe(load_start.elapsed().as_secs_f64());
+
         P_STATE.set(state).map_err(|_| {
             anyhow::Error::msg(
                 "prover state already set. check the program logic to ensure it is only set once",
@@ -302,13 +368,14 @@ impl ProverStateManager {
     /// Loads a verifier state from disk or generate it.
     pub fn verifier(&self) -> anyhow::Result<VerifierState> {
         info!("initializing verifier state...");
-        match self.persistence {
+        let load_start = Instant::now();
+        let verifier_state = match self.persistence {
             CircuitPersistence::None => {
                 info!("generating circuit...");
                 let prover_state = self.circuit_config.as_all_recursive_circuits();
-                Ok(VerifierState {
+                VerifierState {
                     state: prover_state.final_verifier_data(),
-                })
+                }
             }
             CircuitPersistence::Disk(_) => {
                 info!("attempting to load preprocessed verifier circuit from disk...");
@@ -317,7 +384,7 @@ impl ProverStateManager {
                 match disk_state {
                     Ok(state) => {
                         info!("successfully loaded preprocessed verifier circuit from disk");
-                        Ok(VerifierState { state })
+                        VerifierState { state }
                     }
                     Err(_) => {
                         info!("failed to load preprocessed verifier circuit from disk. generating it...");
@@ -327,10 +394,15 @@ impl ProverStateManager {
                         let state = prover_state.final_verifier_data();
                         VerifierResource::put(&self.circuit_config, &state)?;
 
-                        Ok(VerifierState { state })
+                        VerifierState { state }
                     }
                 }
             }
-        }
+        };
+        CIRCUIT_LOAD_DURATION
+            .with_label_values(&["verifier_state"])
+            .observe(load_start.elapsed().as_secs_f64());
+
+        Ok(verifier_state)
     }
 }
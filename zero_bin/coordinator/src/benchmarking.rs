@@ -1,15 +1,36 @@
 //! This module contains a lot of the important data for benchmarking purposes.
 //!
 //! TODO: Switch to using the CSV trait, it'll probably be easier
-use std::{fs::create_dir_all, io::Write, path::PathBuf, thread::sleep, time::Duration};
+use std::{
+    fs::{create_dir_all, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    thread::sleep,
+    time::Duration,
+};
 
 use chrono::{DateTime, Utc};
 use google_cloud_storage::{
-    client::{Client, ClientConfig},
-    http::objects::upload::{Media, UploadObjectRequest, UploadType},
+    client::Client,
+    http::{
+        objects::{
+            download::Range,
+            get::GetObjectRequest,
+            upload::{Media, UploadObjectRequest, UploadType},
+        },
+        Error as GcsError,
+    },
 };
+use ops::TxProofStats;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, warn};
+use zero_bin_common::prover_state::circuit::{Circuit, NUM_TABLES};
+
+use crate::{config::ConfigError, objstore};
+
+mod structured;
+
+pub use structured::{MachineInfo, RunInfo, SCHEMA_VERSION};
 
 //==================================================================================
 // Benchmarking Statistics
@@ -60,17 +81,29 @@ pub struct BenchmarkingStats {
     pub cumulative_gas_used: Option<u64>,
     /// The difficulty of the block we proved
     pub difficulty: u64,
+    /// The time the fetch of the block started, if fetched.  Timestamps
+    /// measured in UTC.
+    pub fetch_start_time: Option<DateTime<Utc>>,
+    /// The time the fetch of the block completed, if fetched.  Timestamps
+    /// measured in UTC.
+    pub fetch_end_time: Option<DateTime<Utc>>,
+    /// The amount of time the fetched block waited before its proof started,
+    /// stored as a [Duration]
+    pub proof_queue_duration: Option<Duration>,
+    /// The statistics of each transaction proof of the block
+    pub transactions: Vec<TransactionBenchmarkingStats>,
 }
 
 impl BenchmarkingStats {
     /// Returns a header row
     pub fn header_row() -> String {
         String::from(
-            "block_number, number_txs, cumulative_number_txs, fetch_duration, unique_proof_duration, prep_duration, txproof_duration, agg_wait_duration, agg_duration, start_time, end_time, cumulative_elapsed_time, proof_out_duration, gas_used, cumulative_gas_used, difficulty, gas_used_per_tx",
+            "block_number, number_txs, cumulative_number_txs, fetch_duration, unique_proof_duration, prep_duration, txproof_duration, agg_wait_duration, agg_duration, start_time, end_time, cumulative_elapsed_time, proof_out_duration, gas_used, cumulative_gas_used, difficulty, gas_used_per_tx, fetch_start_time, fetch_end_time, proof_queue_duration",
         )
     }
 
-    /// Given a vector of [BenchmarkingStats],
+    /// Given a vector of [BenchmarkingStats], returns a String of the CSV
+    /// rows, without the header.
     pub fn vec_to_string(vector: Vec<BenchmarkingStats>) -> String {
         vector
             .into_iter()
@@ -103,7 +136,7 @@ impl BenchmarkingStats {
     #[allow(clippy::format_in_format_args)]
     pub fn as_csv_row(&self) -> String {
         format!(
-            "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, \"{}\"",
+            "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, \"{}\", {}, {}, {}",
             self.block_number,
             self.n_txs,
             Self::unwrap_to_string(self.cumulative_n_txs),
@@ -125,8 +158,138 @@ impl BenchmarkingStats {
                 .map(|gas| gas.to_string())
                 .collect::<Vec<String>>()
                 .join(";"),
+            Self::unwrap_to_string(
+                self.fetch_start_time
+                    .map(|time| time.format("%d-%m-%Y %H:%M:%S"))
+            ),
+            Self::unwrap_to_string(
+                self.fetch_end_time
+                    .map(|time| time.format("%d-%m-%Y %H:%M:%S"))
+            ),
+            Self::unwrap_duration_to_string(self.proof_queue_duration),
+        )
+    }
+}
+
+//==================================================================================
+// Transaction Benchmarking Statistics
+//==================================================================================
+
+/// The Benchmarking Statistics of the proof of a singular transaction.
+#[derive(Debug, Clone)]
+pub struct TransactionBenchmarkingStats {
+    /// The block number of the block holding the transaction
+    pub block_number: u64,
+    /// The index of the transaction within its block
+    pub txn_number: u64,
+    /// The hex-encoded hash of the transaction, or "Dummy" for the padding
+    /// transactions
+    pub txn_hash: String,
+    /// The gas used by the transaction in the original chain
+    pub gas_used: u64,
+    /// The amount of time the worker took to prove the transaction, stored as
+    /// a [Duration]
+    pub proof_duration: Duration,
+    /// The number of CPU cycles run by the transaction, before the CPU trace is
+    /// padded to a power of two
+    pub cpu_cycles: usize,
+    /// The degree bits (i.e. the log2 of the trace length) of each STARK
+    /// table, indexed by [Circuit]
+    pub degree_bits: [usize; NUM_TABLES],
+}
+
+impl TransactionBenchmarkingStats {
+    /// Returns the [TransactionBenchmarkingStats] of a transaction, given the
+    /// [TxProofStats] returned by the workers.
+    pub fn from_txn_proof_stats(stats: &TxProofStats) -> Self {
+        Self {
+            block_number: stats.block_number,
+            txn_number: stats.txn_number,
+            txn_hash: stats.txn_hash.clone(),
+            gas_used: stats.gas_used,
+            proof_duration: stats.proof_duration,
+            cpu_cycles: stats.cpu_cycles,
+            degree_bits: stats.degree_bits,
+        }
+    }
+
+    /// Returns the name of each table, as used in the CSV header and the JSON
+    /// output
+    fn table_names() -> impl Iterator<Item = String> {
+        (0..NUM_TABLES).map(|table| Circuit::from(table).as_str().replace(' ', "_"))
+    }
+
+    /// Returns a header row
+    pub fn header_row() -> String {
+        let table_columns = Self::table_names()
+            .map(|name| format!("{name}_degree_bits, {name}_trace_length"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!(
+            "block_number, txn_number, txn_hash, gas_used, proof_duration, cpu_cycles, {}",
+            table_columns
         )
     }
+
+    /// Turns [TransactionBenchmarkingStats] into a CSV Row
+    pub fn as_csv_row(&self) -> String {
+        let table_columns = self
+            .degree_bits
+            .iter()
+            .map(|bits| format!("{}, {}", bits, 1usize << bits))
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!(
+            "{}, {}, {}, {}, {}, {}, {}",
+            self.block_number,
+            self.txn_number,
+            self.txn_hash,
+            self.gas_used,
+            self.proof_duration.as_secs_f64(),
+            self.cpu_cycles,
+            table_columns,
+        )
+    }
+
+    /// Turns [TransactionBenchmarkingStats] into a JSON object, with the
+    /// durations in seconds.
+    pub fn as_json(&self) -> serde_json::Value {
+        let tables = Self::table_names()
+            .zip(self.degree_bits)
+            .map(|(name, bits)| {
+                (
+                    name,
+                    serde_json::json!({
+                        "degree_bits": bits,
+                        "trace_length": 1usize << bits,
+                    }),
+                )
+            })
+            .collect::<serde_json::Map<String, serde_json::Value>>();
+        serde_json::json!({
+            "block_number": self.block_number,
+            "txn_number": self.txn_number,
+            "txn_hash": self.txn_hash,
+            "gas_used": self.gas_used,
+            "proof_duration": self.proof_duration.as_secs_f64(),
+            "cpu_cycles": self.cpu_cycles,
+            "tables": tables,
+        })
+    }
+
+    /// Given a vector of [TransactionBenchmarkingStats], returns a String of
+    /// the CSV, including the header.
+    pub fn vec_to_csv_string(vector: &[TransactionBenchmarkingStats]) -> String {
+        let mut rows = vec![Self::header_row()];
+        rows.extend(vector.iter().map(|stats| stats.as_csv_row()));
+        rows.join("\n")
+    }
+
+    /// Given a vector of [TransactionBenchmarkingStats], returns a String of
+    /// the JSON list.
+    pub fn vec_to_json_string(vector: &[TransactionBenchmarkingStats]) -> String {
+        serde_json::Value::Array(vector.iter().map(|stats| stats.as_json()).collect()).to_string()
+    }
 }
 
 //==================================================================================
@@ -137,6 +300,20 @@ impl BenchmarkingStats {
 // Benchmarking Output Config
 //----------------------------------------------------------------------------------
 
+/// The format the benchmarking statistics of the blocks are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchmarkFormat {
+    /// A CSV, one row per block, without the per-transaction statistics
+    #[default]
+    Csv,
+    /// One JSON object per line and per block, including the nested
+    /// per-transaction statistics and the run information
+    JsonLines,
+    /// A Parquet file with the same schema as [BenchmarkFormat::JsonLines]
+    Parquet,
+}
+
 /// The output method for benchmarking statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BenchmarkOutputConfig {
@@ -144,6 +321,19 @@ pub enum BenchmarkOutputConfig {
     LocalCsv {
         /// The name of the file to be used
         file_name: String,
+        /// The format of the file.  Default is [BenchmarkFormat::Csv].
+        #[serde(default)]
+        format: BenchmarkFormat,
+        /// If provided, the name of the file the per-transaction statistics
+        /// are stored in.  Written as JSON if it ends with `.json`, as CSV
+        /// otherwise.
+        #[serde(default)]
+        transactions_file_name: Option<String>,
+        /// Whether the rows are appended to the existing files, rather than
+        /// overwriting them.  Not supported by [BenchmarkFormat::Parquet] nor
+        /// by a JSON transactions file.  Default is false.
+        #[serde(default)]
+        append: bool,
     },
     /// Store the csv file output on Google Cloud Storage
     GoogleCloudStorageCsv {
@@ -151,9 +341,77 @@ pub enum BenchmarkOutputConfig {
         file_name: String,
         /// The name of the bucket to be used
         bucket: String,
+        /// The format of the file.  Default is [BenchmarkFormat::Csv].
+        #[serde(default)]
+        format: BenchmarkFormat,
+        /// If provided, the name of the file (gcs obj) the per-transaction
+        /// statistics are stored in.  Written as JSON if it ends with
+        /// `.json`, as CSV otherwise.
+        #[serde(default)]
+        transactions_file_name: Option<String>,
+        /// Whether the rows are appended to the existing gcs objs, rather
+        /// than overwriting them.  Not supported by [BenchmarkFormat::Parquet]
+        /// nor by a JSON transactions file.  Default is false.
+        #[serde(default)]
+        append: bool,
+        /// The number of blocks proven between two uploads of the gcs objs.
+        /// The objs are always uploaded once the run ends.  Default is
+        /// [DFLT_GCS_FLUSH_EVERY].
+        #[serde(default)]
+        flush_every: Option<u64>,
     },
 }
 
+impl BenchmarkOutputConfig {
+    /// Returns the name of the file the block statistics are stored in
+    pub fn file_name(&self) -> &str {
+        match self {
+            Self::LocalCsv { file_name, .. } | Self::GoogleCloudStorageCsv { file_name, .. } => {
+                file_name
+            }
+        }
+    }
+
+    /// Returns the format the block statistics are stored in
+    pub fn format(&self) -> BenchmarkFormat {
+        match self {
+            Self::LocalCsv { format, .. } | Self::GoogleCloudStorageCsv { format, .. } => *format,
+        }
+    }
+
+    /// Returns the name of the per-transaction statistics file, if any
+    pub fn transactions_file_name(&self) -> Option<&str> {
+        match self {
+            Self::LocalCsv {
+                transactions_file_name,
+                ..
+            }
+            | Self::GoogleCloudStorageCsv {
+                transactions_file_name,
+                ..
+            } => transactions_file_name.as_deref(),
+        }
+    }
+
+    /// Returns whether the rows are appended to the existing files
+    pub fn append(&self) -> bool {
+        match self {
+            Self::LocalCsv { append, .. } | Self::GoogleCloudStorageCsv { append, .. } => *append,
+        }
+    }
+
+    /// Returns the number of blocks proven between two flushes of the files.
+    /// Local files are flushed after every block.
+    pub fn flush_every(&self) -> usize {
+        match self {
+            Self::LocalCsv { .. } => 1,
+            Self::GoogleCloudStorageCsv { flush_every, .. } => {
+                flush_every.unwrap_or(DFLT_GCS_FLUSH_EVERY).max(1) as usize
+            }
+        }
+    }
+}
+
 unsafe impl Send for BenchmarkOutputConfig {}
 
 //----------------------------------------------------------------------------------
@@ -163,6 +421,16 @@ unsafe impl Send for BenchmarkOutputConfig {}
 pub enum BenchmarkingOutputBuildError {
     GoogleCloudAuth(anyhow::Error),
     Directory(Option<anyhow::Error>),
+    /// Returned when appending to a file whose format can only be rewritten
+    AppendUnsupported(String),
+    /// Returned when we fail to read the existing file (or gcs obj) we append
+    /// to
+    ExistingFile(String, anyhow::Error),
+    /// Returned when the existing file (or gcs obj) we append to does not
+    /// start with the header (or the schema version) of the rows appended
+    MismatchedHeader(String),
+    /// Returned when the configuration of the coordinator is not available
+    Config(ConfigError),
 }
 
 impl std::fmt::Display for BenchmarkingOutputBuildError {
@@ -188,28 +456,17 @@ impl BenchmarkingOutputData {
         config: &BenchmarkOutputConfig,
     ) -> Result<Self, BenchmarkingOutputBuildError> {
         match config {
-            BenchmarkOutputConfig::GoogleCloudStorageCsv {
-                file_name: _,
-                bucket: _,
-            } => {
-                // Produce the GCP Config
-                let gcp_config = match ClientConfig::default().with_auth().await {
-                    Ok(gcp_config) => {
-                        info!("GCS ClientConfig generated with auth");
-                        gcp_config
-                    }
+            BenchmarkOutputConfig::GoogleCloudStorageCsv { .. } => {
+                // The client is shared with the object stores
+                match objstore::gcs_client().await {
+                    Ok(gcs_client) => Ok(Self::GoogleCloudStorageCsv { gcs_client }),
                     Err(err) => {
                         error!("Failed to authenticate with GCS: {}", err);
-                        return Err(BenchmarkingOutputBuildError::GoogleCloudAuth(err.into()));
+                        Err(BenchmarkingOutputBuildError::GoogleCloudAuth(err))
                     }
-                };
-                // Return the Client
-                info!("Returning GCS Client");
-                Ok(Self::GoogleCloudStorageCsv {
-                    gcs_client: Client::new(gcp_config),
-                })
+                }
             }
-            BenchmarkOutputConfig::LocalCsv { file_name: _ } => {
+            BenchmarkOutputConfig::LocalCsv { .. } => {
                 let output_dir_str = match std::env::var(BENCHMARK_OUT_DIR_ENVKEY) {
                     Ok(dirpath_str) => dirpath_str,
                     Err(std::env::VarError::NotPresent) => {
@@ -268,6 +525,9 @@ pub enum BenchmarkingOutputError {
     GoogleCloudStorageFailedUpload(anyhow::Error),
     FileCreation(PathBuf, anyhow::Error),
     FileWrite(PathBuf, anyhow::Error),
+    /// Returned whenever we fail to encode the stats in the configured
+    /// [BenchmarkFormat]
+    Encoding(BenchmarkFormat, anyhow::Error),
 }
 
 impl std::fmt::Display for BenchmarkingOutputError {
@@ -283,50 +543,398 @@ impl std::error::Error for BenchmarkingOutputError {}
 pub const MAX_GCS_UPLOAD_ATTEMPTS: u64 = 50;
 /// The number of seconds to wait between attempts to upload to GCS
 pub const GCS_FAILED_UPLOAD_SLEEP_SECS: u64 = 5;
+/// The default number of blocks proven between two uploads to GCS.  Every
+/// upload rewrites the whole gcs obj, so uploading after every block would
+/// upload a long run quadratically.
+pub const DFLT_GCS_FLUSH_EVERY: u64 = 50;
 //      LOCAL CSV CONSTANTS
 /// The environment key to get the benchmark output
 pub const BENCHMARK_OUT_DIR_ENVKEY: &str = "BENCHMARK_OUTDIR";
 /// The default directory for benchmark out
 pub const BENCHMARK_OUT_DIR_DFLT: &str = "benchmark_out/";
 
+/// What a file of the [BenchmarkingOutput] holds, and how it is encoded
+#[derive(Debug, Clone, Copy)]
+enum OutputFileKind {
+    /// The statistics of the blocks, in the given format
+    Blocks(BenchmarkFormat),
+    /// The statistics of the transactions, as a CSV
+    TransactionsCsv,
+    /// The statistics of the transactions, as a JSON list
+    TransactionsJson,
+}
+
+impl OutputFileKind {
+    /// Returns whether new rows can be appended to the file.  The other files
+    /// are rewritten entirely on every flush.
+    fn is_appendable(&self) -> bool {
+        matches!(
+            self,
+            Self::Blocks(BenchmarkFormat::Csv | BenchmarkFormat::JsonLines) | Self::TransactionsCsv
+        )
+    }
+
+    /// Returns whether the existing contents of an appendable file start with
+    /// the header of the rows appended to them, or for JSON Lines with a row
+    /// of the current [SCHEMA_VERSION].
+    fn matches_existing(&self, existing: &[u8]) -> bool {
+        let first_line = existing
+            .split(|byte| *byte == b'\n')
+            .next()
+            .unwrap_or_default();
+        let first_line = String::from_utf8_lossy(first_line);
+        match self {
+            Self::Blocks(BenchmarkFormat::Csv) => {
+                first_line.trim() == BenchmarkingStats::header_row()
+            }
+            Self::TransactionsCsv => {
+                first_line.trim() == TransactionBenchmarkingStats::header_row()
+            }
+            Self::Blocks(BenchmarkFormat::JsonLines) => {
+                serde_json::from_str::<serde_json::Value>(&first_line)
+                    .ok()
+                    .and_then(|row| row.get("schema_version")?.as_u64())
+                    == Some(SCHEMA_VERSION as u64)
+            }
+            Self::Blocks(BenchmarkFormat::Parquet) | Self::TransactionsJson => false,
+        }
+    }
+
+    /// Encodes the statistics of the given blocks.  For appendable files,
+    /// every row ends with a newline and the header is only included if
+    /// `with_header` is set.
+    fn encode(
+        &self,
+        stats: &[BenchmarkingStats],
+        run: &RunInfo,
+        with_header: bool,
+    ) -> Result<Vec<u8>, BenchmarkingOutputError> {
+        let csv = |header: String, rows: Vec<String>| {
+            let mut contents = String::new();
+            if with_header {
+                contents.push_str(&header);
+                contents.push('\n');
+            }
+            for row in rows {
+                contents.push_str(&row);
+                contents.push('\n');
+            }
+            contents.into_bytes()
+        };
+        match self {
+            Self::Blocks(BenchmarkFormat::Csv) => Ok(csv(
+                BenchmarkingStats::header_row(),
+                stats.iter().map(|stats| stats.as_csv_row()).collect(),
+            )),
+            Self::Blocks(BenchmarkFormat::JsonLines) => Ok(structured::to_json_lines(stats, run)),
+            Self::Blocks(BenchmarkFormat::Parquet) => structured::to_parquet(stats, run)
+                .map_err(|err| BenchmarkingOutputError::Encoding(BenchmarkFormat::Parquet, err)),
+            Self::TransactionsCsv => Ok(csv(
+                TransactionBenchmarkingStats::header_row(),
+                stats
+                    .iter()
+                    .flat_map(|stats| &stats.transactions)
+                    .map(|txn| txn.as_csv_row())
+                    .collect(),
+            )),
+            Self::TransactionsJson => Ok(TransactionBenchmarkingStats::vec_to_json_string(
+                &stats
+                    .iter()
+                    .flat_map(|stats| stats.transactions.iter().cloned())
+                    .collect::<Vec<TransactionBenchmarkingStats>>(),
+            )
+            .into_bytes()),
+        }
+    }
+}
+
+/// A file (or gcs obj) of the [BenchmarkingOutput], and how much of the
+/// statistics have been flushed to it
+#[derive(Debug)]
+struct OutputFile {
+    /// The name of the file (or gcs obj)
+    name: String,
+    kind: OutputFileKind,
+    /// The contents written before the rows not flushed yet.  For gcs objs
+    /// this is the whole obj as last uploaded, for local files only the
+    /// newline missing at the end of the file we append to.
+    existing: Vec<u8>,
+    /// Whether the file already holds a header or rows, in which case new
+    /// rows are appended to it
+    started: bool,
+    /// The number of blocks pushed to the [BenchmarkingOutput] already
+    /// flushed
+    flushed: usize,
+}
+
+impl OutputFile {
+    fn new(name: &str, kind: OutputFileKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            existing: Vec::new(),
+            started: false,
+            flushed: 0,
+        }
+    }
+
+    /// Prepares the file to have rows appended to the existing file (or gcs
+    /// obj), if any.
+    async fn prepare_append(
+        &mut self,
+        config: &BenchmarkOutputConfig,
+        data: &BenchmarkingOutputData,
+    ) -> Result<(), BenchmarkingOutputBuildError> {
+        if !self.kind.is_appendable() {
+            error!(
+                "Cannot append to {}, its format can only be rewritten",
+                self.name
+            );
+            return Err(BenchmarkingOutputBuildError::AppendUnsupported(
+                self.name.clone(),
+            ));
+        }
+
+        let existing = match (config, data) {
+            (
+                BenchmarkOutputConfig::GoogleCloudStorageCsv { bucket, .. },
+                BenchmarkingOutputData::GoogleCloudStorageCsv { gcs_client },
+            ) => {
+                let downloaded = gcs_client
+                    .download_object(
+                        &GetObjectRequest {
+                            bucket: bucket.clone(),
+                            object: self.name.clone(),
+                            ..Default::default()
+                        },
+                        &Range::default(),
+                    )
+                    .await;
+                match downloaded {
+                    Ok(contents) => contents,
+                    Err(GcsError::Response(err)) if err.code == 404 => Vec::new(),
+                    Err(err) => {
+                        error!("Failed to download {} from GCS: {}", self.name, err);
+                        return Err(BenchmarkingOutputBuildError::ExistingFile(
+                            self.name.clone(),
+                            err.into(),
+                        ));
+                    }
+                }
+            }
+            (_, BenchmarkingOutputData::LocalCsv { dirpath }) => {
+                match std::fs::read(dirpath.join(&self.name)) {
+                    Ok(contents) => contents,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                    Err(err) => {
+                        error!("Failed to read {}: {}", self.name, err);
+                        return Err(BenchmarkingOutputBuildError::ExistingFile(
+                            self.name.clone(),
+                            err.into(),
+                        ));
+                    }
+                }
+            }
+            (_, _) => Vec::new(),
+        };
+
+        if existing.is_empty() {
+            info!("No existing {} to append to, starting a new one", self.name);
+            return Ok(());
+        }
+        if !self.kind.matches_existing(&existing) {
+            error!(
+                "Cannot append to {}, its header does not match the rows of this run",
+                self.name
+            );
+            return Err(BenchmarkingOutputBuildError::MismatchedHeader(
+                self.name.clone(),
+            ));
+        }
+        info!("Appending to the existing {}", self.name);
+
+        // Files written before incremental flushing lack the final newline
+        let missing_newline = !existing.ends_with(b"\n");
+        self.existing = match data {
+            // The local file already holds its contents
+            BenchmarkingOutputData::LocalCsv { .. } => Vec::new(),
+            _ => existing,
+        };
+        if missing_newline {
+            self.existing.push(b'\n');
+        }
+        self.started = true;
+        Ok(())
+    }
+}
+
 /// The [BenchmarkingOutput] is the means of outputting
 pub struct BenchmarkingOutput {
     config: BenchmarkOutputConfig,
     data: BenchmarkingOutputData,
-    pub stats: Vec<BenchmarkingStats>,
+    run: RunInfo,
+    files: Vec<OutputFile>,
+    /// The statistics of the blocks still needed by one of the files, i.e.
+    /// not flushed yet or rewritten entirely on every flush
+    stats: Vec<BenchmarkingStats>,
+    /// The number of blocks dropped from the front of the statistics, once
+    /// flushed to every file
+    dropped: usize,
 }
 
 impl BenchmarkingOutput {
     pub async fn from_config(
         config: BenchmarkOutputConfig,
         init_capacity: Option<u64>,
+        run_name: Option<String>,
+    ) -> Result<Self, BenchmarkingOutputBuildError> {
+        let data = BenchmarkingOutputData::from_config(&config).await?;
+        let circuit_config = crate::config::get()
+            .map_err(BenchmarkingOutputBuildError::Config)?
+            .circuit_config();
+        let run = RunInfo::new(run_name, &circuit_config);
+        Self::with_data(config, data, run, init_capacity).await
+    }
+
+    /// Builds the [BenchmarkingOutput] of the run, given the data of its
+    /// config.
+    async fn with_data(
+        config: BenchmarkOutputConfig,
+        data: BenchmarkingOutputData,
+        run: RunInfo,
+        init_capacity: Option<u64>,
     ) -> Result<Self, BenchmarkingOutputBuildError> {
+        let mut files = vec![OutputFile::new(
+            config.file_name(),
+            OutputFileKind::Blocks(config.format()),
+        )];
+        if let Some(transactions_file_name) = config.transactions_file_name() {
+            let kind = match transactions_file_name.ends_with(".json") {
+                true => OutputFileKind::TransactionsJson,
+                false => OutputFileKind::TransactionsCsv,
+            };
+            files.push(OutputFile::new(transactions_file_name, kind));
+        }
+        if config.append() {
+            for file in files.iter_mut() {
+                file.prepare_append(&config, &data).await?;
+            }
+        }
+
+        // Only the files rewritten on every flush keep every block
+        let keeps_stats = files.iter().any(|file| !file.kind.is_appendable());
         Ok(BenchmarkingOutput {
-            data: BenchmarkingOutputData::from_config(&config).await?,
+            data,
             config,
+            run,
+            files,
             stats: match init_capacity {
-                Some(capacity) => Vec::with_capacity(capacity as usize),
-                None => Vec::new(),
+                Some(capacity) if keeps_stats => Vec::with_capacity(capacity as usize),
+                _ => Vec::new(),
             },
+            dropped: 0,
         })
     }
 
-    /// Pushes a [BenchmarkingOutput] to the list of stats we are storing to
-    /// later be published.
-    pub fn push(&mut self, benchmark_stats: BenchmarkingStats) {
-        self.stats.push(benchmark_stats)
+    /// Pushes a [BenchmarkingStats] to the list of stats and flushes it, so
+    /// that a failed run still leaves the statistics of the blocks it proved.
+    /// Local files are flushed after every block, gcs objs are uploaded every
+    /// [BenchmarkOutputConfig::flush_every] blocks.
+    pub async fn push(
+        &mut self,
+        benchmark_stats: BenchmarkingStats,
+    ) -> Result<(), BenchmarkingOutputError> {
+        self.stats.push(benchmark_stats);
+        let due = self.pushed() % self.config.flush_every() == 0;
+        self.flush(due).await
+    }
+
+    /// Returns the number of blocks pushed so far
+    fn pushed(&self) -> usize {
+        self.dropped + self.stats.len()
     }
 
     /// Finalizes the output (i.e. pushes to bucket, outputs file, etc)
-    pub async fn publish(&self) -> Result<(), BenchmarkingOutputError> {
-        match (&self.config, &self.data) {
+    pub async fn publish(&mut self) -> Result<(), BenchmarkingOutputError> {
+        self.flush(true).await
+    }
+
+    /// Flushes the stats which were not flushed yet.  Appendable local files
+    /// are always flushed, the other files only if `due` is set.  The stats
+    /// flushed to every file are then dropped.
+    async fn flush(&mut self, due: bool) -> Result<(), BenchmarkingOutputError> {
+        let local = matches!(self.data, BenchmarkingOutputData::LocalCsv { .. });
+        let pushed = self.pushed();
+        for file in self.files.iter_mut() {
+            if file.flushed == pushed || !(due || (local && file.kind.is_appendable())) {
+                continue;
+            }
+            if file.kind.is_appendable() {
+                debug!("Appending benchmark stats to {}", file.name);
+                let rows = file.kind.encode(
+                    &self.stats[file.flushed - self.dropped..],
+                    &self.run,
+                    !file.started,
+                )?;
+                if local {
+                    let mut contents = std::mem::take(&mut file.existing);
+                    contents.extend(rows);
+                    Self::write(
+                        &self.config,
+                        &self.data,
+                        &file.name,
+                        &contents,
+                        file.started,
+                    )
+                    .await?;
+                } else {
+                    // The gcs obj is rewritten along with the rows flushed
+                    // before
+                    let mut contents = file.existing.clone();
+                    contents.extend(rows);
+                    Self::write(&self.config, &self.data, &file.name, &contents, false).await?;
+                    file.existing = contents;
+                }
+            } else {
+                debug!("Rewriting benchmark stats to {}", file.name);
+                let contents = file.kind.encode(&self.stats, &self.run, true)?;
+                Self::write(&self.config, &self.data, &file.name, &contents, false).await?;
+            }
+            file.started = true;
+            file.flushed = pushed;
+        }
+
+        // The files rewritten on every flush need every block
+        let needed = self
+            .files
+            .iter()
+            .map(|file| match file.kind.is_appendable() {
+                true => file.flushed,
+                false => self.dropped,
+            })
+            .min()
+            .unwrap_or(pushed);
+        self.stats.drain(..needed - self.dropped);
+        self.dropped = needed;
+        Ok(())
+    }
+
+    /// Writes the contents to the file (or gcs obj) named `file_name`.  The
+    /// contents are appended to the local file if `append` is set, gcs objs
+    /// are always overwritten.
+    async fn write(
+        config: &BenchmarkOutputConfig,
+        data: &BenchmarkingOutputData,
+        file_name: &str,
+        contents: &[u8],
+        append: bool,
+    ) -> Result<(), BenchmarkingOutputError> {
+        match (config, data) {
             (
-                BenchmarkOutputConfig::GoogleCloudStorageCsv { file_name, bucket },
+                BenchmarkOutputConfig::GoogleCloudStorageCsv { bucket, .. },
                 BenchmarkingOutputData::GoogleCloudStorageCsv { gcs_client },
             ) => {
                 info!("Attempting to upload to GCS");
-                let csv_string = BenchmarkingStats::vec_to_csv_string(self.stats.clone());
-                debug!("Benchmark stats successfully turned into CSV string.");
                 let mut attempt_n: u64 = 0;
                 loop {
                     debug!("Starting attempt n {} to upload", attempt_n);
@@ -336,8 +944,8 @@ impl BenchmarkingOutput {
                                 bucket: bucket.clone(),
                                 ..Default::default()
                             },
-                            csv_string.clone(),
-                            &UploadType::Simple(Media::new(file_name.clone())),
+                            contents.to_vec(),
+                            &UploadType::Simple(Media::new(file_name.to_string())),
                         )
                         .await;
 
@@ -371,12 +979,18 @@ impl BenchmarkingOutput {
                 }
             }
             (
-                BenchmarkOutputConfig::LocalCsv { file_name },
+                BenchmarkOutputConfig::LocalCsv { .. },
                 BenchmarkingOutputData::LocalCsv { dirpath },
             ) => {
                 let file_path = dirpath.join(file_name);
                 debug!("Attempting to create file: {:?}", file_path);
-                let mut file = match std::fs::File::create(file_path.clone()) {
+                let mut file = match OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(file_path.clone())
+                {
                     Ok(file) => {
                         info!("Created file: {}", file_name);
                         file
@@ -390,9 +1004,7 @@ impl BenchmarkingOutput {
                     }
                 };
                 debug!("Attempting to write benchmark stats to {:?}", file_path);
-                match file
-                    .write_all(BenchmarkingStats::vec_to_csv_string(self.stats.clone()).as_bytes())
-                {
+                match file.write_all(contents) {
                     Ok(_) => info!("Successfully wrote benchmark stats to {:?}", file_path),
                     Err(err) => {
                         error!("Failed to write to file `{:?}`: {}", file_path, err);
@@ -411,3 +1023,230 @@ impl BenchmarkingOutput {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use zero_bin_common::prover_state::circuit::CircuitConfig;
+
+    use super::*;
+
+    pub(super) fn run() -> RunInfo {
+        RunInfo::new(Some(String::from("test")), &CircuitConfig::default())
+    }
+
+    pub(super) fn stats(block_number: u64) -> BenchmarkingStats {
+        let start_time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        BenchmarkingStats {
+            block_number,
+            n_txs: 2,
+            cumulative_n_txs: Some(2),
+            fetch_duration: Some(Duration::from_millis(250)),
+            total_proof_duration: Duration::from_secs(3),
+            prep_duration: None,
+            txproof_duration: Some(Duration::from_secs(2)),
+            agg_wait_duration: None,
+            agg_duration: Some(Duration::from_secs(1)),
+            start_time,
+            end_time: start_time + chrono::Duration::seconds(3),
+            overall_elapsed_seconds: Some(3),
+            proof_out_duration: None,
+            gas_used: 42_000,
+            gas_used_per_tx: vec![21_000, 21_000],
+            cumulative_gas_used: Some(42_000),
+            difficulty: 0,
+            fetch_start_time: None,
+            fetch_end_time: None,
+            proof_queue_duration: None,
+            transactions: (0..2)
+                .map(|txn_number| TransactionBenchmarkingStats {
+                    block_number,
+                    txn_number,
+                    txn_hash: format!("0x{txn_number:064x}"),
+                    gas_used: 21_000,
+                    proof_duration: Duration::from_secs(1),
+                    cpu_cycles: 1000,
+                    degree_bits: [10; NUM_TABLES],
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns an empty directory in the temporary directory, named after the
+    /// test
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "coordinator-benchmarking-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn local(
+        format: BenchmarkFormat,
+        transactions_file_name: Option<&str>,
+        append: bool,
+    ) -> BenchmarkOutputConfig {
+        BenchmarkOutputConfig::LocalCsv {
+            file_name: String::from("blocks"),
+            format,
+            transactions_file_name: transactions_file_name.map(String::from),
+            append,
+        }
+    }
+
+    async fn output(
+        config: BenchmarkOutputConfig,
+        dirpath: &PathBuf,
+    ) -> Result<BenchmarkingOutput, BenchmarkingOutputBuildError> {
+        let data = BenchmarkingOutputData::LocalCsv {
+            dirpath: dirpath.clone(),
+        };
+        BenchmarkingOutput::with_data(config, data, run(), None).await
+    }
+
+    #[test]
+    fn transaction_rows_hold_the_cpu_cycles() {
+        let txn = &stats(1).transactions[0];
+        let header = TransactionBenchmarkingStats::header_row();
+        let row = txn.as_csv_row();
+        let columns = header.split(", ").zip(row.split(", ")).collect::<Vec<_>>();
+        assert_eq!(columns.len(), header.split(", ").count());
+        assert_eq!(columns.len(), row.split(", ").count());
+        assert!(columns.contains(&("cpu_cycles", "1000")));
+        assert_eq!(txn.as_json()["cpu_cycles"], 1000);
+    }
+
+    #[tokio::test]
+    async fn appends_rows_and_drops_the_flushed_stats() {
+        let dirpath = dir("append");
+        let mut output = output(local(BenchmarkFormat::Csv, Some("txns"), false), &dirpath)
+            .await
+            .unwrap();
+        output.push(stats(1)).await.unwrap();
+        output.push(stats(2)).await.unwrap();
+        output.publish().await.unwrap();
+
+        assert!(output.stats.is_empty());
+        assert_eq!(output.dropped, 2);
+        let blocks = std::fs::read_to_string(dirpath.join("blocks")).unwrap();
+        assert_eq!(
+            blocks,
+            format!(
+                "{}\n{}\n{}\n",
+                BenchmarkingStats::header_row(),
+                stats(1).as_csv_row(),
+                stats(2).as_csv_row()
+            )
+        );
+        let txns = std::fs::read_to_string(dirpath.join("txns")).unwrap();
+        assert_eq!(txns.lines().count(), 5);
+        std::fs::remove_dir_all(&dirpath).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_the_stats_of_rewritten_files() {
+        let dirpath = dir("rewrite");
+        let mut output = output(
+            local(BenchmarkFormat::JsonLines, Some("txns.json"), false),
+            &dirpath,
+        )
+        .await
+        .unwrap();
+        output.push(stats(1)).await.unwrap();
+        output.push(stats(2)).await.unwrap();
+
+        assert_eq!(output.stats.len(), 2);
+        let txns: Vec<serde_json::Value> =
+            serde_json::from_slice(&std::fs::read(dirpath.join("txns.json")).unwrap()).unwrap();
+        assert_eq!(txns.len(), 4);
+        assert_eq!(
+            std::fs::read_to_string(dirpath.join("blocks"))
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+        std::fs::remove_dir_all(&dirpath).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resumes_an_existing_file() {
+        let dirpath = dir("resume");
+        // Written before incremental flushing, without the final newline
+        let existing = format!(
+            "{}\n{}",
+            BenchmarkingStats::header_row(),
+            stats(1).as_csv_row()
+        );
+        std::fs::write(dirpath.join("blocks"), &existing).unwrap();
+
+        let mut output = output(local(BenchmarkFormat::Csv, None, true), &dirpath)
+            .await
+            .unwrap();
+        output.push(stats(2)).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dirpath.join("blocks")).unwrap(),
+            format!("{}\n{}\n", existing, stats(2).as_csv_row())
+        );
+        std::fs::remove_dir_all(&dirpath).unwrap();
+    }
+
+    #[tokio::test]
+    async fn prepare_append_starts_a_missing_file() {
+        let dirpath = dir("missing");
+        let config = local(BenchmarkFormat::Csv, None, true);
+        let data = BenchmarkingOutputData::LocalCsv {
+            dirpath: dirpath.clone(),
+        };
+        let mut file = OutputFile::new("blocks", OutputFileKind::Blocks(BenchmarkFormat::Csv));
+        file.prepare_append(&config, &data).await.unwrap();
+
+        assert!(!file.started);
+        assert!(file.existing.is_empty());
+        std::fs::remove_dir_all(&dirpath).unwrap();
+    }
+
+    #[tokio::test]
+    async fn prepare_append_rejects_a_mismatched_header() {
+        let dirpath = dir("mismatched");
+        std::fs::write(dirpath.join("blocks"), "block_number, number_txs\n1, 2\n").unwrap();
+        let config = local(BenchmarkFormat::Csv, None, true);
+        let data = BenchmarkingOutputData::LocalCsv {
+            dirpath: dirpath.clone(),
+        };
+        let mut file = OutputFile::new("blocks", OutputFileKind::Blocks(BenchmarkFormat::Csv));
+
+        assert!(matches!(
+            file.prepare_append(&config, &data).await,
+            Err(BenchmarkingOutputBuildError::MismatchedHeader(_))
+        ));
+        std::fs::remove_dir_all(&dirpath).unwrap();
+    }
+
+    #[tokio::test]
+    async fn prepare_append_rejects_rewritten_files() {
+        let config = local(BenchmarkFormat::Parquet, None, true);
+        let mut file = OutputFile::new("blocks", OutputFileKind::Blocks(BenchmarkFormat::Parquet));
+
+        assert!(matches!(
+            file.prepare_append(&config, &BenchmarkingOutputData::NotApplicable)
+                .await,
+            Err(BenchmarkingOutputBuildError::AppendUnsupported(_))
+        ));
+    }
+
+    #[test]
+    fn json_lines_match_the_schema_version() {
+        let kind = OutputFileKind::Blocks(BenchmarkFormat::JsonLines);
+        let current = structured::to_json_lines(&[stats(1)], &run());
+        let outdated = format!("{}\n", serde_json::json!({"schema_version": 0}));
+
+        assert!(kind.matches_existing(&current));
+        assert!(!kind.matches_existing(outdated.as_bytes()));
+    }
+}
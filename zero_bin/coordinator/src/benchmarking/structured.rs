@@ -0,0 +1,328 @@
+//! The structured (JSON Lines and Parquet) benchmark outputs.
+//!
+//! Unlike the CSV, every row holds the nested data of the block (the gas used
+//! and the statistics of each transaction), along with the [RunInfo] of the
+//! run that produced it, so that the output of several runs can be loaded into
+//! the same table.  The layout of a row is described by [schema], and any
+//! change to it must bump [SCHEMA_VERSION].
+use std::sync::Arc;
+
+use arrow_json::ReaderBuilder;
+use arrow_schema::{DataType, Field, Fields, Schema, TimeUnit};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::{Deserialize, Serialize};
+use zero_bin_common::{proof_format::circuit_config_digest, prover_state::circuit::CircuitConfig};
+
+use super::{BenchmarkingStats, TransactionBenchmarkingStats};
+
+/// The version of the row layout, stored in every row.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The number of rows decoded into a single Parquet record batch.
+const PARQUET_BATCH_SIZE: usize = 1024;
+
+/// Information about the machine running the coordinator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MachineInfo {
+    /// The hostname, if it could be found
+    pub hostname: Option<String>,
+    /// The operating system, as in [std::env::consts::OS]
+    pub os: String,
+    /// The CPU architecture, as in [std::env::consts::ARCH]
+    pub arch: String,
+    /// The number of CPUs available to the coordinator
+    pub cpus: Option<u64>,
+}
+
+impl MachineInfo {
+    /// Returns the [MachineInfo] of the current machine.
+    pub fn current() -> Self {
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|hostname| hostname.trim().to_string())
+            .filter(|hostname| !hostname.is_empty());
+        Self {
+            hostname,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism()
+                .ok()
+                .map(|cpus| cpus.get() as u64),
+        }
+    }
+}
+
+/// Information about the run, shared by every row it produced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunInfo {
+    /// The name of the run, as in the request
+    pub run_name: Option<String>,
+    /// The machine running the coordinator
+    pub machine: MachineInfo,
+    /// The hex-encoded digest of the circuit configuration the blocks were
+    /// proved with
+    pub circuit_config_digest: String,
+}
+
+impl RunInfo {
+    /// Returns the [RunInfo] of a run on the current machine, proving with
+    /// the given circuit configuration.
+    pub fn new(run_name: Option<String>, circuit_config: &CircuitConfig) -> Self {
+        Self {
+            run_name,
+            machine: MachineInfo::current(),
+            circuit_config_digest: hex::encode(circuit_config_digest(circuit_config)),
+        }
+    }
+}
+
+/// Returns the Arrow [Schema] of the rows.
+pub fn schema() -> Schema {
+    let seconds = || DataType::Float64;
+    let timestamp = || DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into()));
+
+    let machine = Fields::from(vec![
+        Field::new("hostname", DataType::Utf8, true),
+        Field::new("os", DataType::Utf8, false),
+        Field::new("arch", DataType::Utf8, false),
+        Field::new("cpus", DataType::UInt64, true),
+    ]);
+    let table = Fields::from(vec![
+        Field::new("degree_bits", DataType::UInt64, false),
+        Field::new("trace_length", DataType::UInt64, false),
+    ]);
+    let tables = TransactionBenchmarkingStats::table_names()
+        .map(|name| Field::new(name, DataType::Struct(table.clone()), false))
+        .collect::<Fields>();
+    let transaction = Fields::from(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("txn_number", DataType::UInt64, false),
+        Field::new("txn_hash", DataType::Utf8, false),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new("proof_duration", seconds(), false),
+        Field::new("cpu_cycles", DataType::UInt64, false),
+        Field::new("tables", DataType::Struct(tables), false),
+    ]);
+
+    Schema::new(vec![
+        Field::new("schema_version", DataType::UInt32, false),
+        Field::new("run_name", DataType::Utf8, true),
+        Field::new("machine", DataType::Struct(machine), false),
+        Field::new("circuit_config_digest", DataType::Utf8, false),
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("n_txs", DataType::UInt64, false),
+        Field::new("cumulative_n_txs", DataType::UInt64, true),
+        Field::new("fetch_duration", seconds(), true),
+        Field::new("total_proof_duration", seconds(), false),
+        Field::new("prep_duration", seconds(), true),
+        Field::new("txproof_duration", seconds(), true),
+        Field::new("agg_wait_duration", seconds(), true),
+        Field::new("agg_duration", seconds(), true),
+        Field::new("start_time", timestamp(), false),
+        Field::new("end_time", timestamp(), false),
+        Field::new("overall_elapsed_seconds", DataType::UInt64, true),
+        Field::new("proof_out_duration", seconds(), true),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new(
+            "gas_used_per_tx",
+            DataType::List(Arc::new(Field::new("item", DataType::UInt64, false))),
+            false,
+        ),
+        Field::new("cumulative_gas_used", DataType::UInt64, true),
+        Field::new("difficulty", DataType::UInt64, false),
+        Field::new("fetch_start_time", timestamp(), true),
+        Field::new("fetch_end_time", timestamp(), true),
+        Field::new("proof_queue_duration", seconds(), true),
+        Field::new(
+            "transactions",
+            DataType::List(Arc::new(Field::new(
+                "item",
+                DataType::Struct(transaction),
+                false,
+            ))),
+            false,
+        ),
+    ])
+}
+
+/// Turns [BenchmarkingStats] into a row following [schema], with the
+/// durations in seconds and the timestamps in RFC 3339.
+pub fn as_row(stats: &BenchmarkingStats, run: &RunInfo) -> serde_json::Value {
+    let seconds = |duration: Option<std::time::Duration>| duration.map(|d| d.as_secs_f64());
+    serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "run_name": run.run_name,
+        "machine": run.machine,
+        "circuit_config_digest": run.circuit_config_digest,
+        "block_number": stats.block_number,
+        "n_txs": stats.n_txs,
+        "cumulative_n_txs": stats.cumulative_n_txs,
+        "fetch_duration": seconds(stats.fetch_duration),
+        "total_proof_duration": stats.total_proof_duration.as_secs_f64(),
+        "prep_duration": seconds(stats.prep_duration),
+        "txproof_duration": seconds(stats.txproof_duration),
+        "agg_wait_duration": seconds(stats.agg_wait_duration),
+        "agg_duration": seconds(stats.agg_duration),
+        "start_time": stats.start_time.to_rfc3339(),
+        "end_time": stats.end_time.to_rfc3339(),
+        "overall_elapsed_seconds": stats.overall_elapsed_seconds,
+        "proof_out_duration": seconds(stats.proof_out_duration),
+        "gas_used": stats.gas_used,
+        "gas_used_per_tx": stats.gas_used_per_tx,
+        "cumulative_gas_used": stats.cumulative_gas_used,
+        "difficulty": stats.difficulty,
+        "fetch_start_time": stats.fetch_start_time.map(|time| time.to_rfc3339()),
+        "fetch_end_time": stats.fetch_end_time.map(|time| time.to_rfc3339()),
+        "proof_queue_duration": seconds(stats.proof_queue_duration),
+        "transactions": stats
+            .transactions
+            .iter()
+            .map(|txn| txn.as_json())
+            .collect::<Vec<serde_json::Value>>(),
+    })
+}
+
+/// Given a slice of [BenchmarkingStats], returns the JSON Lines, one row per
+/// block.
+pub fn to_json_lines(stats: &[BenchmarkingStats], run: &RunInfo) -> Vec<u8> {
+    stats
+        .iter()
+        .map(|stats| format!("{}\n", as_row(stats, run)))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// Given a slice of [BenchmarkingStats], returns the Parquet file, one row per
+/// block.
+pub fn to_parquet(stats: &[BenchmarkingStats], run: &RunInfo) -> Result<Vec<u8>, anyhow::Error> {
+    let schema = Arc::new(schema());
+    let rows = stats
+        .iter()
+        .map(|stats| as_row(stats, run))
+        .collect::<Vec<serde_json::Value>>();
+
+    let mut decoder = ReaderBuilder::new(schema.clone())
+        .with_batch_size(PARQUET_BATCH_SIZE)
+        .build_decoder()?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema, Some(properties))?;
+    for chunk in rows.chunks(PARQUET_BATCH_SIZE) {
+        decoder.serialize(chunk)?;
+        if let Some(batch) = decoder.flush()? {
+            writer.write(&batch)?;
+        }
+    }
+    writer.close()?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use zero_bin_common::prover_state::circuit::Circuit;
+
+    use super::*;
+    use crate::benchmarking::test::{run, stats};
+
+    /// Returns the names of the fields of the JSON object, sorted
+    fn keys(value: &serde_json::Value) -> Vec<String> {
+        let mut keys = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    /// Returns the names of the fields, sorted
+    fn names(fields: &Fields) -> Vec<String> {
+        let mut names = fields
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rows_have_the_fields_of_the_schema() {
+        let schema = schema();
+        let row = as_row(&stats(1), &run());
+        assert_eq!(keys(&row), names(schema.fields()));
+
+        let DataType::List(transaction) =
+            schema.field_with_name("transactions").unwrap().data_type()
+        else {
+            panic!("transactions is not a list");
+        };
+        let DataType::Struct(transaction) = transaction.data_type() else {
+            panic!("transactions is not a list of structs");
+        };
+        assert_eq!(keys(&row["transactions"][0]), names(transaction));
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let stats = [stats(1), stats(2)];
+        let lines = to_json_lines(&stats, &run());
+
+        let batches = ReaderBuilder::new(Arc::new(schema()))
+            .with_strict_mode(true)
+            .build(Cursor::new(lines))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+            2
+        );
+        assert_eq!(batches[0].schema().as_ref(), &schema());
+    }
+
+    #[test]
+    fn parquet_round_trip() {
+        let stats = (0..3).map(stats).collect::<Vec<_>>();
+        let parquet = to_parquet(&stats, &run()).unwrap();
+
+        // The reader needs a file (or bytes) to seek into
+        let path = std::env::temp_dir().join(format!(
+            "coordinator-structured-{}.parquet",
+            std::process::id()
+        ));
+        std::fs::write(&path, parquet).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+            3
+        );
+        assert_eq!(batches[0].schema().fields(), schema().fields());
+    }
+
+    #[test]
+    fn run_info_digests_the_circuit_config() {
+        let default = run();
+        let mut circuit_config = CircuitConfig::default();
+        circuit_config.set_circuit_size(Circuit::Cpu, 10..20);
+        let configured = RunInfo::new(None, &circuit_config);
+
+        assert_ne!(
+            default.circuit_config_digest,
+            configured.circuit_config_digest
+        );
+    }
+}
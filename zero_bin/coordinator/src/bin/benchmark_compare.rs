@@ -0,0 +1,84 @@
+//! Compares benchmark outputs of the coordinator against a baseline, exiting
+//! with a non-zero status if any of them regressed.
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use coordinator::compare::{read_benchmark_csv, CompareError, Comparison};
+
+/// The exit code returned when a candidate regressed
+const REGRESSION_EXIT_CODE: u8 = 1;
+/// The exit code returned when the comparison could not be made
+const ERROR_EXIT_CODE: u8 = 2;
+
+/// Compares benchmark CSVs, as written by the coordinator, by block number.
+#[derive(Parser)]
+struct Cli {
+    /// The benchmark output used as the baseline
+    baseline: PathBuf,
+    /// The benchmark outputs compared against the baseline
+    #[arg(required = true)]
+    candidates: Vec<PathBuf>,
+    /// The maximum relative slowdown tolerated, e.g. 0.05 for 5%
+    #[arg(long, short, default_value_t = 0.05)]
+    threshold: f64,
+    /// Also report a regression if any single block slowed down by more than
+    /// the threshold, rather than only the aggregate proof time per gas
+    #[arg(long)]
+    per_block: bool,
+    /// Print the comparisons as JSON rather than as a table
+    #[arg(long)]
+    json: bool,
+}
+
+fn compare(cli: &Cli) -> Result<Vec<Comparison>, CompareError> {
+    let baseline = read_benchmark_csv(&cli.baseline)?;
+    cli.candidates
+        .iter()
+        .map(|path| {
+            let candidate = read_benchmark_csv(path)?;
+            Comparison::new(&baseline, &candidate, path.clone(), cli.threshold)
+        })
+        .collect()
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let comparisons = match compare(&cli) {
+        Ok(comparisons) => comparisons,
+        Err(err) => {
+            eprintln!("Failed to compare the benchmark outputs: {}", err);
+            return ExitCode::from(ERROR_EXIT_CODE);
+        }
+    };
+
+    match cli.json {
+        true => match serde_json::to_string_pretty(&comparisons) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("Failed to serialize the comparisons: {}", err);
+                return ExitCode::from(ERROR_EXIT_CODE);
+            }
+        },
+        false => {
+            println!("Baseline: {}", cli.baseline.display());
+            for comparison in &comparisons {
+                println!("\n{}", comparison.report());
+            }
+        }
+    }
+
+    let regressed: Vec<&Comparison> = comparisons
+        .iter()
+        .filter(|comparison| comparison.is_regression(cli.per_block))
+        .collect();
+    match regressed.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => {
+            for comparison in regressed {
+                eprintln!("Regression: {}", comparison.candidate_path.display());
+            }
+            ExitCode::from(REGRESSION_EXIT_CODE)
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Replays a dead letter of the coordinator locally, proving its block with
+//! in-memory workers so that the failure can be reproduced and debugged.
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use coordinator::{config::CoordinatorConfig, deadletter::DeadLetter, psm::load_psm};
+use dotenvy::dotenv;
+use ops::register;
+use paladin::runtime::Runtime;
+use prover::BenchmarkedGeneratedBlockProof;
+use tracing::{error, info, warn};
+use zero_bin_common::prover_state::TableLoadStrategy;
+
+/// Proves the block of a dead letter, as written by the coordinator, with the
+/// prover state of the coordinator's configuration (see `COORDINATOR_CONFIG`).
+#[derive(Parser)]
+struct Cli {
+    /// The dead letter to replay
+    dead_letter: PathBuf,
+    /// The number of in-memory workers.  Default is the configured number of
+    /// workers, otherwise Paladin's default.
+    #[arg(long)]
+    num_workers: Option<usize>,
+    /// Save the inputs of the failing transaction to `./debug` on error
+    #[arg(long)]
+    save_inputs_on_error: bool,
+    /// If provided, the proof of the block is written to the file as JSON
+    #[arg(long)]
+    proof_output: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    leader::init::tracing();
+    let cli = Cli::parse();
+
+    let dead_letter = DeadLetter::read(&cli.dead_letter)?;
+    info!(
+        "Replaying block {} of job {} ({} failure after {} attempt(s), txn {:?}): {}",
+        dead_letter.block_number,
+        dead_letter.job_id,
+        dead_letter.class,
+        dead_letter.attempts,
+        dead_letter.txn_index,
+        dead_letter.error
+    );
+
+    // The workers always run in memory, whatever the configured runtime
+    let config = CoordinatorConfig::from_env()?;
+    let mut paladin_config = config.paladin_config();
+    paladin_config.runtime = paladin::config::Runtime::InMemory;
+    paladin_config.num_workers = cli.num_workers.or(config.paladin.num_workers);
+    paladin_config.amqp_uri = None;
+
+    load_psm(&config.prover_state)
+        .with_load_strategy(TableLoadStrategy::Monolithic)
+        .initialize()?;
+    let runtime = Runtime::from_config(&paladin_config, register()).await?;
+
+    // The block is proven on its own, as the start of a new chain
+    let proof = dead_letter
+        .input
+        .prove_and_benchmark(
+            &runtime,
+            None::<futures::future::Ready<Result<BenchmarkedGeneratedBlockProof>>>,
+            true,
+            cli.save_inputs_on_error,
+            None,
+        )
+        .await;
+    runtime.close().await?;
+
+    let proof = match proof {
+        Ok(proof) => proof,
+        Err(err) => {
+            error!(
+                "Replaying block {} failed: {:#}",
+                dead_letter.block_number, err
+            );
+            return Err(err);
+        }
+    };
+    warn!(
+        "Block {} was proven in {} secs, the failure did not reproduce",
+        dead_letter.block_number,
+        proof.total_dur.unwrap_or_default().as_secs_f64()
+    );
+
+    if let Some(filepath) = cli.proof_output {
+        std::fs::write(&filepath, serde_json::to_vec(&proof.proof)?)?;
+        info!("Wrote the proof to {:?}", filepath);
+    }
+
+    Ok(())
+}
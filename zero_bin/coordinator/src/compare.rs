@@ -0,0 +1,596 @@
+//! This module compares benchmark outputs, as written by
+//! [crate::benchmarking::BenchmarkingOutput::publish], to detect regressions.
+//!
+//! The outputs are joined by block number, and the proof times of a candidate
+//! are compared to those of the baseline both per block and in aggregate.
+use std::{
+    collections::{btree_map::Entry, BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+//==================================================================================
+// Errors
+//==================================================================================
+
+#[derive(Debug)]
+pub enum CompareError {
+    /// Returned when the benchmark output could not be read
+    Read(PathBuf, std::io::Error),
+    /// Returned when the benchmark output is empty
+    Empty(PathBuf),
+    /// Returned when a required column is missing from the header
+    MissingColumn(PathBuf, &'static str),
+    /// Returned when a value could not be parsed, with the line number and
+    /// column
+    Parse(PathBuf, usize, &'static str),
+    /// Returned when a block appears more than once, with the line number of
+    /// the duplicate and the block number
+    DuplicateBlock(PathBuf, usize, u64),
+    /// Returned when the baseline and the candidate have no block in common
+    NoCommonBlocks(PathBuf),
+}
+
+impl std::fmt::Display for CompareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self, f)
+    }
+}
+
+impl std::error::Error for CompareError {}
+
+//==================================================================================
+// Benchmark Records
+//==================================================================================
+
+/// The statistics of a single block needed for the comparison
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BenchmarkRecord {
+    /// The block number of the block proved
+    pub block_number: u64,
+    /// The number of transactions in the block
+    pub n_txs: u64,
+    /// The gas used by the block
+    pub gas_used: u64,
+    /// The time taken to prove the block
+    #[serde(serialize_with = "serialize_secs")]
+    pub proof_duration: Duration,
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+/// Reads the benchmark CSV at `path`, returning its records by block number.
+/// A block appearing twice is an error, as it can't be told which of the
+/// proofs to compare.
+///
+/// Only the columns needed for the comparison are read, so outputs written
+/// before columns were appended can still be compared.
+pub fn read_benchmark_csv(path: &Path) -> Result<BTreeMap<u64, BenchmarkRecord>, CompareError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|err| CompareError::Read(path.into(), err))?;
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+    let header = lines
+        .next()
+        .ok_or_else(|| CompareError::Empty(path.into()))?;
+    let columns: HashMap<&str, usize> = header
+        .split(',')
+        .enumerate()
+        .map(|(i, column)| (column.trim(), i))
+        .collect();
+    let column = |name: &'static str| {
+        columns
+            .get(name)
+            .copied()
+            .ok_or_else(|| CompareError::MissingColumn(path.into(), name))
+    };
+    let block_number_col = column("block_number")?;
+    let n_txs_col = column("number_txs")?;
+    let gas_used_col = column("gas_used")?;
+    let proof_duration_col = column("unique_proof_duration")?;
+
+    let mut records = BTreeMap::new();
+    for (i, line) in lines.enumerate() {
+        // The header is line 1
+        let line_number = i + 2;
+        let values: Vec<&str> = line.split(',').map(str::trim).collect();
+        let value = |col: usize, name: &'static str| {
+            values
+                .get(col)
+                .copied()
+                .ok_or_else(|| CompareError::Parse(path.into(), line_number, name))
+        };
+        let parse_u64 = |col: usize, name: &'static str| {
+            value(col, name)?
+                .parse::<u64>()
+                .map_err(|_| CompareError::Parse(path.into(), line_number, name))
+        };
+
+        let record = BenchmarkRecord {
+            block_number: parse_u64(block_number_col, "block_number")?,
+            n_txs: parse_u64(n_txs_col, "number_txs")?,
+            gas_used: parse_u64(gas_used_col, "gas_used")?,
+            proof_duration: value(proof_duration_col, "unique_proof_duration")?
+                .parse::<f64>()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .ok_or_else(|| {
+                    CompareError::Parse(path.into(), line_number, "unique_proof_duration")
+                })?,
+        };
+        match records.entry(record.block_number) {
+            Entry::Vacant(entry) => {
+                entry.insert(record);
+            }
+            Entry::Occupied(_) => {
+                return Err(CompareError::DuplicateBlock(
+                    path.into(),
+                    line_number,
+                    record.block_number,
+                ))
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+//==================================================================================
+// Comparison
+//==================================================================================
+
+/// Returns the relative change from `baseline` to `candidate`, or None if the
+/// baseline is 0.
+fn relative_change(baseline: f64, candidate: f64) -> Option<f64> {
+    match baseline == 0.0 {
+        true => None,
+        false => Some((candidate - baseline) / baseline),
+    }
+}
+
+/// The comparison of a block present in both the baseline and the candidate
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockDelta {
+    pub block_number: u64,
+    pub baseline: BenchmarkRecord,
+    pub candidate: BenchmarkRecord,
+}
+
+impl BlockDelta {
+    /// Returns the relative change of the proof duration.  Positive values are
+    /// slower.
+    pub fn proof_duration_change(&self) -> Option<f64> {
+        relative_change(
+            self.baseline.proof_duration.as_secs_f64(),
+            self.candidate.proof_duration.as_secs_f64(),
+        )
+    }
+
+    /// Returns true if the block got slower by more than the `threshold`
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        self.proof_duration_change()
+            .is_some_and(|change| change > threshold)
+    }
+}
+
+/// The aggregated statistics of the blocks compared
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct AggregateStats {
+    /// The number of blocks
+    pub n_blocks: u64,
+    /// The number of transactions in all the blocks
+    pub n_txs: u64,
+    /// The gas used by all the blocks
+    pub gas_used: u64,
+    /// The sum of the proof durations of all the blocks
+    #[serde(serialize_with = "serialize_secs")]
+    pub proof_duration: Duration,
+}
+
+impl AggregateStats {
+    fn push(&mut self, record: &BenchmarkRecord) {
+        self.n_blocks += 1;
+        self.n_txs += record.n_txs;
+        self.gas_used += record.gas_used;
+        self.proof_duration += record.proof_duration;
+    }
+
+    /// Returns the proof time in seconds per million gas
+    pub fn secs_per_mgas(&self) -> Option<f64> {
+        (self.gas_used > 0)
+            .then(|| self.proof_duration.as_secs_f64() / (self.gas_used as f64 / 1_000_000.0))
+    }
+
+    /// Returns the proof time in seconds per transaction
+    pub fn secs_per_txn(&self) -> Option<f64> {
+        (self.n_txs > 0).then(|| self.proof_duration.as_secs_f64() / self.n_txs as f64)
+    }
+
+    /// Returns the throughput in gas proven per second
+    pub fn gas_per_sec(&self) -> Option<f64> {
+        let secs = self.proof_duration.as_secs_f64();
+        (secs > 0.0).then(|| self.gas_used as f64 / secs)
+    }
+
+    /// Returns the throughput in transactions proven per second
+    pub fn txns_per_sec(&self) -> Option<f64> {
+        let secs = self.proof_duration.as_secs_f64();
+        (secs > 0.0).then(|| self.n_txs as f64 / secs)
+    }
+}
+
+/// The comparison of a candidate benchmark output against the baseline
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    /// The path of the candidate benchmark output
+    pub candidate_path: PathBuf,
+    /// The maximum relative slowdown tolerated before reporting a regression
+    pub threshold: f64,
+    /// The blocks present in both outputs, by block number
+    pub blocks: Vec<BlockDelta>,
+    /// The blocks only present in one of the outputs
+    pub unmatched_blocks: Vec<u64>,
+    pub baseline: AggregateStats,
+    pub candidate: AggregateStats,
+}
+
+impl Comparison {
+    /// Joins the baseline and the candidate by block number and compares them
+    pub fn new(
+        baseline: &BTreeMap<u64, BenchmarkRecord>,
+        candidate: &BTreeMap<u64, BenchmarkRecord>,
+        candidate_path: PathBuf,
+        threshold: f64,
+    ) -> Result<Self, CompareError> {
+        let mut comparison = Self {
+            candidate_path,
+            threshold,
+            blocks: Vec::new(),
+            unmatched_blocks: Vec::new(),
+            baseline: AggregateStats::default(),
+            candidate: AggregateStats::default(),
+        };
+
+        for (block_number, baseline_record) in baseline {
+            match candidate.get(block_number) {
+                Some(candidate_record) => {
+                    comparison.baseline.push(baseline_record);
+                    comparison.candidate.push(candidate_record);
+                    comparison.blocks.push(BlockDelta {
+                        block_number: *block_number,
+                        baseline: *baseline_record,
+                        candidate: *candidate_record,
+                    });
+                }
+                None => comparison.unmatched_blocks.push(*block_number),
+            }
+        }
+        comparison.unmatched_blocks.extend(
+            candidate
+                .keys()
+                .filter(|block_number| !baseline.contains_key(block_number)),
+        );
+        comparison.unmatched_blocks.sort_unstable();
+
+        match comparison.blocks.is_empty() {
+            true => Err(CompareError::NoCommonBlocks(comparison.candidate_path)),
+            false => Ok(comparison),
+        }
+    }
+
+    /// Returns the relative change of the proof time per gas, or of the proof
+    /// time per block if the blocks used no gas.  Positive values are slower.
+    pub fn proof_time_change(&self) -> Option<f64> {
+        match (
+            self.baseline.secs_per_mgas(),
+            self.candidate.secs_per_mgas(),
+        ) {
+            (Some(baseline), Some(candidate)) => relative_change(baseline, candidate),
+            _ => relative_change(
+                self.baseline.proof_duration.as_secs_f64(),
+                self.candidate.proof_duration.as_secs_f64(),
+            ),
+        }
+    }
+
+    /// Returns the blocks which got slower by more than the threshold
+    pub fn regressed_blocks(&self) -> impl Iterator<Item = &BlockDelta> {
+        self.blocks
+            .iter()
+            .filter(|block| block.is_regression(self.threshold))
+    }
+
+    /// Returns true if the candidate is slower than the baseline by more than
+    /// the threshold in aggregate, or, if `per_block`, for any single block
+    pub fn is_regression(&self, per_block: bool) -> bool {
+        self.proof_time_change()
+            .is_some_and(|change| change > self.threshold)
+            || (per_block && self.regressed_blocks().next().is_some())
+    }
+
+    /// Returns a human readable report of the comparison
+    pub fn report(&self) -> String {
+        fn fmt_opt(value: Option<f64>) -> String {
+            match value {
+                Some(value) => format!("{:.3}", value),
+                None => String::from("-"),
+            }
+        }
+        fn fmt_change(value: Option<f64>) -> String {
+            match value {
+                Some(value) => format!("{:+.2}%", value * 100.0),
+                None => String::from("-"),
+            }
+        }
+
+        let mut lines = vec![
+            format!("Candidate: {}", self.candidate_path.display()),
+            format!(
+                "{:>12} {:>8} {:>14} {:>12} {:>12} {:>10} {:>14} {:>14}",
+                "block",
+                "txs",
+                "gas_used",
+                "baseline_s",
+                "candidate_s",
+                "change",
+                "base_s_per_tx",
+                "cand_s_per_tx"
+            ),
+        ];
+        for block in &self.blocks {
+            let per_txn = |record: &BenchmarkRecord| {
+                (record.n_txs > 0)
+                    .then(|| record.proof_duration.as_secs_f64() / record.n_txs as f64)
+            };
+            lines.push(format!(
+                "{:>12} {:>8} {:>14} {:>12.3} {:>12.3} {:>10} {:>14} {:>14}{}",
+                block.block_number,
+                block.candidate.n_txs,
+                block.candidate.gas_used,
+                block.baseline.proof_duration.as_secs_f64(),
+                block.candidate.proof_duration.as_secs_f64(),
+                fmt_change(block.proof_duration_change()),
+                fmt_opt(per_txn(&block.baseline)),
+                fmt_opt(per_txn(&block.candidate)),
+                match block.is_regression(self.threshold) {
+                    true => "  REGRESSION",
+                    false => "",
+                }
+            ));
+        }
+
+        let aggregate = |name: &str, baseline: Option<f64>, candidate: Option<f64>| {
+            format!(
+                "{:<20} {:>14} {:>14} {:>10}",
+                name,
+                fmt_opt(baseline),
+                fmt_opt(candidate),
+                fmt_change(
+                    baseline
+                        .zip(candidate)
+                        .and_then(|(b, c)| relative_change(b, c))
+                )
+            )
+        };
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<20} {:>14} {:>14} {:>10}",
+            "aggregate", "baseline", "candidate", "change"
+        ));
+        lines.push(aggregate(
+            "proof_time_s",
+            Some(self.baseline.proof_duration.as_secs_f64()),
+            Some(self.candidate.proof_duration.as_secs_f64()),
+        ));
+        lines.push(aggregate(
+            "s_per_mgas",
+            self.baseline.secs_per_mgas(),
+            self.candidate.secs_per_mgas(),
+        ));
+        lines.push(aggregate(
+            "s_per_txn",
+            self.baseline.secs_per_txn(),
+            self.candidate.secs_per_txn(),
+        ));
+        lines.push(aggregate(
+            "gas_per_s",
+            self.baseline.gas_per_sec(),
+            self.candidate.gas_per_sec(),
+        ));
+        lines.push(aggregate(
+            "txns_per_s",
+            self.baseline.txns_per_sec(),
+            self.candidate.txns_per_sec(),
+        ));
+
+        lines.push(String::new());
+        lines.push(format!(
+            "Compared {} block(s), {} block(s) regressed by more than {:.2}%",
+            self.blocks.len(),
+            self.regressed_blocks().count(),
+            self.threshold * 100.0
+        ));
+        if !self.unmatched_blocks.is_empty() {
+            lines.push(format!(
+                "Blocks missing from one of the outputs: {:?}",
+                self.unmatched_blocks
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Writes `contents` to a file in the temporary directory, named after
+    /// the test
+    fn write_csv(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "coordinator-compare-{}-{}.csv",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn record(block_number: u64, n_txs: u64, gas_used: u64, secs: u64) -> BenchmarkRecord {
+        BenchmarkRecord {
+            block_number,
+            n_txs,
+            gas_used,
+            proof_duration: Duration::from_secs(secs),
+        }
+    }
+
+    fn records(records: &[BenchmarkRecord]) -> BTreeMap<u64, BenchmarkRecord> {
+        records
+            .iter()
+            .map(|record| (record.block_number, *record))
+            .collect()
+    }
+
+    #[test]
+    fn reads_the_needed_columns() {
+        let path = write_csv(
+            "columns",
+            "block_number, number_txs, extra, gas_used, unique_proof_duration\n\
+             2, 3, x, 21000, 1.5\n\
+             \n\
+             1, 0, y, 0, 0.25\n",
+        );
+        let records = read_benchmark_csv(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(records[&2].n_txs, 3);
+        assert_eq!(records[&2].gas_used, 21000);
+        assert_eq!(records[&2].proof_duration, Duration::from_millis(1500));
+        assert_eq!(records[&1].proof_duration, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn rejects_a_missing_column() {
+        let path = write_csv("missing", "block_number, number_txs, gas_used\n1, 1, 1\n");
+        let result = read_benchmark_csv(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(CompareError::MissingColumn(_, "unique_proof_duration"))
+        ));
+    }
+
+    #[test]
+    fn rejects_an_invalid_value() {
+        let path = write_csv(
+            "invalid",
+            "block_number, number_txs, gas_used, unique_proof_duration\n\
+             1, 1, 1, 1.0\n\
+             2, 1, 1, -1.0\n",
+        );
+        let result = read_benchmark_csv(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(CompareError::Parse(_, 3, "unique_proof_duration"))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_duplicate_block() {
+        let path = write_csv(
+            "duplicate",
+            "block_number, number_txs, gas_used, unique_proof_duration\n\
+             1, 1, 1, 1.0\n\
+             1, 1, 1, 2.0\n",
+        );
+        let result = read_benchmark_csv(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(CompareError::DuplicateBlock(_, 3, 1))));
+    }
+
+    #[test]
+    fn rejects_an_empty_file() {
+        let path = write_csv("empty", "\n");
+        let result = read_benchmark_csv(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(CompareError::Empty(_))));
+    }
+
+    #[test]
+    fn relative_change_of_zero_baseline() {
+        assert_eq!(relative_change(2.0, 3.0), Some(0.5));
+        assert_eq!(relative_change(2.0, 1.0), Some(-0.5));
+        assert_eq!(relative_change(0.0, 1.0), None);
+    }
+
+    #[test]
+    fn joins_by_block_number() {
+        let baseline = records(&[record(1, 1, 1_000_000, 10), record(2, 2, 1_000_000, 10)]);
+        let candidate = records(&[record(2, 2, 1_000_000, 12), record(3, 1, 1_000_000, 1)]);
+        let comparison =
+            Comparison::new(&baseline, &candidate, PathBuf::from("candidate.csv"), 0.1).unwrap();
+
+        assert_eq!(comparison.blocks.len(), 1);
+        assert_eq!(comparison.blocks[0].block_number, 2);
+        assert_eq!(comparison.unmatched_blocks, vec![1, 3]);
+        assert_eq!(comparison.baseline.n_blocks, 1);
+        assert_eq!(comparison.candidate.proof_duration, Duration::from_secs(12));
+    }
+
+    #[test]
+    fn fails_without_common_blocks() {
+        let baseline = records(&[record(1, 1, 1, 1)]);
+        let candidate = records(&[record(2, 1, 1, 1)]);
+
+        assert!(matches!(
+            Comparison::new(&baseline, &candidate, PathBuf::from("candidate.csv"), 0.1),
+            Err(CompareError::NoCommonBlocks(_))
+        ));
+    }
+
+    #[test]
+    fn detects_regressions() {
+        let baseline = records(&[record(1, 1, 1_000_000, 10), record(2, 1, 1_000_000, 10)]);
+        // Block 2 is 50% slower, the aggregate 20% slower
+        let candidate = records(&[record(1, 1, 1_000_000, 9), record(2, 1, 1_000_000, 15)]);
+
+        let comparison =
+            Comparison::new(&baseline, &candidate, PathBuf::from("candidate.csv"), 0.3).unwrap();
+        assert_eq!(
+            comparison
+                .regressed_blocks()
+                .map(|block| block.block_number)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert!(!comparison.is_regression(false));
+        assert!(comparison.is_regression(true));
+
+        let comparison =
+            Comparison::new(&baseline, &candidate, PathBuf::from("candidate.csv"), 0.1).unwrap();
+        assert!(comparison.is_regression(false));
+    }
+
+    #[test]
+    fn compares_the_proof_time_without_gas() {
+        let baseline = records(&[record(1, 0, 0, 10)]);
+        let candidate = records(&[record(1, 0, 0, 5)]);
+        let comparison =
+            Comparison::new(&baseline, &candidate, PathBuf::from("candidate.csv"), 0.1).unwrap();
+
+        assert_eq!(comparison.proof_time_change(), Some(-0.5));
+        assert!(!comparison.is_regression(true));
+    }
+}
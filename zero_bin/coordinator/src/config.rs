@@ -0,0 +1,935 @@
+//! This module contains the configuration of the coordinator.
+//!
+//! The configuration is read from the TOML file at [CONFIG_PATH_ENVKEY] (if
+//! set), after which the environment variables below override the values of
+//! the file.  Anything left unspecified takes its default value.  The whole
+//! configuration is validated before the coordinator starts, so that a
+//! misconfiguration is reported with a descriptive [ConfigError] rather than
+//! surfacing in the middle of a job.
+use std::{env, path::PathBuf, str::FromStr, sync::OnceLock};
+
+use paladin::config::{Config, Runtime, Serializer};
+use rpc::{cache::CacheConfig, throttle::ThrottleConfig};
+use serde::{Deserialize, Deserializer};
+use tracing::{info, warn};
+use zero_bin_common::{
+    auth::AuthConfig,
+    prover_state::circuit::{Circuit, CircuitConfig, CircuitSize, NUM_TABLES},
+};
+
+use crate::{
+    benchmarking::BenchmarkOutputConfig, jobs::JOB_STORE_DIR_DFLT, proofout::ProofOutputMethod,
+    scheduler::DFLT_MAX_CONCURRENT_JOBS,
+};
+
+//===========================================================================================
+// Environment
+//===========================================================================================
+
+/// The environment key for the path of the configuration file
+pub const CONFIG_PATH_ENVKEY: &str = "COORDINATOR_CONFIG";
+/// The environment key overriding [ServerConfig::addr]
+pub const SERVER_ADDR_ENVKEY: &str = "SERVER_ADDR";
+/// The environment key overriding [ServerConfig::max_body_bytes]
+pub const MAX_BODY_BYTES_ENVKEY: &str = "MAX_REQUEST_BODY_BYTES";
+/// The environment key overriding [PaladinConfig::serializer]
+pub const PALADIN_SERIALIZER_ENVKEY: &str = "PALADIN_SERIALIZER";
+/// The environment key overriding [PaladinConfig::runtime]
+pub const PALADIN_RUNTIME_ENVKEY: &str = "PALADIN_RUNTIME";
+/// The environment key overriding [PaladinConfig::num_workers]
+pub const PALADIN_AMQP_NUM_WORKERS_ENVKEY: &str = "PALADIN_AMQP_NUM_WORKERS";
+/// The environment key overriding [PaladinConfig::amqp_uri]
+pub const PALADIN_AMQP_URI_ENVKEY: &str = "PALADIN_AMQP_URI";
+/// The environment key overriding [ProverStateConfig::table_load_strategy]
+pub const PSM_CIRCUIT_TABLE_LOAD_STRAT_ENVKEY: &str = "PSM_TABLE_LOAD_STRAT";
+/// The environment key overriding [ProverStateConfig::persistence]
+pub const PSM_CIRCUIT_PERSISTENCE_ENVKEY: &str = "PSM_CIRCUIT_PERSISTENCE";
+/// The environment key overriding the directory of
+/// [CoordinatorConfig::rpc_cache], enabling the cache if needed
+pub const RPC_CACHE_DIR_ENVKEY: &str = "ZERO_BIN_RPC_CACHE_DIR";
+/// The environment key overriding [ThrottleConfig::batch_size]
+pub const RPC_BATCH_SIZE_ENVKEY: &str = "RPC_BATCH_SIZE";
+/// The environment key overriding [ThrottleConfig::requests_per_second]
+pub const RPC_REQUESTS_PER_SECOND_ENVKEY: &str = "RPC_REQUESTS_PER_SECOND";
+/// The environment key overriding [ThrottleConfig::max_concurrency]
+pub const RPC_MAX_CONCURRENCY_ENVKEY: &str = "RPC_MAX_CONCURRENCY";
+/// The environment key overriding [JobsConfig::store_dir]
+pub const JOB_STORE_DIR_ENVKEY: &str = "JOB_STORE_DIRPATH";
+/// The environment key overriding [JobsConfig::max_concurrent]
+pub const MAX_CONCURRENT_JOBS_ENVKEY: &str = "MAX_CONCURRENT_JOBS";
+/// The environment key overriding [AuthSettings::bearer_token]
+pub const AUTH_TOKEN_ENVKEY: &str = "COORDINATOR_AUTH_TOKEN";
+/// The environment key overriding [AuthSettings::hmac_secret]
+pub const HMAC_SECRET_ENVKEY: &str = "COORDINATOR_HMAC_SECRET";
+
+pub const DFLT_SERVER_ADDR: &str = "0.0.0.0:8080";
+pub const DFLT_SERVER_WORKERS: usize = 4;
+/// The default maximum size of a request body, in bytes
+pub const DFLT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+//===========================================================================================
+// ConfigError
+//===========================================================================================
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Returned when the configuration file cannot be read
+    Read(PathBuf, std::io::Error),
+    /// Returned when the configuration file is not valid TOML, or does not
+    /// match the configuration
+    Parse(PathBuf, toml::de::Error),
+    /// Returned when an environment variable cannot be parsed, with the key,
+    /// the value and the reason
+    Env(&'static str, String, String),
+    /// Returned when the configuration is inconsistent, with the field and the
+    /// reason
+    Invalid(&'static str, String),
+    /// Returned when the configuration of the process is needed before it was
+    /// set (see [init])
+    Uninitialized,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, err) => {
+                write!(f, "failed to read config file {}: {}", path.display(), err)
+            }
+            Self::Parse(path, err) => {
+                write!(f, "failed to parse config file {}: {}", path.display(), err)
+            }
+            Self::Env(key, value, reason) => {
+                write!(f, "invalid value `{}` for {}: {}", value, key, reason)
+            }
+            Self::Invalid(field, reason) => write!(f, "invalid `{}`: {}", field, reason),
+            Self::Uninitialized => write!(f, "the configuration was not initialized"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+//===========================================================================================
+// Configuration
+//===========================================================================================
+
+/// The configuration of the coordinator
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoordinatorConfig {
+    /// The HTTP server receiving the requests
+    pub server: ServerConfig,
+    /// The credentials the requests must present
+    pub auth: AuthSettings,
+    /// The job store and the number of jobs proven concurrently
+    pub jobs: JobsConfig,
+    /// The Paladin runtime the proofs are dispatched to
+    pub paladin: PaladinConfig,
+    /// The prover state, used when the Paladin runtime is in memory
+    pub prover_state: ProverStateConfig,
+    /// The outputs of the requests that do not specify their own
+    pub defaults: DefaultOutputs,
+    /// If provided, the RPC responses of finalized blocks are cached on disk
+    /// and shared by every job fetching from an RPC node
+    pub rpc_cache: Option<CacheConfig>,
+    /// The limits on the requests sent to the RPC nodes, shared by every job
+    pub rpc_throttle: ThrottleConfig,
+}
+
+/// The configuration of the HTTP server
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// The address to listen on.  Default is [DFLT_SERVER_ADDR].
+    pub addr: String,
+    /// The number of server workers.  Default is [DFLT_SERVER_WORKERS].
+    pub workers: usize,
+    /// The maximum size of a request body, in bytes.  Default is
+    /// [DFLT_MAX_BODY_BYTES].
+    pub max_body_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: String::from(DFLT_SERVER_ADDR),
+            workers: DFLT_SERVER_WORKERS,
+            max_body_bytes: DFLT_MAX_BODY_BYTES,
+        }
+    }
+}
+
+/// The credentials the requests must present, see [AuthConfig].  If neither
+/// is provided, every request is accepted.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// The bearer token the requests may present
+    pub bearer_token: Option<String>,
+    /// The secret the requests may be signed with
+    pub hmac_secret: Option<String>,
+}
+
+impl std::fmt::Debug for AuthSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("AuthSettings")
+            .field("bearer_token", &redacted(&self.bearer_token))
+            .field("hmac_secret", &redacted(&self.hmac_secret))
+            .finish()
+    }
+}
+
+/// The configuration of the jobs
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    /// The directory of the job store.  Default is [JOB_STORE_DIR_DFLT].
+    pub store_dir: PathBuf,
+    /// The maximum number of jobs proven concurrently.  Default is
+    /// [DFLT_MAX_CONCURRENT_JOBS].
+    pub max_concurrent: usize,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            store_dir: PathBuf::from(JOB_STORE_DIR_DFLT),
+            max_concurrent: DFLT_MAX_CONCURRENT_JOBS,
+        }
+    }
+}
+
+/// The Paladin runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaladinRuntime {
+    /// Dispatches the proofs to the workers through an AMQP broker
+    Amqp,
+    /// Proves within the coordinator's process
+    #[default]
+    InMemory,
+}
+
+/// The serializer of the Paladin messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaladinSerializer {
+    Postcard,
+    Cbor,
+}
+
+/// The configuration of the Paladin runtime
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaladinConfig {
+    /// The runtime.  Default is [PaladinRuntime::InMemory].
+    pub runtime: PaladinRuntime,
+    /// The serializer.  If not provided, Paladin's default serializer is used.
+    pub serializer: Option<PaladinSerializer>,
+    /// The number of workers, only used when the runtime is in memory.  If not
+    /// provided, Paladin's default is used.
+    pub num_workers: Option<usize>,
+    /// The uri of the AMQP broker, required when the runtime is AMQP.
+    pub amqp_uri: Option<String>,
+}
+
+/// Whether the processed circuits are persisted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Persistence {
+    None,
+    #[default]
+    Disk,
+}
+
+/// How the circuit tables are loaded, see
+/// [zero_bin_common::prover_state::TableLoadStrategy]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadStrategy {
+    #[default]
+    OnDemand,
+    Monolithic,
+}
+
+/// The configuration of the prover state
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProverStateConfig {
+    /// Whether the processed circuits are persisted.  Default is
+    /// [Persistence::Disk].
+    pub persistence: Persistence,
+    /// How the circuit tables are loaded when persisted to disk.  Default is
+    /// [LoadStrategy::OnDemand].
+    pub table_load_strategy: LoadStrategy,
+    /// The sizes of the circuits.  The size of every circuit not provided is
+    /// its default size.
+    pub circuit_sizes: CircuitSizes,
+}
+
+/// The sizes of the circuits, as ranges of degree bits (i.e. `16..23`)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitSizes {
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub arithmetic: Option<CircuitSize>,
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub byte_packing: Option<CircuitSize>,
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub cpu: Option<CircuitSize>,
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub keccak: Option<CircuitSize>,
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub keccak_sponge: Option<CircuitSize>,
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub logic: Option<CircuitSize>,
+    #[serde(deserialize_with = "de_circuit_size")]
+    pub memory: Option<CircuitSize>,
+}
+
+fn de_circuit_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<CircuitSize>, D::Error> {
+    let size = String::deserialize(deserializer)?;
+    CircuitSize::from_str(&size).map(Some).map_err(|err| {
+        serde::de::Error::custom(format!("invalid circuit size `{}`: {}", size, err))
+    })
+}
+
+impl CircuitSizes {
+    fn get(&self, circuit: Circuit) -> &Option<CircuitSize> {
+        match circuit {
+            Circuit::Arithmetic => &self.arithmetic,
+            Circuit::BytePacking => &self.byte_packing,
+            Circuit::Cpu => &self.cpu,
+            Circuit::Keccak => &self.keccak,
+            Circuit::KeccakSponge => &self.keccak_sponge,
+            Circuit::Logic => &self.logic,
+            Circuit::Memory => &self.memory,
+        }
+    }
+
+    fn get_mut(&mut self, circuit: Circuit) -> &mut Option<CircuitSize> {
+        match circuit {
+            Circuit::Arithmetic => &mut self.arithmetic,
+            Circuit::BytePacking => &mut self.byte_packing,
+            Circuit::Cpu => &mut self.cpu,
+            Circuit::Keccak => &mut self.keccak,
+            Circuit::KeccakSponge => &mut self.keccak_sponge,
+            Circuit::Logic => &mut self.logic,
+            Circuit::Memory => &mut self.memory,
+        }
+    }
+
+    /// Returns the [CircuitConfig], using the default size of the circuits not
+    /// provided
+    pub fn circuit_config(&self) -> CircuitConfig {
+        let mut circuit_config = CircuitConfig::default();
+        for circuit in (0..NUM_TABLES).map(Circuit::from) {
+            if let Some(size) = self.get(circuit) {
+                circuit_config.set_circuit_size(circuit, size.clone());
+            }
+        }
+        circuit_config
+    }
+}
+
+/// The outputs used by the requests which do not specify their own
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultOutputs {
+    /// The default proof output, see [crate::input::ProveBlocksInput]
+    pub proof_output: Option<ProofOutputMethod>,
+    /// The default benchmark output, see [crate::input::ProveBlocksInput]
+    pub benchmark_output: Option<BenchmarkOutputConfig>,
+    /// The default dead-letter output, see [crate::input::ProveBlocksInput]
+    pub dead_letter_output: Option<ProofOutputMethod>,
+}
+
+//===========================================================================================
+// Parsing the environment overrides
+//===========================================================================================
+
+/// Normalizes the value of an environment variable, so that i.e. `IN_MEMORY`,
+/// `in-memory` and `in_memory` are equivalent
+fn normalize(value: &str) -> String {
+    value.trim().to_ascii_lowercase().replace('-', "_")
+}
+
+impl FromStr for PaladinRuntime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "amqp" => Ok(Self::Amqp),
+            "in_memory" | "inmemory" | "memory" => Ok(Self::InMemory),
+            _ => Err(String::from("expected `amqp` or `in_memory`")),
+        }
+    }
+}
+
+impl FromStr for PaladinSerializer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "postcard" => Ok(Self::Postcard),
+            "cbor" => Ok(Self::Cbor),
+            _ => Err(String::from("expected `postcard` or `cbor`")),
+        }
+    }
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "none" => Ok(Self::None),
+            "disk" => Ok(Self::Disk),
+            _ => Err(String::from("expected `none` or `disk`")),
+        }
+    }
+}
+
+impl FromStr for LoadStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize(s).as_str() {
+            "on_demand" | "ondemand" => Ok(Self::OnDemand),
+            "monolithic" => Ok(Self::Monolithic),
+            _ => Err(String::from("expected `on_demand` or `monolithic`")),
+        }
+    }
+}
+
+/// Returns the value of the environment variable, if set
+fn env_lookup(key: &'static str) -> Result<Option<String>, ConfigError> {
+    match env::var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(os_str)) => Err(ConfigError::Env(
+            key,
+            os_str.to_string_lossy().into_owned(),
+            String::from("not unicode"),
+        )),
+    }
+}
+
+/// Returns the parsed value of the override at `key`, if set
+fn parse_override<T, L>(key: &'static str, lookup: &L) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+    L: Fn(&'static str) -> Result<Option<String>, ConfigError>,
+{
+    match lookup(key)? {
+        Some(value) => match value.parse() {
+            Ok(parsed) => {
+                info!("Overriding config with {}={}", key, value);
+                Ok(Some(parsed))
+            }
+            Err(err) => Err(ConfigError::Env(key, value, err.to_string())),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the value of the secret override at `key`, if set, without
+/// logging it
+fn secret_override<L>(key: &'static str, lookup: &L) -> Result<Option<String>, ConfigError>
+where
+    L: Fn(&'static str) -> Result<Option<String>, ConfigError>,
+{
+    let value = lookup(key)?;
+    if value.is_some() {
+        info!("Overriding config with {}", key);
+    }
+    Ok(value)
+}
+
+//===========================================================================================
+// Loading
+//===========================================================================================
+
+impl CoordinatorConfig {
+    /// Loads the configuration from the file at [CONFIG_PATH_ENVKEY] (if set)
+    /// and the environment overrides, and validates it.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut config = match parse_override::<PathBuf, _>(CONFIG_PATH_ENVKEY, &env_lookup)? {
+            Some(path) => Self::from_file(path)?,
+            None => {
+                info!("No config file specified, using the defaults");
+                Self::default()
+            }
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads the configuration from a TOML file, without applying the
+    /// environment overrides
+    pub fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
+        info!("Reading config file {}", path.display());
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => return Err(ConfigError::Read(path, err)),
+        };
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(path, err))
+    }
+
+    /// Overrides the configuration with the values set in the environment
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        self.apply_overrides(env_lookup)
+    }
+
+    /// Overrides the configuration with the values returned by `lookup` for
+    /// the environment keys above, which returns `None` for the keys not set
+    pub fn apply_overrides<L>(&mut self, lookup: L) -> Result<(), ConfigError>
+    where
+        L: Fn(&'static str) -> Result<Option<String>, ConfigError>,
+    {
+        if let Some(addr) = parse_override(SERVER_ADDR_ENVKEY, &lookup)? {
+            self.server.addr = addr;
+        }
+        if let Some(max_body_bytes) = parse_override(MAX_BODY_BYTES_ENVKEY, &lookup)? {
+            self.server.max_body_bytes = max_body_bytes;
+        }
+        if let Some(runtime) = parse_override(PALADIN_RUNTIME_ENVKEY, &lookup)? {
+            self.paladin.runtime = runtime;
+        }
+        if let Some(serializer) = parse_override(PALADIN_SERIALIZER_ENVKEY, &lookup)? {
+            self.paladin.serializer = Some(serializer);
+        }
+        if let Some(num_workers) = parse_override(PALADIN_AMQP_NUM_WORKERS_ENVKEY, &lookup)? {
+            self.paladin.num_workers = Some(num_workers);
+        }
+        if let Some(amqp_uri) = parse_override(PALADIN_AMQP_URI_ENVKEY, &lookup)? {
+            self.paladin.amqp_uri = Some(amqp_uri);
+        }
+        if let Some(persistence) = parse_override(PSM_CIRCUIT_PERSISTENCE_ENVKEY, &lookup)? {
+            self.prover_state.persistence = persistence;
+        }
+        if let Some(strategy) = parse_override(PSM_CIRCUIT_TABLE_LOAD_STRAT_ENVKEY, &lookup)? {
+            self.prover_state.table_load_strategy = strategy;
+        }
+        for circuit in (0..NUM_TABLES).map(Circuit::from) {
+            if let Some(size) = parse_override(circuit.as_env_key(), &lookup)? {
+                *self.prover_state.circuit_sizes.get_mut(circuit) = Some(size);
+            }
+        }
+        if let Some(batch_size) = parse_override(RPC_BATCH_SIZE_ENVKEY, &lookup)? {
+            self.rpc_throttle.batch_size = batch_size;
+        }
+        if let Some(requests_per_second) = parse_override(RPC_REQUESTS_PER_SECOND_ENVKEY, &lookup)?
+        {
+            self.rpc_throttle.requests_per_second = Some(requests_per_second);
+        }
+        if let Some(max_concurrency) = parse_override(RPC_MAX_CONCURRENCY_ENVKEY, &lookup)? {
+            self.rpc_throttle.max_concurrency = max_concurrency;
+        }
+        if let Some(dir) = parse_override::<PathBuf, _>(RPC_CACHE_DIR_ENVKEY, &lookup)? {
+            match &mut self.rpc_cache {
+                Some(rpc_cache) => rpc_cache.dir = dir,
+                None => {
+                    self.rpc_cache = Some(CacheConfig {
+                        dir,
+                        max_bytes: rpc::cache::DFLT_CACHE_MAX_BYTES,
+                        offline: false,
+                        finality_depth: rpc::cache::DFLT_FINALITY_DEPTH,
+                    })
+                }
+            }
+        }
+        if let Some(store_dir) = parse_override(JOB_STORE_DIR_ENVKEY, &lookup)? {
+            self.jobs.store_dir = store_dir;
+        }
+        if let Some(max_concurrent) = parse_override(MAX_CONCURRENT_JOBS_ENVKEY, &lookup)? {
+            self.jobs.max_concurrent = max_concurrent;
+        }
+        if let Some(bearer_token) = secret_override(AUTH_TOKEN_ENVKEY, &lookup)? {
+            self.auth.bearer_token = Some(bearer_token);
+        }
+        if let Some(hmac_secret) = secret_override(HMAC_SECRET_ENVKEY, &lookup)? {
+            self.auth.hmac_secret = Some(hmac_secret);
+        }
+        Ok(())
+    }
+
+    /// Checks the configuration for inconsistencies
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.addr.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "server.addr",
+                String::from("must not be empty"),
+            ));
+        }
+        if self.server.workers == 0 {
+            return Err(ConfigError::Invalid(
+                "server.workers",
+                String::from("must be at least 1"),
+            ));
+        }
+        if self.server.max_body_bytes == 0 {
+            return Err(ConfigError::Invalid(
+                "server.max_body_bytes",
+                String::from("must be at least 1"),
+            ));
+        }
+
+        if self.jobs.store_dir.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid(
+                "jobs.store_dir",
+                String::from("must not be empty"),
+            ));
+        }
+        if self.jobs.max_concurrent == 0 {
+            return Err(ConfigError::Invalid(
+                "jobs.max_concurrent",
+                String::from("must be at least 1"),
+            ));
+        }
+
+        match (self.paladin.runtime, &self.paladin.amqp_uri) {
+            (PaladinRuntime::Amqp, None) => {
+                return Err(ConfigError::Invalid(
+                    "paladin.amqp_uri",
+                    String::from("required by the `amqp` runtime"),
+                ))
+            }
+            (PaladinRuntime::Amqp, Some(uri)) => {
+                if let Err(err) = url::Url::parse(uri) {
+                    return Err(ConfigError::Invalid("paladin.amqp_uri", err.to_string()));
+                }
+                if let Some(num_workers) = self.paladin.num_workers {
+                    warn!(
+                        "Not operating in memory, disregarding number of workers ({})",
+                        num_workers
+                    );
+                }
+            }
+            (PaladinRuntime::InMemory, amqp_uri) => {
+                if self.paladin.num_workers == Some(0) {
+                    return Err(ConfigError::Invalid(
+                        "paladin.num_workers",
+                        String::from("must be at least 1"),
+                    ));
+                }
+                if let Some(uri) = amqp_uri {
+                    warn!(
+                        "Ignoring AMQP uri since we are operating in memory ({})",
+                        uri
+                    );
+                }
+            }
+        }
+
+        for (circuit, size) in self.prover_state.circuit_sizes.circuit_config().enumerate() {
+            if size.is_empty() {
+                return Err(ConfigError::Invalid(
+                    "prover_state.circuit_sizes",
+                    format!("the size of the {} circuit ({:?}) is empty", circuit, size),
+                ));
+            }
+        }
+
+        if self.rpc_throttle.batch_size == 0 {
+            return Err(ConfigError::Invalid(
+                "rpc_throttle.batch_size",
+                String::from("must be at least 1"),
+            ));
+        }
+        if self.rpc_throttle.requests_per_second == Some(0) {
+            return Err(ConfigError::Invalid(
+                "rpc_throttle.requests_per_second",
+                String::from("must be at least 1"),
+            ));
+        }
+        if self.rpc_throttle.max_concurrency == 0 {
+            return Err(ConfigError::Invalid(
+                "rpc_throttle.max_concurrency",
+                String::from("must be at least 1"),
+            ));
+        }
+        if let Some(rpc_cache) = &self.rpc_cache {
+            if rpc_cache.max_bytes == 0 {
+                return Err(ConfigError::Invalid(
+                    "rpc_cache.max_bytes",
+                    String::from("must be at least 1"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the [Config] of the Paladin runtime
+    pub fn paladin_config(&self) -> Config {
+        Config {
+            serializer: match self.paladin.serializer {
+                Some(PaladinSerializer::Postcard) => Serializer::Postcard,
+                Some(PaladinSerializer::Cbor) => Serializer::Cbor,
+                None => Serializer::default(),
+            },
+            runtime: match self.paladin.runtime {
+                PaladinRuntime::Amqp => Runtime::Amqp,
+                PaladinRuntime::InMemory => Runtime::InMemory,
+            },
+            num_workers: match self.paladin.runtime {
+                PaladinRuntime::InMemory => self.paladin.num_workers,
+                PaladinRuntime::Amqp => None,
+            },
+            amqp_uri: match self.paladin.runtime {
+                PaladinRuntime::Amqp => self.paladin.amqp_uri.clone(),
+                PaladinRuntime::InMemory => None,
+            },
+        }
+    }
+
+    /// Returns the [AuthConfig] checking the requests
+    pub fn auth_config(&self) -> AuthConfig {
+        AuthConfig::new(
+            self.auth.bearer_token.as_deref(),
+            self.auth.hmac_secret.as_deref(),
+        )
+    }
+
+    /// Returns the [CircuitConfig] of the circuits
+    #[inline]
+    pub fn circuit_config(&self) -> CircuitConfig {
+        self.prover_state.circuit_sizes.circuit_config()
+    }
+}
+
+//===========================================================================================
+// Global configuration
+//===========================================================================================
+
+static CONFIG: OnceLock<CoordinatorConfig> = OnceLock::new();
+
+/// Sets the configuration of the process, returned by [get].  Returns the
+/// configuration back if it was already set.
+pub fn init(config: CoordinatorConfig) -> Result<(), CoordinatorConfig> {
+    CONFIG.set(config)
+}
+
+/// Returns the configuration of the process, which must have been set on
+/// startup (see [init])
+pub fn get() -> Result<&'static CoordinatorConfig, ConfigError> {
+    CONFIG.get().ok_or(ConfigError::Uninitialized)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(toml: &str) -> CoordinatorConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// Asserts that the configuration is rejected because of `field`
+    fn assert_invalid(toml: &str, field: &str) {
+        match parse(toml).validate() {
+            Err(ConfigError::Invalid(invalid, _)) => assert_eq!(invalid, field, "{}", toml),
+            other => panic!("{} was not rejected: {:?}", toml, other),
+        }
+    }
+
+    #[test]
+    fn parses_the_example() {
+        let config = parse(include_str!("../coordinator.example.toml"));
+        config.validate().unwrap();
+
+        assert_eq!(config.server.addr, DFLT_SERVER_ADDR);
+        assert_eq!(config.server.max_body_bytes, DFLT_MAX_BODY_BYTES);
+        assert_eq!(config.paladin.runtime, PaladinRuntime::InMemory);
+        assert_eq!(config.prover_state.persistence, Persistence::Disk);
+        assert_eq!(
+            config.circuit_config().as_degree_bits_ranges(),
+            CircuitConfig::default().as_degree_bits_ranges()
+        );
+        assert!(config.defaults.proof_output.is_some());
+        assert!(config.defaults.benchmark_output.is_some());
+        assert_eq!(config.rpc_throttle.batch_size, 100);
+        assert_eq!(config.rpc_cache.unwrap().finality_depth, 64);
+    }
+
+    #[test]
+    fn defaults_without_settings() {
+        let config = parse("");
+        config.validate().unwrap();
+
+        assert_eq!(config.server.workers, DFLT_SERVER_WORKERS);
+        assert_eq!(config.paladin.serializer, None);
+        assert_eq!(
+            config.prover_state.table_load_strategy,
+            LoadStrategy::OnDemand
+        );
+        assert!(config.defaults.proof_output.is_none());
+        assert!(config.rpc_cache.is_none());
+        assert_eq!(config.jobs.store_dir, PathBuf::from(JOB_STORE_DIR_DFLT));
+        assert_eq!(config.jobs.max_concurrent, DFLT_MAX_CONCURRENT_JOBS);
+        assert!(!config.auth_config().is_enabled());
+    }
+
+    #[test]
+    fn parses_circuit_sizes() {
+        let config = parse("[prover_state.circuit_sizes]\ncpu = \"10..20\"\n");
+        let circuit_config = config.circuit_config();
+
+        assert_eq!(
+            circuit_config.as_degree_bits_ranges()[Circuit::Cpu as usize],
+            10..20
+        );
+        assert_eq!(
+            circuit_config.as_degree_bits_ranges()[Circuit::Memory as usize],
+            Circuit::Memory.default_size()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_malformed_settings() {
+        for toml in [
+            "[server]\nport = 8080\n",
+            "[paladin]\nruntime = \"grpc\"\n",
+            "[prover_state.circuit_sizes]\ncpu = \"10\"\n",
+            "[rpc_throttle]\nbatch_size = -1\n",
+            "[rpc_cache]\nmax_bytes = 1\n",
+            "[auth]\ntoken = \"secret-token\"\n",
+        ] {
+            assert!(
+                toml::from_str::<CoordinatorConfig>(toml).is_err(),
+                "{}",
+                toml
+            );
+        }
+    }
+
+    #[test]
+    fn reports_the_path_of_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("coordinator-config-{}.toml", std::process::id()));
+        assert!(matches!(
+            CoordinatorConfig::from_file(path.clone()),
+            Err(ConfigError::Read(..))
+        ));
+
+        std::fs::write(&path, "[server]\nport = 8080\n").unwrap();
+        let parsed = CoordinatorConfig::from_file(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(parsed, Err(ConfigError::Parse(failed, _)) if failed == path));
+    }
+
+    #[test]
+    fn validates_the_configuration() {
+        assert_invalid("[server]\naddr = \" \"\n", "server.addr");
+        assert_invalid("[server]\nworkers = 0\n", "server.workers");
+        assert_invalid("[server]\nmax_body_bytes = 0\n", "server.max_body_bytes");
+        assert_invalid("[jobs]\nstore_dir = \"\"\n", "jobs.store_dir");
+        assert_invalid("[jobs]\nmax_concurrent = 0\n", "jobs.max_concurrent");
+        assert_invalid("[paladin]\nruntime = \"amqp\"\n", "paladin.amqp_uri");
+        assert_invalid(
+            "[paladin]\nruntime = \"amqp\"\namqp_uri = \"localhost\"\n",
+            "paladin.amqp_uri",
+        );
+        assert_invalid("[paladin]\nnum_workers = 0\n", "paladin.num_workers");
+        assert_invalid(
+            "[prover_state.circuit_sizes]\ncpu = \"20..20\"\n",
+            "prover_state.circuit_sizes",
+        );
+        assert_invalid(
+            "[rpc_throttle]\nbatch_size = 0\n",
+            "rpc_throttle.batch_size",
+        );
+        assert_invalid(
+            "[rpc_throttle]\nrequests_per_second = 0\n",
+            "rpc_throttle.requests_per_second",
+        );
+        assert_invalid(
+            "[rpc_throttle]\nmax_concurrency = 0\n",
+            "rpc_throttle.max_concurrency",
+        );
+        assert_invalid(
+            "[rpc_cache]\ndir = \"rpc_cache\"\nmax_bytes = 0\n",
+            "rpc_cache.max_bytes",
+        );
+
+        // The workers are only used in memory
+        parse("[paladin]\nruntime = \"amqp\"\namqp_uri = \"amqp://localhost:5672\"\nnum_workers = 0\n")
+            .validate()
+            .unwrap();
+    }
+
+    /// Returns a lookup of the overrides in `vars`, standing in for the
+    /// environment
+    fn lookup(
+        vars: &[(&'static str, &str)],
+    ) -> impl Fn(&'static str) -> Result<Option<String>, ConfigError> {
+        let vars: HashMap<&'static str, String> = vars
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect();
+        move |key| Ok(vars.get(key).cloned())
+    }
+
+    #[test]
+    fn overrides_the_file() {
+        let mut config = parse("[server]\naddr = \"127.0.0.1:80\"\nworkers = 2\n");
+        config
+            .apply_overrides(lookup(&[
+                (SERVER_ADDR_ENVKEY, "127.0.0.1:9090"),
+                (PALADIN_RUNTIME_ENVKEY, "AMQP"),
+                (PALADIN_AMQP_URI_ENVKEY, "amqp://localhost:5672"),
+                (Circuit::Cpu.as_env_key(), "10..20"),
+                (RPC_BATCH_SIZE_ENVKEY, "5"),
+                (RPC_CACHE_DIR_ENVKEY, "rpc_cache"),
+                (JOB_STORE_DIR_ENVKEY, "/var/lib/jobs"),
+                (MAX_CONCURRENT_JOBS_ENVKEY, "8"),
+                (AUTH_TOKEN_ENVKEY, "secret-token"),
+            ]))
+            .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.server.addr, "127.0.0.1:9090");
+        assert_eq!(config.server.workers, 2);
+        assert_eq!(config.paladin.runtime, PaladinRuntime::Amqp);
+        assert_eq!(
+            config.circuit_config().as_degree_bits_ranges()[Circuit::Cpu as usize],
+            10..20
+        );
+        assert_eq!(config.rpc_throttle.batch_size, 5);
+        let rpc_cache = config.rpc_cache.as_ref().unwrap();
+        assert_eq!(rpc_cache.dir, PathBuf::from("rpc_cache"));
+        assert_eq!(rpc_cache.max_bytes, rpc::cache::DFLT_CACHE_MAX_BYTES);
+        assert_eq!(config.jobs.store_dir, PathBuf::from("/var/lib/jobs"));
+        assert_eq!(config.jobs.max_concurrent, 8);
+        assert_eq!(config.auth.bearer_token.as_deref(), Some("secret-token"));
+        assert_eq!(config.auth.hmac_secret, None);
+        assert!(config.auth_config().is_enabled());
+        assert!(!format!("{:?}", config).contains("secret-token"));
+    }
+
+    #[test]
+    fn rejects_malformed_overrides() {
+        let malformed = CoordinatorConfig::default()
+            .apply_overrides(lookup(&[(RPC_MAX_CONCURRENCY_ENVKEY, "many")]));
+        assert!(matches!(
+            malformed,
+            Err(ConfigError::Env(RPC_MAX_CONCURRENCY_ENVKEY, value, _)) if value == "many"
+        ));
+
+        let mut config = CoordinatorConfig::default();
+        config
+            .apply_overrides(lookup(&[(MAX_CONCURRENT_JOBS_ENVKEY, "0")]))
+            .unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("jobs.max_concurrent", _))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+//! This module contains the dead-letter store, collecting the blocks that
+//! failed to be proven so that they can be replayed later.
+//!
+//! Each failed block is stored as a [DeadLetter] holding its
+//! [BlockProverInput], the index of the failing transaction and the error, to
+//! `<prefix>/<job_id>/<block_number>.json` of the [ProofOutputMethod] of the
+//! request (the format of the method is ignored, dead letters are always JSON).
+//! A dead letter is replayed with the `replay_dead_letter` binary.
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use prover::{BlockFailure, BlockProverInput};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use zero_bin_common::failure::FailureClass;
+
+use crate::jobs::JobId;
+use crate::proofout::{ProofOutput, ProofOutputBuildError, ProofOutputError, ProofOutputMethod};
+
+#[derive(Debug)]
+pub enum DeadLetterError {
+    /// Returned when we fail to read the dead letter
+    Read(PathBuf, anyhow::Error),
+    /// Returned when we fail to parse the dead letter
+    Parse(PathBuf, anyhow::Error),
+}
+
+impl std::fmt::Display for DeadLetterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self, f)
+    }
+}
+
+impl std::error::Error for DeadLetterError {}
+
+//==================================================================================
+// Dead Letters
+//==================================================================================
+
+/// A block that failed to be proven, along with everything needed to replay it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// The identifier of the job the block belongs to
+    pub job_id: JobId,
+    /// The name of the run, as in the request
+    pub run_name: Option<String>,
+    /// The block number of the block
+    pub block_number: u64,
+    /// The index of the failing transaction within the block, if a transaction
+    /// failed
+    pub txn_index: Option<usize>,
+    /// The class of the failure
+    pub class: FailureClass,
+    /// The number of times the block was attempted
+    pub attempts: u32,
+    /// The description of the last error
+    pub error: String,
+    /// When the block was given up on
+    pub timestamp: DateTime<Utc>,
+    /// The input of the block
+    pub input: BlockProverInput,
+}
+
+impl DeadLetter {
+    /// Returns the [DeadLetter] of the failure within the job
+    pub fn from_failure(job_id: JobId, run_name: Option<String>, failure: &BlockFailure) -> Self {
+        Self {
+            job_id,
+            run_name,
+            block_number: failure.block_number,
+            txn_index: failure.txn_index,
+            class: failure.class,
+            attempts: failure.attempts,
+            error: format!("{:#}", failure.error),
+            timestamp: Utc::now(),
+            input: failure.input.clone(),
+        }
+    }
+
+    /// Returns the key of the dead letter within the store,
+    /// `<job_id>/<block_number>.json`
+    pub fn key(&self) -> String {
+        format!("{}/{}.json", self.job_id, self.block_number)
+    }
+
+    /// Reads a dead letter from a file
+    pub fn read(filepath: &Path) -> Result<Self, DeadLetterError> {
+        let data = std::fs::read(filepath)
+            .map_err(|err| DeadLetterError::Read(filepath.to_path_buf(), err.into()))?;
+        serde_json::from_slice(&data)
+            .map_err(|err| DeadLetterError::Parse(filepath.to_path_buf(), err.into()))
+    }
+}
+
+//==================================================================================
+// Dead-Letter Store
+//==================================================================================
+
+/// The [DeadLetterStore] writes the [DeadLetter]s of a job to one of the proof
+/// output sinks
+#[derive(Debug)]
+pub struct DeadLetterStore {
+    /// The job the dead letters belong to
+    job_id: JobId,
+    /// The name of the run, as in the request
+    run_name: Option<String>,
+    /// The output the dead letters are written to
+    output: ProofOutput,
+}
+
+impl DeadLetterStore {
+    /// Returns the [DeadLetterStore] of the job writing to the output method
+    pub async fn from_method(
+        method: &ProofOutputMethod,
+        job_id: JobId,
+        run_name: Option<String>,
+    ) -> Result<Self, ProofOutputBuildError> {
+        Ok(Self {
+            job_id,
+            run_name,
+            output: ProofOutput::from_method(method).await?,
+        })
+    }
+
+    /// Writes the dead letter of the failure, returning where it was written to
+    pub async fn write(&self, failure: &BlockFailure) -> Result<String, ProofOutputError> {
+        let dead_letter = DeadLetter::from_failure(self.job_id, self.run_name.clone(), failure);
+        let data = serde_json::to_vec(&dead_letter)
+            .map_err(|err| ProofOutputError::SerializationError(err.into()))?;
+        let location = self
+            .output
+            .sink
+            .write_object(&dead_letter.key(), data, "application/json")
+            .await?;
+        info!(
+            "Wrote the dead letter of block {} to {}",
+            dead_letter.block_number, location
+        );
+        Ok(location)
+    }
+}
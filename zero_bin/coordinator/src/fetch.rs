@@ -1,14 +1,35 @@
-//! This is useful for fetching [ProverInput] per block
+//! This is useful for fetching [BlockProverInput] per block
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
 use alloy::{
-    providers::RootProvider,
-    rpc::types::{BlockId, BlockNumberOrTag},
+    providers::Provider,
+    rpc::types::{BlockId, BlockNumberOrTag, BlockTransactionsKind},
+    transports::Transport,
 };
-use anyhow::Error;
-use rpc::{benchmark_prover_input, BenchmarkedProverInput};
-use tracing::info;
-use zero_bin_common::block_interval::BlockInterval;
+use anyhow::{anyhow, Context as _, Error};
+use chrono::{DateTime, Utc};
+use futures::{future, stream::BoxStream, Stream, StreamExt};
+use prover::BlockProverInput;
+use rpc::{
+    cache::RpcCache,
+    hashes::BlockHashWindow,
+    provider::{build_retry_provider, subscribe_new_heads},
+    throttle::Throttle,
+};
+use tokio::sync::Semaphore;
+use tracing::{debug, error, info};
+use zero_bin_common::{block_interval::BlockInterval, metrics::FETCH_DURATION};
 
 use super::input::BlockSource;
+use crate::{
+    config::CoordinatorConfig,
+    objstore::{ObjectStore, ObjectStoreError},
+};
 
 //==============================================================================
 // FetchError
@@ -16,6 +37,32 @@ use super::input::BlockSource;
 #[derive(Debug)]
 pub enum FetchError {
     ZeroBinRpcFetchError(Error),
+    /// Returned when we fail to read the blocks from the given file or
+    /// directory
+    LocalFetchError(String, Error),
+    /// Returned when we fail to read the blocks from the object store
+    ObjectStoreFetchError(ObjectStoreError),
+    /// Returned when we fail to parse the blocks from the given file or object
+    Parse(String, Error),
+    /// Returned when the block source does not contain the given blocks of
+    /// the interval
+    MissingBlocks(Vec<u64>),
+    /// Returned when the block source cannot provide the blocks of the
+    /// interval (i.e. following the chain tip from a file)
+    UnsupportedInterval(BlockInterval),
+    /// Returned with the block number and the error when a block could not be
+    /// fetched and its
+    /// [FailureClass::Fetch](zero_bin_common::failure::FailureClass::Fetch)
+    /// policy skips it.  The following blocks are still fetched.
+    BlockSkipped(u64, Error),
+}
+
+impl FetchError {
+    /// Returns true if only the one block was skipped, rather than the
+    /// fetching having failed
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, Self::BlockSkipped(..))
+    }
 }
 
 impl std::fmt::Display for FetchError {
@@ -46,70 +93,670 @@ impl Default for Checkpoint {
 }
 
 impl Checkpoint {
-    pub fn get_checkpoint_from_blocknum(&self, block_number: u64) -> BlockId {
+    /// Returns the offset of the checkpoint from the first block of the
+    /// interval, or [None] if the checkpoint is a constant block
+    pub fn offset(&self) -> Option<u64> {
         match self {
-            Self::Constant(num @ BlockId::Number(_)) => *num,
-            Self::Constant(BlockId::Hash(_)) => {
-                unreachable!("Coordinator does not support Hash Block IDs")
-            }
-            Self::BlockNumberNegativeOffset(offset) => {
-                BlockId::Number(BlockNumberOrTag::Number(block_number - *offset))
-            }
-            _ => BlockId::Number(BlockNumberOrTag::Number(block_number - 1)),
+            Self::Constant(_) => None,
+            Self::BlockNumberNegativeOffset(offset) => Some(*offset),
+            Self::Default => Some(1),
         }
     }
 
-    pub fn get_checkpoint_from_interval(&self, block_interval: BlockInterval) -> BlockId {
-        match block_interval {
-            BlockInterval::FollowFrom {
-                start_block,
-                block_time: _,
-            } => self.get_checkpoint_from_blocknum(start_block),
-            BlockInterval::Range(range) => self.get_checkpoint_from_blocknum(range.start),
-            BlockInterval::SingleBlockId(BlockId::Number(BlockNumberOrTag::Number(start))) => {
-                self.get_checkpoint_from_blocknum(start)
-            }
-            BlockInterval::SingleBlockId(BlockId::Number(_) | BlockId::Hash(_)) => {
-                todo!("Coordinator only supports Numbers, not Tags or Block Hashes")
-            }
+    pub fn get_checkpoint_from_blocknum(&self, block_number: u64) -> Result<BlockId, Error> {
+        let offset = match self {
+            Self::Constant(block_id) => return Ok(*block_id),
+            Self::BlockNumberNegativeOffset(offset) => *offset,
+            Self::Default => 1,
+        };
+        block_number
+            .checked_sub(offset)
+            .map(|checkpoint| BlockId::Number(BlockNumberOrTag::Number(checkpoint)))
+            .ok_or_else(|| {
+                anyhow!(
+                    "checkpoint offset {} is larger than block number {}",
+                    offset,
+                    block_number
+                )
+            })
+    }
+
+    /// Returns the checkpoint of the interval.  Unless the checkpoint is a
+    /// constant block, an interval of a single block hash or tag must first be
+    /// resolved to its block number (see [resolve_block_interval]).
+    pub fn get_checkpoint_from_interval(
+        &self,
+        block_interval: &BlockInterval,
+    ) -> Result<BlockId, Error> {
+        match (self, first_block_number(block_interval)) {
+            (Self::Constant(block_id), _) => Ok(*block_id),
+            (_, Some(block_number)) => self.get_checkpoint_from_blocknum(block_number),
+            (_, None) => Err(anyhow!(
+                "the checkpoint of block {} requires its block number",
+                block_interval
+            )),
         }
     }
 }
 
-/// Fetches the prover input given the [BlockSource]
-pub async fn fetch(
+/// Returns the number of the first block of the interval, or [None] if the
+/// interval is a single block hash or tag
+pub fn first_block_number(block_interval: &BlockInterval) -> Option<u64> {
+    match block_interval {
+        BlockInterval::FollowFrom { start_block, .. } => Some(*start_block),
+        BlockInterval::Range(range) => Some(range.start),
+        BlockInterval::SingleBlockId(BlockId::Number(BlockNumberOrTag::Number(number))) => {
+            Some(*number)
+        }
+        BlockInterval::SingleBlockId(_) => None,
+    }
+}
+
+/// Resolves an interval of a single block hash or tag (i.e. `latest`) to the
+/// number of the block.  Other intervals are returned as is.
+pub async fn resolve_block_interval<ProviderT, TransportT>(
+    provider: &ProviderT,
+    block_interval: BlockInterval,
+) -> Result<BlockInterval, Error>
+where
+    ProviderT: Provider<TransportT>,
+    TransportT: Transport + Clone,
+{
+    let block_id = match block_interval {
+        BlockInterval::SingleBlockId(block_id) if first_block_number(&block_interval).is_none() => {
+            block_id
+        }
+        block_interval => return Ok(block_interval),
+    };
+    let block_number = provider
+        .get_block(block_id, BlockTransactionsKind::Hashes)
+        .await?
+        .with_context(|| format!("block {} does not exist", block_id))?
+        .header
+        .number
+        .with_context(|| format!("block {} is missing field `number`", block_id))?;
+    info!(
+        "Resolved block {} to block number {}",
+        block_id, block_number
+    );
+    Ok(BlockInterval::SingleBlockId(BlockId::Number(
+        BlockNumberOrTag::Number(block_number),
+    )))
+}
+
+static RPC_CACHE: OnceLock<Option<RpcCache>> = OnceLock::new();
+static RPC_THROTTLE: OnceLock<Throttle> = OnceLock::new();
+static BLOCK_HASH_WINDOW: OnceLock<BlockHashWindow> = OnceLock::new();
+
+/// Returns the RPC cache of the configuration, shared by every job.  The cache
+/// is opened the first time it is needed; if it fails to open, the blocks are
+/// fetched without it.
+fn rpc_cache(config: &CoordinatorConfig) -> Option<RpcCache> {
+    RPC_CACHE
+        .get_or_init(|| {
+            let config = config.rpc_cache.clone()?;
+            match RpcCache::open(config) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    error!(
+                        "Failed to open the RPC cache, fetching without it: {:#}",
+                        err
+                    );
+                    None
+                }
+            }
+        })
+        .clone()
+}
+
+/// Returns the RPC throttle of the configuration, so that the limits apply to
+/// every job together
+fn rpc_throttle(config: &CoordinatorConfig) -> Throttle {
+    RPC_THROTTLE
+        .get_or_init(|| Throttle::new(config.rpc_throttle.clone()))
+        .clone()
+}
+
+/// Returns the window of block hashes shared by every job, so that a job
+/// proving the blocks following those of an earlier job does not fetch their
+/// previous block hashes again
+fn block_hash_window() -> BlockHashWindow {
+    BLOCK_HASH_WINDOW
+        .get_or_init(BlockHashWindow::default)
+        .clone()
+}
+
+/// The default number of blocks fetched concurrently
+pub const DFLT_FETCH_CONCURRENCY: usize = 4;
+/// The default number of blocks that may be fetched ahead of the last proven
+/// block
+pub const DFLT_FETCH_LOOKAHEAD: usize = 8;
+/// The default backoff in milliseconds before the first retry of a request to
+/// the RPC
+pub const DFLT_RPC_BACKOFF: u64 = 1000;
+/// The default number of retries of a request to the RPC
+pub const DFLT_RPC_MAX_RETRIES: u32 = 3;
+
+/// The timings of fetching a single block
+#[derive(Debug, Clone, Copy)]
+pub struct FetchInfo {
+    /// The block number of the fetched block
+    pub block_number: u64,
+    /// The time it took to fetch the block
+    pub fetch_duration: Duration,
+    /// When the fetch started
+    pub start_time: DateTime<Utc>,
+    /// When the fetch completed
+    pub end_time: DateTime<Utc>,
+}
+
+/// A fetched [BlockProverInput] along with its [FetchInfo]
+#[derive(Debug)]
+pub struct FetchedBlock {
+    pub input: BlockProverInput,
+    pub info: FetchInfo,
+}
+
+/// Fetches the prover input given the [BlockSource], yielding each block as
+/// soon as it has been fetched, in order of the [BlockInterval].  For
+/// [BlockInterval::FollowFrom], new blocks are fetched as they are mined and
+/// the stream does not end.
+///
+/// Up to `concurrency` blocks are fetched at once.  Each fetched block takes a
+/// permit from `lookahead`, which should be returned (see
+/// [Semaphore::add_permits]) once the block has been proven, bounding how far
+/// ahead of the proving we fetch.  Closing `lookahead` stops the fetching.
+///
+/// The failed requests to the RPC are retried by its provider, as per the
+/// `backoff` and `max_retries` of the [BlockSource::ZeroBinRpc], so a block
+/// failing to be fetched is not fetched again.  If `skip_failed_blocks`, a
+/// [FetchError::BlockSkipped] is yielded in its place, still holding its
+/// permit, and the fetching goes on.  Any other error ends the stream.
+pub async fn fetch_stream(
     block_interval: BlockInterval,
     checkpoint_method: &Option<Checkpoint>,
     source: &BlockSource,
-) -> Result<BenchmarkedProverInput, FetchError> {
+    concurrency: usize,
+    lookahead: Arc<Semaphore>,
+    skip_failed_blocks: bool,
+) -> Result<impl Stream<Item = Result<FetchedBlock, FetchError>>, FetchError> {
     match source {
         // Use ZeroBing's RPC fetch
-        BlockSource::ZeroBinRpc { rpc_url } => {
+        BlockSource::ZeroBinRpc {
+            rpc_url,
+            rpc_type,
+            backoff,
+            max_retries,
+        } => {
             info!(
-                "Requesting from block {} from RPC ({})",
-                block_interval, rpc_url
+                "Requesting from block {} from {:?} RPC ({})",
+                block_interval, rpc_type, rpc_url
             );
 
-            let checkpoint = checkpoint_method
-                .unwrap_or_default()
-                .get_checkpoint_from_interval(block_interval.clone());
-
             let provider_url = match url::Url::parse(rpc_url) {
                 Ok(url) => url,
                 Err(err) => return Err(FetchError::ZeroBinRpcFetchError(err.into())),
             };
-
-            match benchmark_prover_input(
-                &RootProvider::new_http(provider_url),
-                block_interval,
-                checkpoint,
-                rpc::RpcType::Jerigon,
+            let config =
+                crate::config::get().map_err(|err| FetchError::ZeroBinRpcFetchError(err.into()))?;
+            let provider = build_retry_provider(
+                provider_url.clone(),
+                *backoff,
+                *max_retries,
+                rpc_cache(config),
+                rpc_throttle(config),
             )
             .await
+            .map_err(FetchError::ZeroBinRpcFetchError)?;
+            let rpc_type = rpc_type.clone();
+            let hash_window = block_hash_window();
+
+            let block_interval = resolve_block_interval(&provider, block_interval)
+                .await
+                .map_err(FetchError::ZeroBinRpcFetchError)?;
+            let checkpoint = checkpoint_method
+                .unwrap_or_default()
+                .get_checkpoint_from_interval(&block_interval)
+                .map_err(FetchError::ZeroBinRpcFetchError)?;
+
+            let checkpoint_state_trie_root =
+                match rpc::checkpoint_state_trie_root(&provider, checkpoint).await {
+                    Ok(state_trie_root) => state_trie_root,
+                    Err(err) => return Err(FetchError::ZeroBinRpcFetchError(err)),
+                };
+
+            // Follow-from intervals keep yielding new blocks as they are mined
+            let block_numbers: BoxStream<'static, Result<u64, Error>> = match block_interval {
+                BlockInterval::FollowFrom { .. } => {
+                    // Over WebSocket and IPC, wait for the new heads rather
+                    // than polling the node
+                    let new_heads = match subscribe_new_heads(&provider_url).await {
+                        Ok(new_heads) => new_heads,
+                        Err(err) => return Err(FetchError::ZeroBinRpcFetchError(err)),
+                    };
+                    match block_interval
+                        .into_unbounded_stream(provider.clone(), new_heads)
+                        .await
+                    {
+                        Ok(block_numbers) => block_numbers.boxed(),
+                        Err(err) => return Err(FetchError::ZeroBinRpcFetchError(err)),
+                    }
+                }
+                _ => match block_interval.into_bounded_stream() {
+                    Ok(block_numbers) => block_numbers.map(Ok).boxed(),
+                    Err(err) => return Err(FetchError::ZeroBinRpcFetchError(err)),
+                },
+            };
+
+            let fetches = block_numbers
+                .then(move |block_number| {
+                    let lookahead = lookahead.clone();
+                    async move {
+                        // The permit is returned once the block is proven
+                        lookahead.acquire().await.map(|permit| {
+                            permit.forget();
+                            block_number
+                        })
+                    }
+                })
+                .take_while(|permitted| future::ready(permitted.is_ok()))
+                .filter_map(|permitted| future::ready(permitted.ok()))
+                .map(move |block_number| {
+                    let provider = provider.clone();
+                    let rpc_type = rpc_type.clone();
+                    let hash_window = hash_window.clone();
+                    async move {
+                        let block_number =
+                            block_number.map_err(FetchError::ZeroBinRpcFetchError)?;
+                        debug!("Fetching block {}", block_number);
+                        let start_time: DateTime<Utc> = Utc::now();
+                        let start = Instant::now();
+                        // The requests were already retried by the provider
+                        let input = match rpc::block_prover_input(
+                            &provider,
+                            BlockId::Number(BlockNumberOrTag::Number(block_number)),
+                            checkpoint_state_trie_root,
+                            &rpc_type,
+                            &hash_window,
+                        )
+                        .await
+                        {
+                            Ok(input) => input,
+                            Err(err) if skip_failed_blocks => {
+                                error!("Skipping block {}: {:#}", block_number, err);
+                                return Err(FetchError::BlockSkipped(block_number, err));
+                            }
+                            Err(err) => return Err(FetchError::ZeroBinRpcFetchError(err)),
+                        };
+                        let fetch_duration = start.elapsed();
+                        FETCH_DURATION.observe(fetch_duration.as_secs_f64());
+                        info!(
+                            "Fetched block {} in {} secs",
+                            block_number,
+                            fetch_duration.as_secs_f64()
+                        );
+                        Ok(FetchedBlock {
+                            input,
+                            info: FetchInfo {
+                                block_number,
+                                fetch_duration,
+                                start_time,
+                                end_time: Utc::now(),
+                            },
+                        })
+                    }
+                })
+                .buffered(concurrency.max(1));
+
+            Ok(spawn_fetches(fetches.boxed(), concurrency))
+        }
+        BlockSource::LocalFile { path } => {
+            info!("Reading blocks {} from file {}", block_interval, path);
+            let start_time: DateTime<Utc> = Utc::now();
+            let start = Instant::now();
+            let blocks = read_blocks_file(Path::new(path)).await?;
+            Ok(spawn_fetches(
+                loaded_blocks(blocks, block_interval, start, start_time).await?,
+                1,
+            ))
+        }
+        BlockSource::LocalDirectory { path } => {
+            info!("Reading blocks {} from directory {}", block_interval, path);
+            let start_time: DateTime<Utc> = Utc::now();
+            let start = Instant::now();
+            let mut entries = tokio::fs::read_dir(path)
+                .await
+                .map_err(|err| FetchError::LocalFetchError(path.clone(), err.into()))?;
+            let mut filepaths = Vec::new();
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|err| FetchError::LocalFetchError(path.clone(), err.into()))?
             {
-                Ok(input) => Ok(input),
-                Err(err) => Err(FetchError::ZeroBinRpcFetchError(err)),
+                let filepath = entry.path();
+                if filepath.extension().is_some_and(|ext| ext == "json") {
+                    filepaths.push(filepath);
+                }
+            }
+            filepaths.sort();
+
+            let mut blocks = Vec::new();
+            for filepath in filepaths {
+                blocks.extend(read_blocks_file(&filepath).await?);
             }
+            Ok(spawn_fetches(
+                loaded_blocks(blocks, block_interval, start, start_time).await?,
+                1,
+            ))
         }
+        BlockSource::ObjectStore { store, prefix } => {
+            info!(
+                "Reading blocks {} from objects {} in {:?}",
+                block_interval, prefix, store
+            );
+            let start_time: DateTime<Utc> = Utc::now();
+            let start = Instant::now();
+            let store = ObjectStore::from_config(store)
+                .await
+                .map_err(FetchError::ObjectStoreFetchError)?;
+
+            let mut blocks = Vec::new();
+            for object in store
+                .list(prefix)
+                .await
+                .map_err(FetchError::ObjectStoreFetchError)?
+                .into_iter()
+                .filter(|object| object.ends_with(".json"))
+            {
+                let data = store
+                    .get(&object)
+                    .await
+                    .map_err(FetchError::ObjectStoreFetchError)?;
+                blocks.extend(parse_blocks(&data, &object)?);
+            }
+            Ok(spawn_fetches(
+                loaded_blocks(blocks, block_interval, start, start_time).await?,
+                1,
+            ))
+        }
+    }
+}
+
+/// Reads the [BlockProverInput]s from a JSON file, as produced by `rpc fetch`
+async fn read_blocks_file(filepath: &Path) -> Result<Vec<BlockProverInput>, FetchError> {
+    debug!("Reading blocks from {:?}", filepath);
+    let data = tokio::fs::read(filepath)
+        .await
+        .map_err(|err| FetchError::LocalFetchError(filepath.display().to_string(), err.into()))?;
+    parse_blocks(&data, &filepath.display().to_string())
+}
+
+/// Parses the [BlockProverInput]s from JSON, as produced by `rpc fetch`
+fn parse_blocks(data: &[u8], name: &str) -> Result<Vec<BlockProverInput>, FetchError> {
+    serde_json::from_slice(data).map_err(|err| FetchError::Parse(name.to_string(), err.into()))
+}
+
+/// Returns the stream of the blocks of the [BlockInterval] from the blocks
+/// loaded beforehand, in order of the [BlockInterval].  The fetch timings of
+/// each block are those of loading all the blocks.
+async fn loaded_blocks(
+    blocks: Vec<BlockProverInput>,
+    block_interval: BlockInterval,
+    start: Instant,
+    start_time: DateTime<Utc>,
+) -> Result<BoxStream<'static, Result<FetchedBlock, FetchError>>, FetchError> {
+    let block_numbers: Vec<u64> = match block_interval.clone().into_bounded_stream() {
+        Ok(block_numbers) => block_numbers.collect().await,
+        Err(err) => {
+            error!("Cannot read blocks {}: {}", block_interval, err);
+            return Err(FetchError::UnsupportedInterval(block_interval));
+        }
+    };
+
+    let mut blocks: BTreeMap<u64, BlockProverInput> = blocks
+        .into_iter()
+        .map(|block| (block.get_block_number().to::<u64>(), block))
+        .collect();
+
+    let missing: Vec<u64> = block_numbers
+        .iter()
+        .filter(|block_number| !blocks.contains_key(block_number))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        error!("Missing blocks from the block source: {:?}", missing);
+        return Err(FetchError::MissingBlocks(missing));
+    }
+
+    let fetch_duration = start.elapsed();
+    let end_time: DateTime<Utc> = Utc::now();
+    info!(
+        "Loaded {} blocks in {} secs",
+        block_numbers.len(),
+        fetch_duration.as_secs_f64()
+    );
+
+    let fetched: Vec<Result<FetchedBlock, FetchError>> = block_numbers
+        .into_iter()
+        .map(|block_number| {
+            Ok(FetchedBlock {
+                input: blocks
+                    .remove(&block_number)
+                    .expect("Missing blocks were checked above"),
+                info: FetchInfo {
+                    block_number,
+                    fetch_duration,
+                    start_time,
+                    end_time,
+                },
+            })
+        })
+        .collect();
+    Ok(futures::stream::iter(fetched).boxed())
+}
+
+/// Drives the fetches in their own task, so fetching continues independently
+/// of the proving, returning the stream of fetched blocks.
+fn spawn_fetches(
+    fetches: BoxStream<'static, Result<FetchedBlock, FetchError>>,
+    buffer: usize,
+) -> impl Stream<Item = Result<FetchedBlock, FetchError>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(buffer.max(1));
+
+    tokio::spawn(async move {
+        let mut fetches = fetches;
+        while let Some(fetched) = fetches.next().await {
+            let failed = matches!(&fetched, Err(err) if !err.is_skipped());
+            // Stop fetching once a fetch failed or nobody is listening
+            if tx.send(fetched).await.is_err() || failed {
+                break;
+            }
+        }
+        debug!("Completed fetching");
+    });
+
+    futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|fetched| (fetched, rx))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn number(block_number: u64) -> BlockId {
+        BlockId::Number(BlockNumberOrTag::Number(block_number))
+    }
+
+    #[test]
+    fn checkpoint_from_block_number() {
+        assert_eq!(
+            Checkpoint::Default.get_checkpoint_from_blocknum(5).unwrap(),
+            number(4)
+        );
+        assert_eq!(
+            Checkpoint::BlockNumberNegativeOffset(3)
+                .get_checkpoint_from_blocknum(5)
+                .unwrap(),
+            number(2)
+        );
+        assert_eq!(
+            Checkpoint::Constant(number(1))
+                .get_checkpoint_from_blocknum(5)
+                .unwrap(),
+            number(1)
+        );
+        assert!(Checkpoint::BlockNumberNegativeOffset(6)
+            .get_checkpoint_from_blocknum(5)
+            .is_err());
+    }
+
+    #[test]
+    fn checkpoint_from_interval() {
+        let hash = BlockInterval::new(
+            "0xb51ceca7ba912779ed6721d2b93849758af0d2354683170fb71dead6e439e6cb",
+        )
+        .unwrap();
+        assert_eq!(
+            Checkpoint::Default
+                .get_checkpoint_from_interval(&BlockInterval::Range(5..8))
+                .unwrap(),
+            number(4)
+        );
+        assert_eq!(
+            Checkpoint::Constant(number(1))
+                .get_checkpoint_from_interval(&hash)
+                .unwrap(),
+            number(1)
+        );
+        assert!(Checkpoint::Default
+            .get_checkpoint_from_interval(&hash)
+            .is_err());
+    }
+
+    /// The blocks 2 to 7, as written by `rpc fetch`
+    const BLOCKS_FILE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../tools/artifacts/witness_b2_b7.json"
+    );
+
+    fn blocks_dir(name: &str) -> std::path::PathBuf {
+        let dirpath =
+            std::env::temp_dir().join(format!("coordinator-fetch-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dirpath).unwrap();
+        dirpath
+    }
+
+    /// Returns the numbers of the blocks read from the source, in order
+    async fn fetch_local(
+        block_interval: BlockInterval,
+        source: BlockSource,
+    ) -> Result<Vec<u64>, FetchError> {
+        let lookahead = Arc::new(Semaphore::new(1));
+        fetch_stream(block_interval, &None, &source, 1, lookahead, false)
+            .await?
+            .map(|fetched| {
+                fetched.map(|block| {
+                    assert_eq!(
+                        block.input.get_block_number().to::<u64>(),
+                        block.info.block_number
+                    );
+                    block.info.block_number
+                })
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reads_the_blocks_of_a_file() {
+        let source = || BlockSource::LocalFile {
+            path: BLOCKS_FILE.to_string(),
+        };
+
+        assert_eq!(
+            fetch_local(BlockInterval::Range(3..6), source())
+                .await
+                .unwrap(),
+            vec![3, 4, 5]
+        );
+        assert!(matches!(
+            fetch_local(BlockInterval::Range(6..10), source()).await,
+            Err(FetchError::MissingBlocks(missing)) if missing == vec![8, 9]
+        ));
+        assert!(matches!(
+            fetch_local(BlockInterval::new("2..").unwrap(), source()).await,
+            Err(FetchError::UnsupportedInterval(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_the_blocks_of_a_directory_in_order() {
+        let dirpath = blocks_dir("directory");
+        let blocks: Vec<serde_json::Value> =
+            serde_json::from_slice(&std::fs::read(BLOCKS_FILE).unwrap()).unwrap();
+        // The files are read in order of their names, not of their blocks
+        std::fs::write(
+            dirpath.join("a.json"),
+            serde_json::to_vec(&blocks[3..]).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dirpath.join("b.json"),
+            serde_json::to_vec(&blocks[..3]).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dirpath.join("README.txt"), "not blocks").unwrap();
+
+        let fetched = fetch_local(
+            BlockInterval::Range(2..8),
+            BlockSource::LocalDirectory {
+                path: dirpath.display().to_string(),
+            },
+        )
+        .await;
+        std::fs::remove_dir_all(&dirpath).unwrap();
+
+        assert_eq!(fetched.unwrap(), vec![2, 3, 4, 5, 6, 7]);
+    }
+
+    #[tokio::test]
+    async fn reports_malformed_and_missing_files() {
+        let dirpath = blocks_dir("malformed");
+        let filepath = dirpath.join("blocks.json");
+        std::fs::write(&filepath, "{\"not\": \"blocks\"}").unwrap();
+
+        let from_file = fetch_local(
+            BlockInterval::Range(2..4),
+            BlockSource::LocalFile {
+                path: filepath.display().to_string(),
+            },
+        )
+        .await;
+        let from_directory = fetch_local(
+            BlockInterval::Range(2..4),
+            BlockSource::LocalDirectory {
+                path: dirpath.display().to_string(),
+            },
+        )
+        .await;
+        std::fs::remove_dir_all(&dirpath).unwrap();
+        let from_missing_file = fetch_local(
+            BlockInterval::Range(2..4),
+            BlockSource::LocalFile {
+                path: filepath.display().to_string(),
+            },
+        )
+        .await;
+
+        let name = filepath.display().to_string();
+        assert!(matches!(from_file, Err(FetchError::Parse(failed, _)) if failed == name));
+        assert!(matches!(from_directory, Err(FetchError::Parse(failed, _)) if failed == name));
+        assert!(matches!(
+            from_missing_file,
+            Err(FetchError::LocalFetchError(failed, _)) if failed == name
+        ));
     }
 }
@@ -1,8 +1,19 @@
 //! This module contains a lot of the important input structs
+use alloy::rpc::types::{BlockId, BlockNumberOrTag};
+use rpc::{provider::RpcTransport, RpcType};
 use serde::{Deserialize, Serialize};
-use zero_bin_common::block_interval::BlockInterval;
+use zero_bin_common::{block_interval::BlockInterval, failure::RetryPolicies};
 
-use crate::{benchmarking::BenchmarkOutputConfig, fetch::Checkpoint};
+use crate::{
+    benchmarking::BenchmarkOutputConfig,
+    config::DefaultOutputs,
+    fetch::{
+        first_block_number, Checkpoint, DFLT_FETCH_CONCURRENCY, DFLT_FETCH_LOOKAHEAD,
+        DFLT_RPC_BACKOFF, DFLT_RPC_MAX_RETRIES,
+    },
+    objstore::ObjectStoreConfig,
+    scheduler::DFLT_JOB_PRIORITY,
+};
 
 /// The source of Blocks to produce the [prover::ProverInput].
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,13 +23,112 @@ pub enum BlockSource {
     ZeroBinRpc {
         /// The url of the RPC
         rpc_url: String,
+        /// The type of the RPC, either `jerigon` or `native`.  Default is
+        /// `jerigon`.
+        #[serde(default)]
+        rpc_type: RpcType,
+        /// The backoff in milliseconds before the first retry of a request,
+        /// doubled on every retry.  Default is
+        /// [crate::fetch::DFLT_RPC_BACKOFF].
+        #[serde(default = "dflt_rpc_backoff")]
+        backoff: u64,
+        /// The maximum number of retries of a request.  Default is
+        /// [crate::fetch::DFLT_RPC_MAX_RETRIES].
+        #[serde(default = "dflt_rpc_max_retries")]
+        max_retries: u32,
+    },
+    /// Reads the [prover::BlockProverInput]s from a JSON file, as produced by
+    /// `rpc fetch`
+    LocalFile {
+        /// The path of the JSON file
+        path: String,
+    },
+    /// Reads the [prover::BlockProverInput]s from every JSON file in a
+    /// directory, as produced by `rpc fetch`
+    LocalDirectory {
+        /// The path of the directory
+        path: String,
+    },
+    /// Reads the [prover::BlockProverInput]s from every JSON object with the
+    /// given prefix in an object store, as produced by `rpc fetch`
+    ObjectStore {
+        /// The object store holding the objects
+        store: ObjectStoreConfig,
+        /// The prefix of the objects
+        prefix: String,
     },
 }
 
 unsafe impl Send for BlockSource {}
 
+fn dflt_rpc_backoff() -> u64 {
+    DFLT_RPC_BACKOFF
+}
+
+fn dflt_rpc_max_retries() -> u32 {
+    DFLT_RPC_MAX_RETRIES
+}
+
+use crate::notify::NotificationTarget;
 use crate::proofout::ProofOutputMethod;
 
+//===========================================================================================
+// Validation
+//===========================================================================================
+
+/// Returned when a [ProveBlocksInput] is rejected before starting its job
+#[derive(Debug)]
+pub enum InputValidationError {
+    /// Returned when the block interval cannot be parsed
+    BlockInterval(String, anyhow::Error),
+    /// Returned when the block interval does not contain any block
+    EmptyBlockInterval(String),
+    /// Returned when the checkpoint is inconsistent with the block interval
+    Checkpoint(String),
+    /// Returned when the block source cannot provide the blocks of the
+    /// interval
+    UnsupportedInterval(String),
+    /// Returned when the url of the RPC cannot be parsed
+    RpcUrl(String, anyhow::Error),
+    /// Returned when the retry policies are inconsistent with the block
+    /// source
+    RetryPolicies(String),
+}
+
+impl InputValidationError {
+    /// Returns the field of the [ProveBlocksInput] that was rejected
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::BlockInterval(..) | Self::EmptyBlockInterval(_) => "block_interval",
+            Self::Checkpoint(_) => "checkpoint",
+            Self::UnsupportedInterval(_) => "block_source",
+            Self::RpcUrl(..) => "block_source.rpc_url",
+            Self::RetryPolicies(_) => "retry_policies",
+        }
+    }
+}
+
+impl std::fmt::Display for InputValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BlockInterval(interval, err) => {
+                write!(f, "invalid block interval `{}`: {}", interval, err)
+            }
+            Self::EmptyBlockInterval(interval) => {
+                write!(f, "block interval `{}` contains no blocks", interval)
+            }
+            Self::Checkpoint(reason) => write!(f, "invalid checkpoint: {}", reason),
+            Self::UnsupportedInterval(reason) => {
+                write!(f, "unsupported block interval: {}", reason)
+            }
+            Self::RpcUrl(url, err) => write!(f, "invalid rpc url `{}`: {}", url, err),
+            Self::RetryPolicies(reason) => write!(f, "invalid retry policies: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for InputValidationError {}
+
 /// The input for starting the many-blocks proving
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProveBlocksInput {
@@ -27,8 +137,14 @@ pub struct ProveBlocksInput {
     /// The block interval
     ///
     /// See [BlockInterval::new] to see the acceptable [String] representations
-    /// for the [BlockInterval]
+    /// for the [BlockInterval].  An interval of the form `N..` follows the
+    /// chain tip, proving new blocks as they appear until the job is
+    /// cancelled.
     block_interval: String,
+    /// The block time of the chain in milliseconds, used to poll for new
+    /// blocks when following the chain tip.  If not provided, the default
+    /// block time of [BlockInterval] is used.
+    block_time: Option<u64>,
     /// The checkpoint block number.  If not provided, will be the
     /// the block before the current block number, or
     /// [Checkpoint::BlockNumberNegativeOffset] set to 1.
@@ -41,17 +157,143 @@ pub struct ProveBlocksInput {
     /// Stores the output of the benchmark.  If not provided, no benchmarking
     /// stats will be stored
     benchmark_output: Option<BenchmarkOutputConfig>,
+    /// Stores the inputs of the blocks that failed, see [crate::deadletter].
+    /// If not provided, the failed blocks are only recorded in the job
+    dead_letter_output: Option<ProofOutputMethod>,
+    /// The targets notified of the progress of the job.  If not provided, no
+    /// notifications will be sent
+    notifications: Option<Vec<NotificationTarget>>,
     /// Whether or not we should forward the previous proof to the next proof.
     ///
     /// NOTE: There may be some problems if set to true.  Default is false.
     forward_prev: Option<bool>,
+    /// The priority of the request, requests with a higher priority are
+    /// started first.  Default is [crate::scheduler::DFLT_JOB_PRIORITY].
+    priority: Option<i32>,
+    /// The number of blocks fetched concurrently.  Default is
+    /// [crate::fetch::DFLT_FETCH_CONCURRENCY].
+    fetch_concurrency: Option<usize>,
+    /// The maximum number of blocks fetched ahead of the blocks that have been
+    /// proven.  Default is [crate::fetch::DFLT_FETCH_LOOKAHEAD].
+    fetch_lookahead: Option<usize>,
+    /// How failed blocks are retried, per class of failure.  Classes not
+    /// provided use the defaults of [RetryPolicies].
+    retry_policies: Option<RetryPolicies>,
 }
 
 unsafe impl Send for ProveBlocksInput {}
 
 impl ProveBlocksInput {
+    /// Checks the input for the errors that would otherwise only surface once
+    /// its job has started: the block interval must parse and contain blocks,
+    /// the checkpoint must come before the first block of the interval, and the
+    /// block source must be able to provide the blocks of the interval.
+    pub fn validate(&self) -> Result<(), InputValidationError> {
+        let block_interval = self
+            .get_block_interval()
+            .map_err(|err| InputValidationError::BlockInterval(self.block_interval.clone(), err))?;
+        if matches!(&block_interval, BlockInterval::Range(range) if range.is_empty()) {
+            return Err(InputValidationError::EmptyBlockInterval(
+                self.block_interval.clone(),
+            ));
+        }
+
+        // The requests are retried by the RPC provider, not the fetching
+        let fetch = self.get_retry_policies().fetch;
+        if fetch.max_retries != 0 || fetch.backoff_ms != 0 {
+            return Err(InputValidationError::RetryPolicies(String::from(
+                "fetch failures are retried as per the `max_retries` and `backoff` of the block \
+                 source, the `fetch` policy can only skip",
+            )));
+        }
+
+        match &self.block_source {
+            BlockSource::ZeroBinRpc { rpc_url, .. } => {
+                url::Url::parse(rpc_url)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|url| RpcTransport::of(&url))
+                    .map_err(|err| InputValidationError::RpcUrl(rpc_url.clone(), err))?;
+                self.validate_checkpoint(&block_interval)
+            }
+            // The blocks are read as is, so only their numbers can be looked up
+            _ => match block_interval {
+                BlockInterval::FollowFrom { .. } => Err(InputValidationError::UnsupportedInterval(
+                    String::from("only the RPC block source can follow the chain tip"),
+                )),
+                BlockInterval::SingleBlockId(block_id)
+                    if first_block_number(&block_interval).is_none() =>
+                {
+                    Err(InputValidationError::UnsupportedInterval(format!(
+                        "only the RPC block source can look up block {}",
+                        block_id
+                    )))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn validate_checkpoint(
+        &self,
+        block_interval: &BlockInterval,
+    ) -> Result<(), InputValidationError> {
+        let checkpoint = self.checkpoint.unwrap_or_default();
+        // The first block of a block hash is only known once it is resolved
+        let Some(first_block) = first_block_number(block_interval) else {
+            return Ok(());
+        };
+
+        match (checkpoint, checkpoint.offset()) {
+            (Checkpoint::Constant(BlockId::Number(BlockNumberOrTag::Number(number))), _)
+                if number >= first_block =>
+            {
+                Err(InputValidationError::Checkpoint(format!(
+                    "checkpoint block {} is not before the first block {}",
+                    number, first_block
+                )))
+            }
+            (Checkpoint::Constant(_), _) => Ok(()),
+            (_, Some(0)) => Err(InputValidationError::Checkpoint(String::from(
+                "the offset must be at least 1",
+            ))),
+            (_, Some(offset)) if offset > first_block => {
+                Err(InputValidationError::Checkpoint(format!(
+                    "offset {} is larger than the first block {}",
+                    offset, first_block
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn get_block_interval(&self) -> Result<BlockInterval, anyhow::Error> {
-        BlockInterval::new(&self.block_interval)
+        match BlockInterval::new(&self.block_interval)? {
+            BlockInterval::FollowFrom { start_block, .. } => Ok(BlockInterval::FollowFrom {
+                start_block,
+                block_time: self.block_time,
+            }),
+            block_interval => Ok(block_interval),
+        }
+    }
+
+    /// Returns the input with the outputs it does not provide taken from the
+    /// [DefaultOutputs] of the configuration
+    pub fn with_default_outputs(self, defaults: &DefaultOutputs) -> Self {
+        Self {
+            proof_output: self.proof_output.or_else(|| defaults.proof_output.clone()),
+            benchmark_output: self
+                .benchmark_output
+                .or_else(|| defaults.benchmark_output.clone()),
+            dead_letter_output: self
+                .dead_letter_output
+                .or_else(|| defaults.dead_letter_output.clone()),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn get_run_name(&self) -> &Option<String> {
+        &self.run_name
     }
 
     #[inline]
@@ -64,6 +306,21 @@ impl ProveBlocksInput {
         &self.benchmark_output
     }
 
+    #[inline]
+    pub fn get_dead_letter_output(&self) -> &Option<ProofOutputMethod> {
+        &self.dead_letter_output
+    }
+
+    #[inline]
+    pub fn get_notifications(&self) -> &[NotificationTarget] {
+        self.notifications.as_deref().unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn get_block_interval_str(&self) -> &str {
+        &self.block_interval
+    }
+
     #[inline]
     pub fn get_checkpoint(&self) -> &Option<Checkpoint> {
         &self.checkpoint
@@ -74,6 +331,28 @@ impl ProveBlocksInput {
         &self.block_source
     }
 
+    #[inline]
+    pub fn get_priority(&self) -> i32 {
+        self.priority.unwrap_or(DFLT_JOB_PRIORITY)
+    }
+
+    #[inline]
+    pub fn get_fetch_concurrency(&self) -> usize {
+        self.fetch_concurrency
+            .unwrap_or(DFLT_FETCH_CONCURRENCY)
+            .max(1)
+    }
+
+    #[inline]
+    pub fn get_fetch_lookahead(&self) -> usize {
+        self.fetch_lookahead.unwrap_or(DFLT_FETCH_LOOKAHEAD).max(1)
+    }
+
+    #[inline]
+    pub fn get_retry_policies(&self) -> RetryPolicies {
+        self.retry_policies.unwrap_or_default()
+    }
+
     pub fn get_expected_number_proofs(&self) -> Option<u64> {
         match self.get_block_interval() {
             // Ranges should be determined by start and end
@@ -93,3 +372,135 @@ impl ProveBlocksInput {
         self.get_expected_number_proofs()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BLOCK_HASH: &str = "0xb51ceca7ba912779ed6721d2b93849758af0d2354683170fb71dead6e439e6cb";
+
+    fn rpc() -> serde_json::Value {
+        serde_json::json!({"ZeroBinRpc": {"rpc_url": "http://localhost:8545/"}})
+    }
+
+    fn local_file() -> serde_json::Value {
+        serde_json::json!({"LocalFile": {"path": "blocks.json"}})
+    }
+
+    fn input(
+        block_interval: &str,
+        block_source: serde_json::Value,
+        checkpoint: Option<Checkpoint>,
+    ) -> ProveBlocksInput {
+        serde_json::from_value(serde_json::json!({
+            "block_interval": block_interval,
+            "block_source": block_source,
+            "checkpoint": checkpoint,
+        }))
+        .unwrap()
+    }
+
+    fn constant(block_number: u64) -> Option<Checkpoint> {
+        Some(Checkpoint::Constant(BlockId::Number(
+            BlockNumberOrTag::Number(block_number),
+        )))
+    }
+
+    #[test]
+    fn accepts_valid_inputs() {
+        assert!(input("5..=7", rpc(), None).validate().is_ok());
+        assert!(input("5..", rpc(), None).validate().is_ok());
+        assert!(input(BLOCK_HASH, rpc(), None).validate().is_ok());
+        assert!(input("5..=7", local_file(), None).validate().is_ok());
+        assert!(input(
+            "5..=7",
+            serde_json::json!({"ZeroBinRpc": {"rpc_url": "wss://localhost:8546/"}}),
+            None
+        )
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_block_intervals() {
+        let err = input("5..=x", rpc(), None).validate().unwrap_err();
+        assert!(matches!(err, InputValidationError::BlockInterval(..)));
+        assert_eq!(err.field(), "block_interval");
+
+        assert!(matches!(
+            input("5..5", rpc(), None).validate(),
+            Err(InputValidationError::EmptyBlockInterval(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_rpc_urls() {
+        for rpc_url in ["localhost", "ftp://localhost/"] {
+            let err = input(
+                "5..=7",
+                serde_json::json!({"ZeroBinRpc": {"rpc_url": rpc_url}}),
+                None,
+            )
+            .validate()
+            .unwrap_err();
+            assert!(matches!(err, InputValidationError::RpcUrl(..)));
+            assert_eq!(err.field(), "block_source.rpc_url");
+        }
+    }
+
+    #[test]
+    fn rejects_fetch_retries() {
+        let mut request = input("5..=7", rpc(), None);
+        request.retry_policies = serde_json::from_str(r#"{"fetch": {"skip": true}}"#).unwrap();
+        assert!(request.validate().is_ok());
+
+        request.retry_policies = serde_json::from_str(r#"{"fetch": {"max_retries": 3}}"#).unwrap();
+        let err = request.validate().unwrap_err();
+        assert!(matches!(err, InputValidationError::RetryPolicies(_)));
+        assert_eq!(err.field(), "retry_policies");
+    }
+
+    #[test]
+    fn rejects_intervals_the_files_cannot_provide() {
+        assert!(matches!(
+            input("5..", local_file(), None).validate(),
+            Err(InputValidationError::UnsupportedInterval(_))
+        ));
+        assert!(matches!(
+            input(BLOCK_HASH, local_file(), None).validate(),
+            Err(InputValidationError::UnsupportedInterval(_))
+        ));
+    }
+
+    #[test]
+    fn validates_constant_checkpoints() {
+        assert!(input("5..=7", rpc(), constant(4)).validate().is_ok());
+        for block_number in [5, 6] {
+            assert!(matches!(
+                input("5..=7", rpc(), constant(block_number)).validate(),
+                Err(InputValidationError::Checkpoint(_))
+            ));
+        }
+        // The first block of a block hash is only known once resolved
+        assert!(input(BLOCK_HASH, rpc(), constant(5)).validate().is_ok());
+    }
+
+    #[test]
+    fn validates_checkpoint_offsets() {
+        let offset = |offset| Some(Checkpoint::BlockNumberNegativeOffset(offset));
+        assert!(input("5..=7", rpc(), offset(5)).validate().is_ok());
+        assert!(input("5..=7", rpc(), Some(Checkpoint::Default))
+            .validate()
+            .is_ok());
+        for offset in [offset(0), offset(6)] {
+            assert!(matches!(
+                input("5..=7", rpc(), offset).validate(),
+                Err(InputValidationError::Checkpoint(_))
+            ));
+        }
+        assert!(matches!(
+            input("0..=2", rpc(), None).validate(),
+            Err(InputValidationError::Checkpoint(_))
+        ));
+    }
+}
@@ -0,0 +1,713 @@
+//! This module contains the persistent job store, used to keep track of every
+//! [ProveBlocksInput] received by the coordinator along with its progress.
+//!
+//! Each job is stored as its own JSON file (`<job_id>.json`) within the job
+//! store directory, so that queued jobs survive a restart of the coordinator.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{create_dir_all, read_dir},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use prover::BlockFailure;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+use zero_bin_common::failure::FailureClass;
+
+use crate::input::ProveBlocksInput;
+
+/// The identifier of a job
+pub type JobId = u64;
+
+//==================================================================================
+// Job Records
+//==================================================================================
+
+/// The status of a job within the coordinator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    /// The job is waiting to be processed
+    Queued,
+    /// The blocks are being fetched
+    Fetching,
+    /// The blocks are being proven
+    Proving,
+    /// The job completed successfully
+    Done,
+    /// The job failed, see [JobRecord::error] for details
+    Failed,
+    /// The job was cancelled before it completed
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Returns true if the job will not be processed any further
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// The status of a single block within a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockStatus {
+    /// The [prover::BlockProverInput] has been fetched
+    Fetched,
+    /// The block has been proven
+    Proved,
+    /// The block failed to be proven
+    Failed,
+    /// The block failed and was skipped, see [JobProgress::failures]
+    Skipped,
+}
+
+/// A block that failed, once its retries were exhausted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockFailureRecord {
+    /// The block number of the block
+    pub block_number: u64,
+    /// The class of the failure
+    pub class: FailureClass,
+    /// The number of times the block was attempted
+    pub attempts: u32,
+    /// Whether the block was skipped rather than failing the job
+    pub skipped: bool,
+    /// The description of the last error
+    pub error: String,
+    /// When the block was given up on
+    pub timestamp: DateTime<Utc>,
+    /// Where the dead letter of the block was written to, if it was (see
+    /// [crate::deadletter])
+    #[serde(default)]
+    pub dead_letter: Option<String>,
+}
+
+impl From<&BlockFailure> for BlockFailureRecord {
+    fn from(failure: &BlockFailure) -> Self {
+        Self {
+            block_number: failure.block_number,
+            class: failure.class,
+            attempts: failure.attempts,
+            skipped: failure.skipped,
+            error: format!("{:#}", failure.error),
+            timestamp: Utc::now(),
+            dead_letter: None,
+        }
+    }
+}
+
+/// The per-block progress of a job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// The number of blocks we expect to prove, if known
+    pub expected_blocks: Option<u64>,
+    /// The number of blocks proven so far
+    pub proved_blocks: u64,
+    /// The status of each block we have started on, keyed by block number
+    pub blocks: BTreeMap<u64, BlockStatus>,
+    /// Every block below this number was proven or skipped, and its status
+    /// dropped from [Self::blocks] (see [JobProgress::compact])
+    #[serde(default)]
+    pub finished_below: Option<u64>,
+    /// The blocks that failed, in the order they failed
+    #[serde(default)]
+    pub failures: Vec<BlockFailureRecord>,
+    /// The number of failures dropped from [Self::failures]
+    #[serde(default)]
+    pub dropped_failures: u64,
+}
+
+impl JobProgress {
+    /// Drops the oldest blocks that were proven or skipped once more than
+    /// [MAX_TRACKED_BLOCKS] are tracked, and the oldest failures beyond
+    /// [MAX_RECORDED_FAILURES], so that a job following the chain tip does not
+    /// grow its progress (and its job file) forever
+    fn compact(&mut self) {
+        while self.blocks.len() > MAX_TRACKED_BLOCKS {
+            match self.blocks.first_key_value() {
+                Some((&block_number, BlockStatus::Proved | BlockStatus::Skipped)) => {
+                    self.blocks.pop_first();
+                    self.finished_below = Some(block_number + 1);
+                }
+                _ => break,
+            }
+        }
+        if self.failures.len() > MAX_RECORDED_FAILURES {
+            let dropped = self.failures.len() - MAX_RECORDED_FAILURES;
+            self.failures.drain(..dropped);
+            self.dropped_failures += dropped as u64;
+        }
+    }
+}
+
+/// The stored information of a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    /// The identifier of the job
+    pub id: JobId,
+    /// The original request
+    pub input: ProveBlocksInput,
+    /// The current status of the job
+    pub status: JobStatus,
+    /// The progress of the job
+    pub progress: JobProgress,
+    /// The error description if the job failed
+    pub error: Option<String>,
+    /// When the job was received
+    pub created_at: DateTime<Utc>,
+    /// When the job was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+//==================================================================================
+// Job Store
+//==================================================================================
+
+#[derive(Debug)]
+pub enum JobStoreError {
+    /// Failed to create the directory, or the path is not a directory
+    Directory(PathBuf, Option<anyhow::Error>),
+    /// Failed to read a job file
+    Read(PathBuf, anyhow::Error),
+    /// Failed to write a job file
+    Write(PathBuf, anyhow::Error),
+    /// The job does not exist
+    NotFound(JobId),
+    /// The job has already finished, with the contained status
+    AlreadyFinished(JobId, JobStatus),
+}
+
+impl std::fmt::Display for JobStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self, f)
+    }
+}
+
+impl std::error::Error for JobStoreError {}
+
+/// The default directory for the job store
+pub const JOB_STORE_DIR_DFLT: &str = "jobs/";
+/// How often [JobHandle::cancelled] checks whether the job was cancelled
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// The maximum number of blocks kept in [JobProgress::blocks]
+const MAX_TRACKED_BLOCKS: usize = 1024;
+/// The maximum number of failures kept in [JobProgress::failures]
+const MAX_RECORDED_FAILURES: usize = 256;
+
+/// The [JobStore] keeps every job in memory and mirrors each change to disk.
+#[derive(Debug)]
+pub struct JobStore {
+    /// The directory the job files are stored in
+    dirpath: PathBuf,
+    /// The jobs, keyed by their id
+    jobs: Mutex<BTreeMap<JobId, JobRecord>>,
+    /// The abort signals of the jobs, raised when a job is cancelled
+    abort_signals: Mutex<HashMap<JobId, Arc<AtomicBool>>>,
+}
+
+impl JobStore {
+    /// Opens the [JobStore] in the given directory, loading all the
+    /// pre-existing jobs.
+    pub fn open(dirpath: PathBuf) -> Result<Self, JobStoreError> {
+        match (dirpath.exists(), dirpath.is_dir()) {
+            (true, true) => debug!("`{:?}` is pre-existing directory", dirpath),
+            (true, false) => {
+                error!("Job store path is not a directory: {:?}", dirpath);
+                return Err(JobStoreError::Directory(dirpath, None));
+            }
+            (false, _) => {
+                info!("Creating directory: {:?}", dirpath);
+                if let Err(err) = create_dir_all(dirpath.clone()) {
+                    error!("Failed to create directory {:?}: {}", dirpath, err);
+                    return Err(JobStoreError::Directory(dirpath, Some(err.into())));
+                }
+            }
+        }
+
+        let entries = match read_dir(&dirpath) {
+            Ok(entries) => entries,
+            Err(err) => return Err(JobStoreError::Read(dirpath, err.into())),
+        };
+
+        let mut jobs = BTreeMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let record: JobRecord = match std::fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| serde_json::from_slice(&bytes).map_err(anyhow::Error::from))
+            {
+                Ok(record) => record,
+                Err(err) => {
+                    error!("Failed to load job file {:?}: {}", path, err);
+                    return Err(JobStoreError::Read(path, err));
+                }
+            };
+            jobs.insert(record.id, record);
+        }
+
+        info!("Loaded {} job(s) from {:?}", jobs.len(), dirpath);
+
+        Ok(Self {
+            dirpath,
+            jobs: Mutex::new(jobs),
+            abort_signals: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Writes the record to its file.  The record is first written to a
+    /// temporary file then renamed, so a crash never leaves a partial record.
+    fn persist(&self, record: &JobRecord) -> Result<(), JobStoreError> {
+        let filepath = self.dirpath.join(format!("{}.json", record.id));
+        let tmp_filepath = self.dirpath.join(format!("{}.json.tmp", record.id));
+
+        let bytes = match serde_json::to_vec_pretty(record) {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(JobStoreError::Write(filepath, err.into())),
+        };
+        if let Err(err) = std::fs::write(&tmp_filepath, bytes) {
+            error!("Failed to write job file `{:?}`: {}", tmp_filepath, err);
+            return Err(JobStoreError::Write(tmp_filepath, err.into()));
+        }
+        if let Err(err) = std::fs::rename(&tmp_filepath, &filepath) {
+            error!("Failed to move job file to `{:?}`: {}", filepath, err);
+            return Err(JobStoreError::Write(filepath, err.into()));
+        }
+        Ok(())
+    }
+
+    /// Creates and stores a new [JobStatus::Queued] job, returning its id.
+    pub fn create(&self, input: ProveBlocksInput) -> Result<JobId, JobStoreError> {
+        let mut jobs = self.jobs.lock().expect("Job store lock poisoned");
+        let id = jobs.keys().next_back().map(|id| id + 1).unwrap_or(1);
+        let now = Utc::now();
+        let record = JobRecord {
+            id,
+            progress: JobProgress {
+                expected_blocks: input.estimate_expected_number_proofs(),
+                ..Default::default()
+            },
+            input,
+            status: JobStatus::Queued,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.persist(&record)?;
+        jobs.insert(id, record);
+        info!("Created job {}", id);
+        Ok(id)
+    }
+
+    /// Returns a copy of the job, if it exists
+    pub fn get(&self, id: JobId) -> Option<JobRecord> {
+        self.jobs
+            .lock()
+            .expect("Job store lock poisoned")
+            .get(&id)
+            .cloned()
+    }
+
+    /// Returns a copy of every job, ordered by id
+    pub fn list(&self) -> Vec<JobRecord> {
+        self.jobs
+            .lock()
+            .expect("Job store lock poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Applies `f` to the job and stores the result.
+    pub fn update<F>(&self, id: JobId, f: F) -> Result<(), JobStoreError>
+    where
+        F: FnOnce(&mut JobRecord),
+    {
+        let mut jobs = self.jobs.lock().expect("Job store lock poisoned");
+        let record = match jobs.get_mut(&id) {
+            Some(record) => record,
+            None => return Err(JobStoreError::NotFound(id)),
+        };
+        f(record);
+        record.updated_at = Utc::now();
+        self.persist(record)
+    }
+
+    /// Creates the abort signal of a job about to be processed, which is raised
+    /// once the job is cancelled.  The signal lives until it is released (see
+    /// [JobStore::release_abort_signal]).
+    pub fn register_abort_signal(&self, id: JobId) -> Arc<AtomicBool> {
+        self.abort_signals
+            .lock()
+            .expect("Job store lock poisoned")
+            .entry(id)
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the abort signal of the job, if it is being processed (see
+    /// [JobStore::register_abort_signal])
+    pub fn abort_signal(&self, id: JobId) -> Option<Arc<AtomicBool>> {
+        self.abort_signals
+            .lock()
+            .expect("Job store lock poisoned")
+            .get(&id)
+            .cloned()
+    }
+
+    /// Drops the abort signal of a job that is no longer being processed, so
+    /// that the signals do not accumulate over the life of the coordinator
+    pub fn release_abort_signal(&self, id: JobId) {
+        self.abort_signals
+            .lock()
+            .expect("Job store lock poisoned")
+            .remove(&id);
+    }
+
+    /// Cancels a job that has not yet finished, marking it as
+    /// [JobStatus::Cancelled] and raising its abort signal.
+    pub fn cancel(&self, id: JobId) -> Result<(), JobStoreError> {
+        let mut finished = None;
+        self.update(id, |record| {
+            if record.status.is_terminal() {
+                finished = Some(record.status);
+            } else {
+                record.status = JobStatus::Cancelled;
+            }
+        })?;
+        if let Some(status) = finished {
+            return Err(JobStoreError::AlreadyFinished(id, status));
+        }
+
+        // A job that is not being processed has no signal, and is skipped once
+        // dequeued since it is cancelled
+        if let Some(abort_signal) = self.abort_signal(id) {
+            abort_signal.store(true, Ordering::SeqCst);
+        }
+        info!("Cancelled job {}", id);
+        Ok(())
+    }
+
+    /// Returns the ids of the jobs that should be (re)started, ordered by id.
+    ///
+    /// Jobs that were in the middle of being processed when the coordinator
+    /// stopped are reset to [JobStatus::Queued] as their progress was lost.
+    pub fn resumable(&self) -> Result<Vec<JobId>, JobStoreError> {
+        let ids = self
+            .jobs
+            .lock()
+            .expect("Job store lock poisoned")
+            .values()
+            .filter(|record| !record.status.is_terminal())
+            .map(|record| record.id)
+            .collect::<Vec<_>>();
+
+        for id in ids.iter() {
+            self.update(*id, |record| {
+                if record.status != JobStatus::Queued {
+                    warn!(
+                        "Job {} was interrupted while {:?}, re-queueing it",
+                        record.id, record.status
+                    );
+                    record.status = JobStatus::Queued;
+                    record.progress = JobProgress {
+                        expected_blocks: record.progress.expected_blocks,
+                        ..Default::default()
+                    };
+                }
+            })?;
+        }
+
+        Ok(ids)
+    }
+}
+
+//==================================================================================
+// Job Handle
+//==================================================================================
+
+/// A handle to a single job in the [JobStore], used while processing the job
+/// to report its progress.
+///
+/// Failing to persist the progress is logged but is not treated as fatal to
+/// the job itself.
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    /// The identifier of the job
+    pub id: JobId,
+    /// The store the job lives in
+    store: Arc<JobStore>,
+}
+
+impl JobHandle {
+    pub fn new(id: JobId, store: Arc<JobStore>) -> Self {
+        Self { id, store }
+    }
+
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut JobRecord),
+    {
+        if let Err(err) = self.store.update(self.id, f) {
+            error!("Failed to update job {}: {}", self.id, err);
+        }
+    }
+
+    /// Returns the abort signal of the job, see [JobStore::abort_signal].  A
+    /// job which is not being processed gets a signal that is never raised.
+    pub fn abort_signal(&self) -> Arc<AtomicBool> {
+        self.store.abort_signal(self.id).unwrap_or_default()
+    }
+
+    /// Returns the number of blocks proven so far
+    pub fn proved_blocks(&self) -> u64 {
+        self.store
+            .get(self.id)
+            .map(|record| record.progress.proved_blocks)
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the job has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.store
+            .abort_signal(self.id)
+            .is_some_and(|abort_signal| abort_signal.load(Ordering::SeqCst))
+    }
+
+    /// Completes once the job has been cancelled
+    pub async fn cancelled(&self) {
+        while !self.is_cancelled() {
+            tokio::time::sleep(CANCELLATION_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Sets the status of the job.  A job that has already finished (i.e. has
+    /// been cancelled) keeps its status.
+    pub fn set_status(&self, status: JobStatus) {
+        debug!("Job {} is now {:?}", self.id, status);
+        self.update(|record| {
+            if !record.status.is_terminal() {
+                record.status = status;
+            }
+        });
+    }
+
+    /// Sets the status of a block within the job
+    pub fn set_block_status(&self, block_number: u64, status: BlockStatus) {
+        self.update(|record| {
+            if status == BlockStatus::Proved {
+                record.progress.proved_blocks += 1;
+            }
+            record.progress.blocks.insert(block_number, status);
+            record.progress.compact();
+        });
+    }
+
+    /// Records a block that failed, setting its status to
+    /// [BlockStatus::Skipped] or [BlockStatus::Failed]
+    pub fn record_failure(&self, failure: BlockFailureRecord) {
+        self.update(|record| {
+            let status = match failure.skipped {
+                true => BlockStatus::Skipped,
+                false => BlockStatus::Failed,
+            };
+            record.progress.blocks.insert(failure.block_number, status);
+            record.progress.failures.push(failure);
+            record.progress.compact();
+        });
+    }
+
+    /// Marks the job as [JobStatus::Failed] with the given error description
+    pub fn fail(&self, err: String) {
+        error!("Job {} failed: {}", self.id, err);
+        self.update(|record| {
+            if record.status != JobStatus::Cancelled {
+                record.status = JobStatus::Failed;
+            }
+            record.error = Some(err);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns an empty directory for the job store of the test
+    fn job_store_dir(name: &str) -> PathBuf {
+        let dirpath =
+            std::env::temp_dir().join(format!("coordinator-jobs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dirpath);
+        dirpath
+    }
+
+    fn input(block_interval: &str) -> ProveBlocksInput {
+        serde_json::from_value(serde_json::json!({
+            "block_interval": block_interval,
+            "block_source": {"ZeroBinRpc": {"rpc_url": "http://localhost:8545/"}},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn creates_jobs_with_increasing_ids() {
+        let store = JobStore::open(job_store_dir("create")).unwrap();
+        assert_eq!(store.create(input("3..=5")).unwrap(), 1);
+        assert_eq!(store.create(input("6..=8")).unwrap(), 2);
+
+        let record = store.get(2).unwrap();
+        assert_eq!(record.status, JobStatus::Queued);
+        assert_eq!(record.progress.expected_blocks, Some(3));
+        assert_eq!(
+            store
+                .list()
+                .iter()
+                .map(|record| record.id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn reloads_the_persisted_jobs() {
+        let dirpath = job_store_dir("reload");
+        let store = JobStore::open(dirpath.clone()).unwrap();
+        let id = store.create(input("3..=5")).unwrap();
+        store
+            .update(id, |record| record.status = JobStatus::Done)
+            .unwrap();
+        drop(store);
+
+        let store = JobStore::open(dirpath).unwrap();
+        assert_eq!(store.get(id).unwrap().status, JobStatus::Done);
+        // Ids keep increasing from the reloaded jobs
+        assert_eq!(store.create(input("6..=8")).unwrap(), id + 1);
+    }
+
+    #[test]
+    fn update_of_a_missing_job_fails() {
+        let store = JobStore::open(job_store_dir("missing")).unwrap();
+        assert!(matches!(
+            store.update(1, |_| ()),
+            Err(JobStoreError::NotFound(1))
+        ));
+    }
+
+    #[test]
+    fn cancel_raises_the_abort_signal() {
+        let store = JobStore::open(job_store_dir("cancel")).unwrap();
+        let id = store.create(input("3..=5")).unwrap();
+        let abort_signal = store.register_abort_signal(id);
+
+        store.cancel(id).unwrap();
+        assert!(abort_signal.load(Ordering::SeqCst));
+        assert_eq!(store.get(id).unwrap().status, JobStatus::Cancelled);
+        assert!(matches!(
+            store.cancel(id),
+            Err(JobStoreError::AlreadyFinished(_, JobStatus::Cancelled))
+        ));
+    }
+
+    #[test]
+    fn releases_the_abort_signals() {
+        let store = JobStore::open(job_store_dir("release")).unwrap();
+        let id = store.create(input("3..=5")).unwrap();
+        let job = JobHandle::new(id, Arc::new(store));
+        job.store.register_abort_signal(id);
+        assert_eq!(job.store.abort_signals.lock().unwrap().len(), 1);
+
+        job.store.cancel(id).unwrap();
+        assert!(job.is_cancelled());
+        job.store.release_abort_signal(id);
+        assert!(job.store.abort_signals.lock().unwrap().is_empty());
+
+        // Looking up the signal of a released job does not create it again
+        assert!(!job.is_cancelled());
+        assert!(!job.abort_signal().load(Ordering::SeqCst));
+        assert!(job.store.abort_signal(id).is_none());
+        assert!(job.store.abort_signals.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancels_jobs_that_are_not_processed() {
+        let store = JobStore::open(job_store_dir("queued")).unwrap();
+        let id = store.create(input("3..=5")).unwrap();
+
+        store.cancel(id).unwrap();
+        assert_eq!(store.get(id).unwrap().status, JobStatus::Cancelled);
+        assert!(store.abort_signals.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn compacts_the_finished_blocks() {
+        let mut progress = JobProgress::default();
+        progress.blocks.insert(5000, BlockStatus::Fetched);
+        for block_number in 1000..3000 {
+            progress.blocks.insert(block_number, BlockStatus::Proved);
+            progress.compact();
+        }
+        assert_eq!(progress.blocks.len(), MAX_TRACKED_BLOCKS);
+        assert_eq!(progress.finished_below, Some(1977));
+        assert_eq!(
+            progress.blocks.first_key_value(),
+            Some((&1977, &BlockStatus::Proved))
+        );
+
+        // A block still being proven stops the compaction
+        progress.blocks.insert(1, BlockStatus::Fetched);
+        progress.blocks.insert(3000, BlockStatus::Proved);
+        progress.compact();
+        assert_eq!(progress.blocks.len(), MAX_TRACKED_BLOCKS + 2);
+    }
+
+    #[test]
+    fn drops_the_oldest_failures() {
+        let mut progress = JobProgress::default();
+        for block_number in 0..300 {
+            progress.failures.push(BlockFailureRecord {
+                block_number,
+                class: FailureClass::Proving,
+                attempts: 1,
+                skipped: true,
+                error: String::from("error"),
+                timestamp: Utc::now(),
+                dead_letter: None,
+            });
+            progress.compact();
+        }
+        assert_eq!(progress.failures.len(), MAX_RECORDED_FAILURES);
+        assert_eq!(progress.failures[0].block_number, 44);
+        assert_eq!(progress.dropped_failures, 44);
+    }
+
+    #[test]
+    fn resumable_requeues_the_interrupted_jobs() {
+        let store = JobStore::open(job_store_dir("resumable")).unwrap();
+        let queued = store.create(input("3..=5")).unwrap();
+        let proving = store.create(input("6..=8")).unwrap();
+        let done = store.create(input("9..=10")).unwrap();
+        store
+            .update(proving, |record| {
+                record.status = JobStatus::Proving;
+                record.progress.proved_blocks = 1;
+                record.progress.blocks.insert(6, BlockStatus::Proved);
+            })
+            .unwrap();
+        store
+            .update(done, |record| record.status = JobStatus::Done)
+            .unwrap();
+
+        assert_eq!(store.resumable().unwrap(), vec![queued, proving]);
+        let record = store.get(proving).unwrap();
+        assert_eq!(record.status, JobStatus::Queued);
+        assert_eq!(record.progress.proved_blocks, 0);
+        assert!(record.progress.blocks.is_empty());
+    }
+}
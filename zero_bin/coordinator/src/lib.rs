@@ -1,8 +1,15 @@
 //! This crate contains everything needed to run zero-bin persistently
 //! while enabling benchmarking statistics.
 pub mod benchmarking;
+pub mod compare;
+pub mod config;
+pub mod deadletter;
 pub mod fetch;
 pub mod input;
+pub mod jobs;
 pub mod manyprover;
+pub mod notify;
+pub mod objstore;
 pub mod proofout;
 pub mod psm;
+pub mod scheduler;
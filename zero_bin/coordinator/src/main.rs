@@ -1,33 +1,36 @@
 //! This file provides a means of setting up a web-server to handle multi-block
 //! proofs
 use std::{
-    env,
     path::PathBuf,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
-use anyhow::Result;
-use coordinator::manyprover::ManyProver;
+use actix_web::{
+    http::header::AUTHORIZATION, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use anyhow::{anyhow, Result};
+use coordinator::jobs::{JobHandle, JobId, JobStore, JobStoreError};
+use coordinator::manyprover::{ManyProver, ManyProverError};
+use coordinator::notify::{JobEvent, Notifier};
+use coordinator::scheduler::Scheduler;
 pub use coordinator::{
-    benchmarking, fetch,
+    benchmarking,
+    config::{self, CoordinatorConfig},
+    fetch,
     input::{self, ProveBlocksInput},
-    manyprover, proofout, psm,
+    jobs, manyprover, notify, proofout, psm, scheduler,
 };
 use dotenvy::dotenv;
 use ops::register;
-use paladin::{
-    config::{Config, Serializer},
-    runtime::Runtime,
-};
+use paladin::runtime::Runtime;
+use tokio::signal::unix::{signal, SignalKind};
 // use leader::init;
 use tracing::{debug, error, info, warn};
-use zero_bin_common::prover_state;
-
-pub const SERVER_ADDR_ENVKEY: &str = "SERVER_ADDR";
-pub const DFLT_SERVER_ADDR: &str = "0.0.0.0:8080";
-pub const NUM_SERVER_WORKERS: usize = 4;
+use zero_bin_common::{
+    auth::{AuthConfig, AuthRequest, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+    metrics, prover_state,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -40,6 +43,27 @@ async fn main() -> Result<()> {
     dotenv().ok();
 
     leader::init::tracing();
+    metrics::init();
+
+    //------------------------------------------------------------------------
+    // Configuration
+    //------------------------------------------------------------------------
+
+    info!("Loading the configuration");
+    let config = match CoordinatorConfig::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Invalid configuration: {}", err);
+            return Err(err.into());
+        }
+    };
+    debug!("Configuration: {:#?}", config);
+    // Also makes the configuration available to the jobs
+    if coordinator::config::init(config).is_err() {
+        error!("The configuration was already initialized");
+        return Err(anyhow!("the configuration was already initialized"));
+    }
+    let config = config::get()?;
 
     // Loading the logger
     // debug!("Loading env_logger");
@@ -55,14 +79,49 @@ async fn main() -> Result<()> {
     // init::tracing();
 
     //------------------------------------------------------------------------
-    // Request queue
+    // Job store
+    //------------------------------------------------------------------------
+
+    info!("Opening the job store");
+    let job_store = match JobStore::open(config.jobs.store_dir.clone()) {
+        Ok(job_store) => Arc::new(job_store),
+        Err(err) => {
+            error!("Failed to open the job store: {}", err);
+            return Err(err.into());
+        }
+    };
+
+    //------------------------------------------------------------------------
+    // Scheduler
     //------------------------------------------------------------------------
 
-    info!("Initializing the request queue");
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<ProveBlocksInput>(50);
+    info!("Initializing the scheduler");
+    let scheduler = Arc::new(Scheduler::new(config.jobs.max_concurrent));
+    info!(
+        "Proving up to {} job(s) concurrently",
+        scheduler.max_concurrency()
+    );
+
+    // Re-queue the jobs that did not complete before the last shutdown
+    match job_store.resumable() {
+        Ok(resumable) if resumable.is_empty() => debug!("No jobs to resume"),
+        Ok(resumable) => {
+            info!("Resuming {} job(s): {:?}", resumable.len(), resumable);
+            for job_id in resumable {
+                if let Some(record) = job_store.get(job_id) {
+                    scheduler.enqueue(job_id, record.input.get_priority());
+                }
+            }
+        }
+        Err(err) => {
+            error!("Failed to determine the jobs to resume: {}", err);
+            return Err(err.into());
+        }
+    }
 
     // Store it in a Data for server
-    let post_queue = web::Data::new(tx);
+    let scheduler_data = web::Data::from(scheduler.clone());
+    let job_store_data = web::Data::from(job_store.clone());
 
     //------------------------------------------------------------------------
     // Runtime
@@ -72,13 +131,13 @@ async fn main() -> Result<()> {
 
     let runtime = {
         info!("Attempting to build paladin config for Runtime");
-        let config = build_paladin_config_from_env();
+        let paladin_config = config.paladin_config();
 
         debug!("Determining if should initialize a prover state config...");
-        match &config.runtime {
+        match &paladin_config.runtime {
             paladin::config::Runtime::InMemory => {
                 info!("InMemory runtime, initializing a prover_state_manager");
-                let psm = psm::load_psm_from_env();
+                let psm = psm::load_psm(&config.prover_state);
                 info!("Overwriting TableLoadStrategy to Monolothic due InMemory runtime");
                 let psm = psm.with_load_strategy(prover_state::TableLoadStrategy::Monolithic);
                 info!("Attempting to initialize the Prover State Manager.");
@@ -110,13 +169,13 @@ async fn main() -> Result<()> {
         }
 
         info!("Building Paladin Runtime");
-        match Runtime::from_config(&config, register()).await {
+        match Runtime::from_config(&paladin_config, register()).await {
             Ok(runtime) => {
                 info!("Created Paladin Runtime");
                 runtime
             }
             Err(err) => {
-                error!("Config: {:#?}", config);
+                error!("Config: {:#?}", paladin_config);
                 error!("Error while constructing the runtime: {}", err);
                 panic!("Failed to build Paladin runtime from config: {}", err);
             }
@@ -132,29 +191,28 @@ async fn main() -> Result<()> {
 
     debug!("Setting up server endpoint");
 
-    let server_addr = match env::var(SERVER_ADDR_ENVKEY) {
-        Ok(addr) => {
-            info!("Retrieved server address: {}", addr);
-            addr
-        }
-        Err(env::VarError::NotPresent) => {
-            warn!("Using default server address: {}", DFLT_SERVER_ADDR);
-            String::from(DFLT_SERVER_ADDR)
-        }
-        Err(env::VarError::NotUnicode(os_str)) => {
-            error!("Non-unicode server address: {:?}", os_str);
-            panic!("Non-unicode server address: {:?}", os_str);
-        }
-    };
+    let server_addr = config.server.addr.clone();
+    let max_body_bytes = config.server.max_body_bytes;
+    let auth_data = web::Data::new(config.auth_config());
+    if !auth_data.is_enabled() {
+        warn!("No authentication configured, accepting requests from anyone");
+    }
 
     // Set up the server
     let server = match HttpServer::new(move || {
         App::new()
-            .app_data(post_queue.clone())
+            .app_data(scheduler_data.clone())
+            .app_data(job_store_data.clone())
+            .app_data(auth_data.clone())
+            .app_data(web::PayloadConfig::new(max_body_bytes))
             .service(web::resource("/").route(web::post().to(handle_post)))
+            .route("/jobs", web::get().to(handle_list_jobs))
+            .route("/jobs/{id}", web::get().to(handle_get_job))
+            .route("/jobs/{id}", web::delete().to(handle_cancel_job))
             .route("/health", web::get().to(handle_health))
+            .route("/metrics", web::get().to(handle_metrics))
     })
-    .workers(NUM_SERVER_WORKERS)
+    .workers(config.server.workers)
     .bind(server_addr.as_str())
     {
         Ok(item) => item,
@@ -165,53 +223,198 @@ async fn main() -> Result<()> {
     info!("Starting HTTP Server: {}", server_addr);
     tokio::task::spawn(server.run());
 
-    // Start the processing loop
+    // Start the processing loop, until the coordinator is asked to stop
+    let mut sigterm =
+        signal(SignalKind::terminate()).expect("Failed to create SIGTERM signal handler");
     info!("Starting the processing loop.");
     let mut run_cnt: usize = 0;
     loop {
         run_cnt += 1;
         info!("Awaiting request for run {} in current session.", run_cnt);
-        match rx.recv().await {
-            Some(input) => {
-                info!("Received request for run #{} in current session", run_cnt);
-                info!("From queue: {:?}", input);
-                match ManyProver::new(input, runtime_arc.clone()).await {
-                    Ok(mut manyprover) => {
-                        match manyprover.prove_blocks().await {
-                            Ok(_) => info!("Completed a request."),
-                            Err(err) => error!("Critical error: {}", err),
-                        };
-                    }
-                    Err(err) => error!("Critical configuration error: {}", err),
-                }
-            }
-            None => {
-                info!("Channel to process posts is closed.");
-                // Attempt to close the runtime proper.
-                match runtime_arc.close().await {
-                    Ok(_) => info!("Successfully terminated the runtime."),
-                    Err(err) => error!("Error closing the runtime: {}", err),
-                }
-                break;
-            }
-        }
+        let (job_id, permit) = tokio::select! {
+            next = scheduler.next() => next,
+            _ = sigterm.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        info!(
+            "Starting job {} for run #{} in current session",
+            job_id, run_cnt
+        );
+        let job_store = job_store.clone();
+        let runtime = runtime_arc.clone();
+        tokio::spawn(async move {
+            // Registered before the job is read, so that no cancellation is missed
+            job_store.register_abort_signal(job_id);
+            process_job(job_id, job_store.clone(), runtime).await;
+            job_store.release_abort_signal(job_id);
+            // Free the slot for the next job
+            drop(permit);
+        });
+    }
+
+    // The jobs still running are re-queued on the next start, see
+    // [JobStore::resumable]
+    info!("Stopping the processing loop.");
+    // Attempt to close the runtime proper.
+    match runtime_arc.close().await {
+        Ok(_) => info!("Successfully terminated the runtime."),
+        Err(err) => error!("Error closing the runtime: {}", err),
     }
 
     info!("Closing Coordinator");
     Ok(())
 }
 
+/// Proves the blocks of the job, recording the outcome in the [JobStore]
+async fn process_job(job_id: JobId, job_store: Arc<JobStore>, runtime: Arc<Runtime>) {
+    let input = match job_store.get(job_id) {
+        Some(record) if record.status.is_terminal() => {
+            info!("Skipping job {}, it is already {:?}", job_id, record.status);
+            return;
+        }
+        Some(record) => record.input,
+        None => {
+            error!("Job {} does not exist in the job store", job_id);
+            return;
+        }
+    };
+    info!("From queue: {:?}", input);
+    let job = JobHandle::new(job_id, job_store);
+    let notifier = match Notifier::new(
+        input.get_notifications(),
+        job_id,
+        input.get_run_name().clone(),
+    ) {
+        Ok(notifier) => notifier,
+        Err(err) => {
+            error!("Critical configuration error: {}", err);
+            metrics::record_failure("configuration");
+            job.fail(err.to_string());
+            return;
+        }
+    };
+    let failure = match ManyProver::new(input, runtime, job.clone(), notifier.clone()).await {
+        Ok(mut manyprover) => match manyprover.prove_blocks().await {
+            Ok(_) => {
+                info!("Completed job {}.", job_id);
+                None
+            }
+            Err(err @ ManyProverError::Cancelled) => {
+                info!("Job {} was cancelled.", job_id);
+                Some((err.kind(), err.to_string()))
+            }
+            Err(err) => {
+                error!("Critical error: {}", err);
+                metrics::record_failure(err.kind());
+                job.fail(err.to_string());
+                Some((err.kind(), err.to_string()))
+            }
+        },
+        Err(err) => {
+            error!("Critical configuration error: {}", err);
+            metrics::record_failure("configuration");
+            job.fail(err.to_string());
+            Some(("configuration", err.to_string()))
+        }
+    };
+    if let Some((kind, error)) = failure {
+        notifier.notify(JobEvent::JobFailed {
+            kind: kind.to_string(),
+            error,
+        });
+    }
+}
+
 /// Returns [HttpResponse] ([HttpResponse::Ok]) to respond that we are healthy
 async fn handle_health() -> impl Responder {
     debug!("Received health check, responding `OK`");
     HttpResponse::Ok().body("OK")
 }
 
-/// Recevies a request for [manyprover::ManyProver::prove_blocks]
+/// Returns the Prometheus metrics of the coordinator, along with those of the
+/// in-memory workers if any
+async fn handle_metrics() -> impl Responder {
+    match metrics::encode() {
+        Ok(metrics) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(metrics),
+        Err(err) => {
+            error!("Failed to encode the metrics: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Checks that the request is authenticated (see [zero_bin_common::auth]),
+/// returning the [HttpResponse::Unauthorized] to respond with otherwise.
+fn authorize(req: &HttpRequest, body: &[u8], auth: &AuthConfig) -> Result<(), HttpResponse> {
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or(req.path(), |path| path.as_str());
+    auth.verify(&AuthRequest {
+        method: req.method().as_str(),
+        path,
+        authorization: header(AUTHORIZATION.as_str()),
+        signature: header(SIGNATURE_HEADER),
+        timestamp: header(TIMESTAMP_HEADER),
+        body,
+    })
+    .map_err(|err| {
+        warn!(
+            "Rejected unauthenticated request to {}: {}",
+            req.path(),
+            err
+        );
+        metrics::record_failure("auth");
+        HttpResponse::Unauthorized().json(serde_json::json!({ "error": err.to_string() }))
+    })
+}
+
+/// Recevies a request for [manyprover::ManyProver::prove_blocks], storing it
+/// as a new job and responding with the job's id.
+///
+/// Responds [HttpResponse::BadRequest] with the error and the rejected field
+/// if the request is invalid (see [ProveBlocksInput::validate]).
 async fn handle_post(
-    wdtx: web::Data<tokio::sync::mpsc::Sender<ProveBlocksInput>>,
-    input: web::Json<ProveBlocksInput>,
+    req: HttpRequest,
+    scheduler: web::Data<Scheduler>,
+    job_store: web::Data<JobStore>,
+    auth: web::Data<AuthConfig>,
+    body: web::Bytes,
 ) -> impl Responder {
+    if let Err(response) = authorize(&req, &body, &auth) {
+        return response;
+    }
+
+    let input: ProveBlocksInput = match serde_json::from_slice(&body) {
+        Ok(input) => input,
+        Err(err) => {
+            warn!("Rejected malformed request: {}", err);
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": err.to_string(), "field": null }));
+        }
+    };
+    let defaults = match config::get() {
+        Ok(config) => &config.defaults,
+        Err(err) => {
+            error!("Cannot accept requests: {}", err);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": err.to_string(), "field": null }));
+        }
+    };
+    let input = input.with_default_outputs(defaults);
+    if let Err(err) = input.validate() {
+        warn!("Rejected invalid request: {}", err);
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({ "error": err.to_string(), "field": err.field() }));
+    }
+
     let start_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
         Ok(duration) => duration.as_secs(),
         Err(err) => {
@@ -220,128 +423,80 @@ async fn handle_post(
     };
     info!("Received request to prove blocks Request {}", start_time);
 
-    match wdtx.send(input.0).await {
-        Ok(_) => info!("Successfully queued Request {}", start_time),
+    let priority = input.get_priority();
+    let job_id = match job_store.create(input) {
+        Ok(job_id) => job_id,
         Err(err) => {
             error!(
-                "Critical error while trying to queue Request {}: {}",
+                "Critical error while trying to store Request {}: {}",
                 start_time, err
             );
-            return HttpResponse::InternalServerError();
+            return HttpResponse::InternalServerError().finish();
         }
-    }
+    };
+
+    scheduler.enqueue(job_id, priority);
+    info!(
+        "Successfully queued Request {} as job {}",
+        start_time, job_id
+    );
 
     // Respond the Accepted response
-    HttpResponse::Accepted()
+    HttpResponse::Accepted().json(serde_json::json!({ "job_id": job_id }))
 }
 
-//=======================================================================================================
-// Paladin Runtime Construction
-//=======================================================================================================
-
-/// The environment key for the Paladin Serializer, used to construct
-/// [paladin::config::Serializer]
-pub const PALADIN_SERIALIZER_ENVKEY: &str = "PALADIN_SERIALIZER";
-/// The environment key for the Paladin Runtime, used to construct
-/// [paladin::config::Runtime]
-pub const PALADIN_RUNTIME_ENVKEY: &str = "PALADIN_RUNTIME";
-/// The environment key for the number of workers (only matters in operating in
-/// memory)
-pub const PALADIN_AMQP_NUM_WORKERS_ENVKEY: &str = "PALADIN_AMQP_NUM_WORKERS";
-/// The environment key for the amqp uri (only matters in operating w/ AMQP)
-pub const PALADIN_AMQP_URI_ENVKEY: &str = "PALADIN_AMQP_URI";
-/// The default number of workers to be used when operating in memory if not
-/// specified in the environment
-pub const DFLT_NUM_WORKERS: usize = 1;
-
-/// Constructs the [Config] given environment variables.
-fn build_paladin_config_from_env() -> Config {
-    let serializer = match env::var(PALADIN_SERIALIZER_ENVKEY) {
-        Ok(serializer) if serializer.contains("POSTCARD") => Serializer::Postcard,
-        Err(env::VarError::NotPresent) => {
-            info!("Paladin Serializer not specified, using Default");
-            Serializer::default()
-        }
-        Ok(serializer) if serializer.contains("CBOR") => Serializer::Cbor,
-        Ok(unknown_serializer) => {
-            panic!("Unsure what Paladin Serializer: {}", unknown_serializer);
-        }
-        Err(env::VarError::NotUnicode(os_str)) => {
-            panic!("Non-Unicode input for Paladin Serializer: {:?}", os_str);
-        }
-    };
-
-    let runtime = match env::var(PALADIN_RUNTIME_ENVKEY) {
-        Ok(paladin_runtime) if paladin_runtime.contains("AMQP") => paladin::config::Runtime::Amqp,
-        Ok(paladin_runtime) if paladin_runtime.contains("MEMORY") => {
-            paladin::config::Runtime::InMemory
-        }
-        Ok(unknown_runtime) => {
-            panic!("Unsure what Paladin Runtime: {}", unknown_runtime);
-        }
-        Err(env::VarError::NotPresent) => {
-            info!("Paladin Runtime not specified, using default");
-            paladin::config::Runtime::InMemory
-        }
-        Err(env::VarError::NotUnicode(os_str)) => {
-            panic!("Non-Unicode input for Paladin Runtime: {:?}", os_str);
-        }
-    };
+/// Returns every job in the [JobStore]
+async fn handle_list_jobs(
+    req: HttpRequest,
+    job_store: web::Data<JobStore>,
+    auth: web::Data<AuthConfig>,
+) -> impl Responder {
+    if let Err(response) = authorize(&req, &[], &auth) {
+        return response;
+    }
+    HttpResponse::Ok().json(job_store.list())
+}
 
-    let num_workers = match (runtime, env::var(PALADIN_AMQP_NUM_WORKERS_ENVKEY)) {
-        (paladin::config::Runtime::InMemory, Ok(num_workers)) => {
-            match num_workers.parse::<usize>() {
-                Ok(num_workers) => Some(num_workers),
-                Err(err) => {
-                    error!("Failed to parse number of workers: {}", err);
-                    panic!("Failed to parse number of workers: {}", err);
-                }
-            }
-        }
-        (paladin::config::Runtime::InMemory, Err(env::VarError::NotPresent)) => {
-            info!(
-                "Number of workers not specified for InMemory runtime, using default: {}",
-                DFLT_NUM_WORKERS
-            );
-            None //Some(DFLT_NUM_WORKERS)
-        }
-        (paladin::config::Runtime::InMemory, Err(env::VarError::NotUnicode(os_str))) => {
-            info!("Non-Unicode input for number of workers: {:?}", os_str);
-            panic!("Non-Unicode input for number of workers: {:?}", os_str);
-        }
-        (_, Ok(num_workers)) => {
-            info!(
-                "Not operating in memory, disregarding number of workers from env ({})",
-                num_workers
-            );
-            None
-        }
-        (_, _) => None,
-    };
+/// Returns the job with the given id, or [HttpResponse::NotFound]
+async fn handle_get_job(
+    req: HttpRequest,
+    job_store: web::Data<JobStore>,
+    auth: web::Data<AuthConfig>,
+    id: web::Path<JobId>,
+) -> impl Responder {
+    if let Err(response) = authorize(&req, &[], &auth) {
+        return response;
+    }
+    match job_store.get(id.into_inner()) {
+        Some(record) => HttpResponse::Ok().json(record),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
 
-    let amqp_uri = match (runtime, env::var(PALADIN_AMQP_URI_ENVKEY)) {
-        (paladin::config::Runtime::Amqp, Ok(uri)) => Some(uri),
-        (paladin::config::Runtime::Amqp, Err(env::VarError::NotPresent)) => {
-            panic!("If AMQP runtime, must specify amqp uri in environment");
-        }
-        (paladin::config::Runtime::Amqp, Err(env::VarError::NotUnicode(os_str))) => {
-            panic!("Non-Unicode input for amqp uri string: {:?}", os_str);
+/// Cancels the job with the given id, stopping it if it is currently being
+/// proven.
+///
+/// Responds [HttpResponse::Conflict] if the job has already finished.
+async fn handle_cancel_job(
+    req: HttpRequest,
+    job_store: web::Data<JobStore>,
+    auth: web::Data<AuthConfig>,
+    id: web::Path<JobId>,
+) -> impl Responder {
+    if let Err(response) = authorize(&req, &[], &auth) {
+        return response;
+    }
+    let id = id.into_inner();
+    info!("Received request to cancel job {}", id);
+    match job_store.cancel(id) {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(JobStoreError::NotFound(_)) => HttpResponse::NotFound().finish(),
+        Err(err @ JobStoreError::AlreadyFinished(..)) => {
+            HttpResponse::Conflict().body(err.to_string())
         }
-        (_, Ok(uri)) => {
-            info!(
-                "Ignoring AMQP Uri string since we are operating InMemory ({})",
-                uri
-            );
-            None
+        Err(err) => {
+            error!("Failed to cancel job {}: {}", id, err);
+            HttpResponse::InternalServerError().finish()
         }
-        (_, _) => None,
-    };
-
-    // Construct the Config
-    Config {
-        serializer,
-        runtime,
-        num_workers,
-        amqp_uri,
     }
 }
@@ -5,15 +5,22 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use chrono::{DateTime, Utc};
+use futures::{future, StreamExt};
 use paladin::runtime::Runtime;
-use tokio::task::JoinError;
+use prover::{prove_and_benchmark_stream, BlockFailure};
+use tokio::{sync::Semaphore, task::JoinError};
 use tracing::{debug, error, info, warn};
+use zero_bin_common::{failure::FailureClass, metrics};
 
 use crate::benchmarking::{
     BenchmarkingOutput, BenchmarkingOutputBuildError, BenchmarkingOutputError, BenchmarkingStats,
+    TransactionBenchmarkingStats,
 };
-use crate::fetch::{fetch, FetchError};
+use crate::deadletter::DeadLetterStore;
+use crate::fetch::{fetch_stream, FetchError, FetchedBlock};
 use crate::input::ProveBlocksInput;
+use crate::jobs::{BlockFailureRecord, BlockStatus, JobHandle, JobStatus};
+use crate::notify::{JobEvent, Notifier};
 use crate::proofout::{ProofOutput, ProofOutputBuildError, ProofOutputError};
 
 //===========================================================================================
@@ -22,11 +29,14 @@ use crate::proofout::{ProofOutput, ProofOutputBuildError, ProofOutputError};
 #[derive(Debug)]
 pub enum ManyProverError {
     Fetch(FetchError),
-    Proof(anyhow::Error),
+    /// A block failed, and its failure was not skipped
+    Proof(BlockFailure),
     BenchmarkingOutput(BenchmarkingOutputError),
     ProofOutError(ProofOutputError),
     ParallelJoinError(JoinError),
     FailedToSendTask(u64),
+    /// The job was cancelled before it completed
+    Cancelled,
 }
 
 impl From<FetchError> for ManyProverError {
@@ -35,6 +45,21 @@ impl From<FetchError> for ManyProverError {
     }
 }
 
+impl ManyProverError {
+    /// Returns the kind of error, as recorded in the failure metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Fetch(_) => "fetch",
+            Self::Proof(failure) => failure.class.as_str(),
+            Self::BenchmarkingOutput(_) => "benchmark_output",
+            Self::ProofOutError(_) => "proof_output",
+            Self::ParallelJoinError(_) => "parallel_join",
+            Self::FailedToSendTask(_) => "send_task",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
 impl std::fmt::Display for ManyProverError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Debug::fmt(&self, f)
@@ -53,6 +78,8 @@ pub enum ManyProverBuildError {
     BenchmarkingOutput(BenchmarkingOutputBuildError),
     /// An error while preparing the means of outputting the proof output
     ProofOutError(ProofOutputBuildError),
+    /// An error while preparing the means of outputting the dead letters
+    DeadLetterOutError(ProofOutputBuildError),
     /// Returned with a description of why the configuration was invalid
     InvalidConfiguration(String),
 }
@@ -79,6 +106,12 @@ pub struct ManyProver {
     pub proof_out: Option<ProofOutput>,
     /// If present, the expected handler for outputting benchmark statistics
     pub benchmark_out: Option<BenchmarkingOutput>,
+    /// If present, the store the inputs of the failed blocks are written to
+    pub dead_letter_out: Option<DeadLetterStore>,
+    /// The job in the [crate::jobs::JobStore] we report our progress to
+    pub job: JobHandle,
+    /// Notifies the targets of the request of the progress of the job
+    pub notifier: Notifier,
 }
 
 unsafe impl Send for ManyProver {}
@@ -89,6 +122,8 @@ impl ManyProver {
     pub async fn new(
         input: ProveBlocksInput,
         runtime: Arc<Runtime>,
+        job: JobHandle,
+        notifier: Notifier,
     ) -> Result<Self, ManyProverBuildError> {
         //=================================================================================
         // Starting messages
@@ -105,7 +140,7 @@ impl ManyProver {
         debug!("Preparing means of outputting the generated proofs...");
         // get the proof
         let proof_out = match input.get_proof_output() {
-            Some(proof_method) => match ProofOutput::from_method(proof_method) {
+            Some(proof_method) => match ProofOutput::from_method(proof_method).await {
                 Ok(proof_out) => {
                     info!("Instansiated means of proof output");
                     Some(proof_out)
@@ -127,6 +162,7 @@ impl ManyProver {
                 match BenchmarkingOutput::from_config(
                     benchmark_config.clone(),
                     input.estimate_expected_number_proofs(),
+                    input.get_run_name().clone(),
                 )
                 .await
                 {
@@ -143,11 +179,33 @@ impl ManyProver {
             }
         };
 
+        debug!("Preparing dead-letter output...");
+        let dead_letter_out = match input.get_dead_letter_output() {
+            Some(method) => {
+                match DeadLetterStore::from_method(method, job.id, input.get_run_name().clone())
+                    .await
+                {
+                    Ok(dead_letter_out) => Some(dead_letter_out),
+                    Err(err) => {
+                        error!("Failed to construct the dead-letter output: {}", err);
+                        return Err(ManyProverBuildError::DeadLetterOutError(err));
+                    }
+                }
+            }
+            None => {
+                info!("Dead-letter output is disabled, failed blocks will only be recorded.");
+                None
+            }
+        };
+
         Ok(Self {
             input_request: input,
             runtime,
             proof_out,
             benchmark_out,
+            dead_letter_out,
+            job,
+            notifier,
         })
     }
 
@@ -168,97 +226,292 @@ impl ManyProver {
     pub async fn prove_blocks(&mut self) -> Result<(), ManyProverError> {
         info!("Startng to prove blocks");
 
+        if self.job.is_cancelled() {
+            return Err(ManyProverError::Cancelled);
+        }
+
         info!("Starting fetch");
-        let prover_input = fetch(
+        self.job.set_status(JobStatus::Fetching);
+        self.notifier.notify(JobEvent::JobStarted {
+            block_interval: self.input_request.get_block_interval_str().to_string(),
+        });
+        let block_proof_start_time: DateTime<Utc> = Utc::now();
+
+        // Fetched blocks hold a permit until they have been proven, bounding
+        // how far the fetching runs ahead of the proving.
+        let lookahead = Arc::new(Semaphore::new(self.input_request.get_fetch_lookahead()));
+
+        let fetched_blocks = fetch_stream(
             self.input_request
                 .get_block_interval()
                 .map_err(FetchError::ZeroBinRpcFetchError)?,
             self.input_request.get_checkpoint(),
             self.input_request.get_blocksource(),
+            self.input_request.get_fetch_concurrency(),
+            lookahead.clone(),
+            self.input_request.get_retry_policies().fetch.skip,
         )
         .await?;
-        info!("Fetch completed");
-
-        info!("Starting proofs");
-        let block_proof_start_time: DateTime<Utc> = Utc::now();
-        let block_proofs = match prover_input
-            .proverinput
-            .prove_and_benchmark(&self.runtime, None, true, None)
-            .await
-        {
-            Ok(block_proofs) => block_proofs,
-            Err(err) => return Err(ManyProverError::Proof(err)),
-        };
-        info!("Finalized benchmarked proofs");
-
-        let mut cumulative_n_txs: u64 = 0;
-        let mut cumulative_gas_used: u64 = 0;
-
-        let mut fetch_times = prover_input.fetch_times.iter();
-
-        for (blocknum, maybe_block_proof) in block_proofs.iter() {
-            let fetch_time = fetch_times.next();
 
-            let benchmark_block_proof = match maybe_block_proof {
-                Some(benchmark_block_proof) => benchmark_block_proof,
-                None => {
-                    warn!("BLOCK NUM {} DID NOT HAVE A RETURNED VALUE", blocknum);
-                    continue;
+        // The block proofs are output as soon as they are produced, while the
+        // following blocks are still being fetched and proved.
+        let runtime = &self.runtime;
+        let proof_out = &self.proof_out;
+        let dead_letter_out = &self.dead_letter_out;
+        let benchmark_out = &mut self.benchmark_out;
+        let job = &self.job;
+        let notifier = &self.notifier;
+        let retry_policies = self.input_request.get_retry_policies();
+        let fetch_lookahead = lookahead.clone();
+
+        // The fetch timings are passed alongside the blocks to the output, in
+        // the same order as the proofs.
+        let (fetch_tx, mut fetch_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (proof_tx, mut proof_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let proving = async move {
+            let mut proving_started = false;
+            let blocks = fetched_blocks
+                .map(move |fetched| {
+                    // `Some(None)` for a skipped block, `None` once the fetching
+                    // failed
+                    match fetched {
+                        Ok(FetchedBlock { input, info }) => {
+                            job.set_block_status(info.block_number, BlockStatus::Fetched);
+                            if !proving_started {
+                                info!("Starting proofs");
+                                job.set_status(JobStatus::Proving);
+                                proving_started = true;
+                            }
+                            let _ = fetch_tx.send(Ok(info));
+                            Some(Some(input))
+                        }
+                        Err(FetchError::BlockSkipped(block_number, err)) => {
+                            let failure = BlockFailureRecord {
+                                block_number,
+                                class: FailureClass::Fetch,
+                                attempts: 1,
+                                skipped: true,
+                                error: format!("{:#}", err),
+                                timestamp: Utc::now(),
+                                // There is no input to replay
+                                dead_letter: None,
+                            };
+                            job.record_failure(failure.clone());
+                            // The block will not be proven
+                            fetch_lookahead.add_permits(1);
+                            notifier.notify(skipped_event(failure));
+                            Some(None)
+                        }
+                        Err(err) => {
+                            error!("Failed to fetch block: {}", err);
+                            let _ = fetch_tx.send(Err(err));
+                            None
+                        }
+                    }
+                })
+                // Prove the blocks fetched before a failed fetch, then stop
+                .take_while(|input| future::ready(input.is_some()))
+                .filter_map(|input| future::ready(input.flatten()));
+
+            let block_proofs = prove_and_benchmark_stream(
+                blocks,
+                runtime,
+                None,
+                true,
+                retry_policies,
+                Some(job.abort_signal()),
+            );
+            futures::pin_mut!(block_proofs);
+            while let Some(block_proof) = block_proofs.next().await {
+                // Skipped blocks do not stop the following blocks
+                let failed = matches!(&block_proof, Err(failure) if !failure.skipped);
+                // Stop proving once a block failed or the output stopped
+                if proof_tx.send(block_proof).is_err() || failed {
+                    break;
                 }
-            };
-
-            cumulative_n_txs += benchmark_block_proof.n_txs;
-            cumulative_gas_used += benchmark_block_proof.gas_used;
+            }
+        };
 
-            let proof_out_time = match &self.proof_out {
-                Some(proof_out) => {
-                    let proof_out_instant = Instant::now();
-                    match proof_out.write(&benchmark_block_proof.proof) {
-                        Ok(_) => (),
-                        Err(err) => return Err(ManyProverError::ProofOutError(err)),
+        let outputting = async {
+            let mut cumulative_n_txs: u64 = 0;
+            let mut cumulative_gas_used: u64 = 0;
+
+            while let Some(block_proof) = proof_rx.recv().await {
+                let fetch_info = match fetch_rx.recv().await {
+                    Some(Ok(fetch_info)) => Some(fetch_info),
+                    _ => None,
+                };
+
+                let benchmark_block_proof = match block_proof {
+                    Ok(benchmark_block_proof) => benchmark_block_proof,
+                    Err(failure) if job.is_cancelled() => {
+                        warn!("Failed to prove block {}", failure.block_number);
+                        job.set_block_status(failure.block_number, BlockStatus::Failed);
+                        return Err(ManyProverError::Cancelled);
                     }
-                    Some(proof_out_instant.elapsed())
-                }
-                None => None,
-            };
+                    Err(failure) => {
+                        warn!("{}", failure);
+                        let mut record = BlockFailureRecord::from(&failure);
+                        if let Some(dead_letter_out) = dead_letter_out {
+                            match dead_letter_out.write(&failure).await {
+                                Ok(location) => record.dead_letter = Some(location),
+                                Err(err) => {
+                                    error!(
+                                        "Failed to write the dead letter of block {}: {}",
+                                        failure.block_number, err
+                                    );
+                                    metrics::record_failure("dead_letter");
+                                }
+                            }
+                        }
+                        job.record_failure(record.clone());
+                        if !failure.skipped {
+                            return Err(ManyProverError::Proof(failure));
+                        }
+                        notifier.notify(skipped_event(record));
+                        // Allow the next block to be fetched
+                        lookahead.add_permits(1);
+                        continue;
+                    }
+                };
+                info!(
+                    "Received proof for block {}",
+                    benchmark_block_proof.proof.b_height
+                );
+
+                // Allow the next block to be fetched
+                lookahead.add_permits(1);
+
+                cumulative_n_txs += benchmark_block_proof.n_txs;
+                cumulative_gas_used += benchmark_block_proof.gas_used;
+
+                let (proof_location, proof_out_time) = match proof_out {
+                    Some(proof_out) => {
+                        let proof_out_instant = Instant::now();
+                        let proof_location =
+                            match proof_out.write(&benchmark_block_proof.proof).await {
+                                Ok(proof_location) => proof_location,
+                                Err(err) => return Err(ManyProverError::ProofOutError(err)),
+                            };
+                        (Some(proof_location), Some(proof_out_instant.elapsed()))
+                    }
+                    None => (None, None),
+                };
 
-            match &mut self.benchmark_out {
-                Some(benchmark_out) => benchmark_out.push(BenchmarkingStats {
+                job.set_block_status(benchmark_block_proof.proof.b_height, BlockStatus::Proved);
+                notifier.notify(JobEvent::BlockProved {
                     block_number: benchmark_block_proof.proof.b_height,
+                    proof_location,
                     n_txs: benchmark_block_proof.n_txs,
-                    cumulative_n_txs: Some(cumulative_n_txs),
-                    fetch_duration: fetch_time.copied(),
-                    total_proof_duration: benchmark_block_proof
-                        .total_dur
-                        .expect("Value is expected"),
-                    prep_duration: benchmark_block_proof.prep_dur,
-                    proof_out_duration: proof_out_time,
-                    agg_wait_duration: benchmark_block_proof.agg_wait_dur,
-                    agg_duration: benchmark_block_proof.agg_dur,
                     gas_used: benchmark_block_proof.gas_used,
-                    gas_used_per_tx: benchmark_block_proof.gas_used_per_tx.clone(),
-                    txproof_duration: benchmark_block_proof.proof_dur,
+                    fetch_duration: fetch_info.map(|info| info.fetch_duration.as_secs_f64()),
+                    proof_duration: benchmark_block_proof
+                        .total_dur
+                        .unwrap_or_default()
+                        .as_secs_f64(),
+                    proof_out_duration: proof_out_time.map(|dur| dur.as_secs_f64()),
                     start_time: benchmark_block_proof.start_time,
                     end_time: benchmark_block_proof.end_time,
-                    difficulty: benchmark_block_proof.difficulty,
-                    cumulative_gas_used: Some(cumulative_gas_used),
-                    overall_elapsed_seconds: Some(
-                        (benchmark_block_proof.end_time - block_proof_start_time).num_seconds()
-                            as u64,
-                    ),
-                }),
-                None => todo!(),
+                });
+
+                match benchmark_out {
+                    Some(benchmark_out) => {
+                        match benchmark_out
+                            .push(BenchmarkingStats {
+                                block_number: benchmark_block_proof.proof.b_height,
+                                n_txs: benchmark_block_proof.n_txs,
+                                cumulative_n_txs: Some(cumulative_n_txs),
+                                fetch_duration: fetch_info.map(|info| info.fetch_duration),
+                                total_proof_duration: benchmark_block_proof
+                                    .total_dur
+                                    .expect("Value is expected"),
+                                prep_duration: benchmark_block_proof.prep_dur,
+                                proof_out_duration: proof_out_time,
+                                agg_wait_duration: benchmark_block_proof.agg_wait_dur,
+                                agg_duration: benchmark_block_proof.agg_dur,
+                                gas_used: benchmark_block_proof.gas_used,
+                                gas_used_per_tx: benchmark_block_proof.gas_used_per_tx.clone(),
+                                txproof_duration: benchmark_block_proof.proof_dur,
+                                start_time: benchmark_block_proof.start_time,
+                                end_time: benchmark_block_proof.end_time,
+                                difficulty: benchmark_block_proof.difficulty,
+                                cumulative_gas_used: Some(cumulative_gas_used),
+                                overall_elapsed_seconds: Some(
+                                    (benchmark_block_proof.end_time - block_proof_start_time)
+                                        .num_seconds() as u64,
+                                ),
+                                fetch_start_time: fetch_info.map(|info| info.start_time),
+                                fetch_end_time: fetch_info.map(|info| info.end_time),
+                                proof_queue_duration: fetch_info.and_then(|info| {
+                                    (benchmark_block_proof.start_time - info.end_time)
+                                        .to_std()
+                                        .ok()
+                                }),
+                                transactions: benchmark_block_proof
+                                    .txn_stats
+                                    .iter()
+                                    .map(TransactionBenchmarkingStats::from_txn_proof_stats)
+                                    .collect(),
+                            })
+                            .await
+                        {
+                            Ok(_) => (),
+                            Err(err) => return Err(ManyProverError::BenchmarkingOutput(err)),
+                        }
+                    }
+                    None => (),
+                }
             }
-        }
 
-        match &self.benchmark_out {
-            Some(benchmark_out) => match benchmark_out.publish().await {
-                Ok(_) => (),
-                Err(err) => return Err(ManyProverError::BenchmarkingOutput(err)),
-            },
-            None => (),
-        }
+            // All fetched blocks were proven, report if the fetching stopped
+            // early
+            match fetch_rx.try_recv() {
+                Ok(Err(err)) => Err(ManyProverError::Fetch(err)),
+                _ => Ok(()),
+            }
+        };
+
+        let result = {
+            futures::pin_mut!(outputting);
+            tokio::select! {
+                // Dropping `proving` stops dispatching the remaining blocks
+                result = &mut outputting => result,
+                _ = proving => outputting.await,
+                // Following the chain tip only ends once the job is cancelled
+                _ = job.cancelled() => Err(ManyProverError::Cancelled),
+            }
+        };
+        // Stop any fetching still in progress
+        lookahead.close();
+        // Flush the benchmark stats which were not flushed yet, even if the
+        // job failed
+        let published = match &mut self.benchmark_out {
+            Some(benchmark_out) => benchmark_out
+                .publish()
+                .await
+                .map_err(ManyProverError::BenchmarkingOutput),
+            None => Ok(()),
+        };
+        result?;
+        published?;
+        info!("Finalized benchmarked proofs");
+
+        self.job.set_status(JobStatus::Done);
+        self.notifier.notify(JobEvent::JobCompleted {
+            proved_blocks: self.job.proved_blocks(),
+        });
 
         Ok(())
     }
 }
+
+/// Returns the [JobEvent::BlockSkipped] of the failure
+fn skipped_event(failure: BlockFailureRecord) -> JobEvent {
+    JobEvent::BlockSkipped {
+        block_number: failure.block_number,
+        class: failure.class.to_string(),
+        attempts: failure.attempts,
+        error: failure.error,
+    }
+}
@@ -0,0 +1,432 @@
+//! This module contains the notifications sent to other systems as a job
+//! progresses.
+//!
+//! Every [NotificationTarget] of a [crate::input::ProveBlocksInput] receives a
+//! [JobNotification] when the job starts, whenever a block is proven or
+//! skipped, and when the job completes or fails.  Notifications are best
+//! effort: they are sent in the background, and a target failing to receive
+//! one is logged, but never fails the job.
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::future;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, error, info};
+use zero_bin_common::metrics;
+
+use crate::jobs::JobId;
+
+//==================================================================================
+// Notification Targets
+//==================================================================================
+
+/// Where the notifications of a job are sent to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NotificationTarget {
+    /// POSTs every [JobNotification] as JSON to a url
+    Webhook {
+        /// The url to POST the notifications to
+        url: String,
+        /// Additional headers to send along with the notifications (i.e.
+        /// `Authorization`)
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+unsafe impl Send for NotificationTarget {}
+
+#[derive(Debug)]
+pub enum NotifierBuildError {
+    /// Returned when the webhook url or one of its headers is invalid
+    InvalidWebhook(String, anyhow::Error),
+    /// Returned when the HTTP client sending the notifications could not be
+    /// built
+    Client(anyhow::Error),
+}
+
+impl std::fmt::Display for NotifierBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self, f)
+    }
+}
+
+impl std::error::Error for NotifierBuildError {}
+
+/// The maximum amount of time a target has to receive a notification
+pub const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+//==================================================================================
+// Events
+//==================================================================================
+
+/// An event in the lifecycle of a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// The job started fetching and proving its blocks
+    JobStarted {
+        /// The block interval of the request
+        block_interval: String,
+    },
+    /// A block was proven, and its proof was output
+    BlockProved {
+        /// The block number of the block proved
+        block_number: u64,
+        /// Where the proof was output, if it was
+        proof_location: Option<String>,
+        /// The number of transactions in the block
+        n_txs: u64,
+        /// The gas used by the block
+        gas_used: u64,
+        /// The time taken to fetch the block, in seconds
+        fetch_duration: Option<f64>,
+        /// The time taken to prove the block, in seconds
+        proof_duration: f64,
+        /// The time taken to output the proof, in seconds
+        proof_out_duration: Option<f64>,
+        /// When the proof started
+        start_time: DateTime<Utc>,
+        /// When the proof ended
+        end_time: DateTime<Utc>,
+    },
+    /// A block failed once its retries were exhausted, and was skipped
+    BlockSkipped {
+        /// The block number of the block skipped
+        block_number: u64,
+        /// The class of the failure (i.e. `fetch` or `witness_generation`)
+        class: String,
+        /// The number of times the block was attempted
+        attempts: u32,
+        /// The description of the last error
+        error: String,
+    },
+    /// The job completed successfully
+    JobCompleted {
+        /// The number of blocks proven
+        proved_blocks: u64,
+    },
+    /// The job failed (or was cancelled)
+    JobFailed {
+        /// The kind of error, as recorded in the failure metrics (i.e.
+        /// `fetch`, `proving` or `cancelled`)
+        kind: String,
+        /// The description of the error
+        error: String,
+    },
+}
+
+impl JobEvent {
+    /// Returns the name of the event, as in its `event` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::JobStarted { .. } => "job_started",
+            Self::BlockProved { .. } => "block_proved",
+            Self::BlockSkipped { .. } => "block_skipped",
+            Self::JobCompleted { .. } => "job_completed",
+            Self::JobFailed { .. } => "job_failed",
+        }
+    }
+}
+
+/// The body of every notification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobNotification {
+    /// The identifier of the job
+    pub job_id: JobId,
+    /// The name of the run, as in the request
+    pub run_name: Option<String>,
+    /// When the event happened
+    pub timestamp: DateTime<Utc>,
+    /// The event itself
+    #[serde(flatten)]
+    pub event: JobEvent,
+}
+
+//==================================================================================
+// Notifier
+//==================================================================================
+
+/// A webhook receiving the notifications
+#[derive(Debug, Clone)]
+struct Webhook {
+    url: reqwest::Url,
+    headers: reqwest::header::HeaderMap,
+}
+
+impl Webhook {
+    fn new(url: &str, headers: &HashMap<String, String>) -> Result<Self, NotifierBuildError> {
+        let invalid = |err: anyhow::Error| NotifierBuildError::InvalidWebhook(url.to_string(), err);
+
+        let headers = headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|err| invalid(err.into()))?,
+                    reqwest::header::HeaderValue::from_str(value)
+                        .map_err(|err| invalid(err.into()))?,
+                ))
+            })
+            .collect::<Result<_, NotifierBuildError>>()?;
+
+        Ok(Self {
+            url: reqwest::Url::parse(url).map_err(|err| invalid(err.into()))?,
+            headers,
+        })
+    }
+}
+
+/// The [Notifier] sends the [JobEvent]s of a job to all of its
+/// [NotificationTarget]s.
+///
+/// The notifications are sent in order by a background task, so that a slow
+/// target never holds up the job.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    job_id: JobId,
+    run_name: Option<String>,
+    /// The channel to the background task, if there is any target
+    sender: Option<UnboundedSender<JobNotification>>,
+}
+
+impl Notifier {
+    /// Returns the [Notifier] of a job, given its [NotificationTarget]s, and
+    /// spawns the task sending its notifications.
+    pub fn new(
+        targets: &[NotificationTarget],
+        job_id: JobId,
+        run_name: Option<String>,
+    ) -> Result<Self, NotifierBuildError> {
+        let webhooks = targets
+            .iter()
+            .map(|target| match target {
+                NotificationTarget::Webhook { url, headers } => Webhook::new(url, headers),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sender = match webhooks.is_empty() {
+            true => None,
+            false => {
+                let client = reqwest::Client::builder()
+                    .timeout(NOTIFICATION_TIMEOUT)
+                    .build()
+                    .map_err(|err| NotifierBuildError::Client(err.into()))?;
+                let (sender, receiver) = mpsc::unbounded_channel();
+                tokio::spawn(send_notifications(client, webhooks, receiver));
+                Some(sender)
+            }
+        };
+
+        Ok(Self {
+            job_id,
+            run_name,
+            sender,
+        })
+    }
+
+    /// Queues the event to be sent to every target.
+    pub fn notify(&self, event: JobEvent) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        debug!(
+            "Queuing {} notification of job {}",
+            event.name(),
+            self.job_id
+        );
+        let notification = JobNotification {
+            job_id: self.job_id,
+            run_name: self.run_name.clone(),
+            timestamp: Utc::now(),
+            event,
+        };
+        if sender.send(notification).is_err() {
+            error!("Failed to queue the notification of job {}", self.job_id);
+            metrics::record_failure("notification");
+        }
+    }
+}
+
+/// Sends the notifications received to every webhook, until every [Notifier]
+/// of the job is dropped.  Failures are logged, and recorded as
+/// `notification` failures.
+async fn send_notifications(
+    client: reqwest::Client,
+    webhooks: Vec<Webhook>,
+    mut receiver: UnboundedReceiver<JobNotification>,
+) {
+    while let Some(notification) = receiver.recv().await {
+        let name = notification.event.name();
+        let notification = &notification;
+        let client = &client;
+        future::join_all(webhooks.iter().map(|webhook| async move {
+            let response = client
+                .post(webhook.url.clone())
+                .headers(webhook.headers.clone())
+                .json(notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            match response {
+                Ok(_) => info!("Sent {} notification to {}", name, webhook.url),
+                Err(err) => {
+                    error!(
+                        "Failed to send {} notification to {}: {}",
+                        name, webhook.url, err
+                    );
+                    metrics::record_failure("notification");
+                }
+            }
+        }))
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    fn webhook(url: &str, headers: &[(&str, &str)]) -> NotificationTarget {
+        NotificationTarget::Webhook {
+            url: url.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Reads a request, returning its lowercased headers and its body
+    async fn read_request(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut request = Vec::new();
+        let mut chunk = [0; 1024];
+        loop {
+            let n = stream.read(&mut chunk).await.unwrap();
+            request.extend_from_slice(&chunk[..n]);
+            if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |length| length.trim().parse::<usize>().unwrap());
+                let body = end + 4..end + 4 + length;
+                if request.len() >= body.end {
+                    return (headers, request[body].to_vec());
+                }
+            }
+            assert!(n > 0, "the connection was closed mid-request");
+        }
+    }
+
+    /// Serves a webhook on a local port, returning its url and the headers and
+    /// bodies of the requests it received
+    async fn serve() -> (String, UnboundedReceiver<(String, serde_json::Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let (headers, body) = read_request(&mut stream).await;
+                let _ = sender.send((headers, serde_json::from_slice(&body).unwrap()));
+                // Closing the connection keeps one request per connection
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn rejects_invalid_webhooks() {
+        for target in [
+            webhook("not a url", &[]),
+            webhook("http://localhost/hook", &[("bad header", "value")]),
+            webhook("http://localhost/hook", &[("Authorization", "bad\nvalue")]),
+        ] {
+            assert!(matches!(
+                Notifier::new(&[target], 1, None),
+                Err(NotifierBuildError::InvalidWebhook(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn ignores_events_without_targets() {
+        let notifier = Notifier::new(&[], 1, None).unwrap();
+        notifier.notify(JobEvent::JobCompleted { proved_blocks: 1 });
+    }
+
+    #[test]
+    fn notifications_are_tagged_with_the_event_name() {
+        let events = [
+            JobEvent::JobStarted {
+                block_interval: String::from("1..=2"),
+            },
+            JobEvent::BlockSkipped {
+                block_number: 1,
+                class: String::from("fetch"),
+                attempts: 3,
+                error: String::from("timeout"),
+            },
+            JobEvent::JobCompleted { proved_blocks: 1 },
+            JobEvent::JobFailed {
+                kind: String::from("cancelled"),
+                error: String::from("cancelled"),
+            },
+        ];
+        for event in events {
+            let name = event.name();
+            let notification = serde_json::to_value(JobNotification {
+                job_id: 3,
+                run_name: Some(String::from("run")),
+                timestamp: Utc::now(),
+                event,
+            })
+            .unwrap();
+
+            assert_eq!(notification["event"], name);
+            assert_eq!(notification["job_id"], 3);
+            let decoded: JobNotification = serde_json::from_value(notification).unwrap();
+            assert_eq!(decoded.event.name(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_the_notifications_in_order() {
+        let (url, mut requests) = serve().await;
+        let notifier = Notifier::new(
+            &[webhook(&url, &[("Authorization", "Bearer secret")])],
+            7,
+            Some(String::from("run")),
+        )
+        .unwrap();
+
+        notifier.notify(JobEvent::JobStarted {
+            block_interval: String::from("1..=2"),
+        });
+        notifier.notify(JobEvent::JobCompleted { proved_blocks: 2 });
+
+        for name in ["job_started", "job_completed"] {
+            let (headers, body) = tokio::time::timeout(Duration::from_secs(10), requests.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(headers.contains("authorization: bearer secret"));
+            assert_eq!(body["event"], name);
+            assert_eq!(body["job_id"], 7);
+            assert_eq!(body["run_name"], "run");
+        }
+    }
+}
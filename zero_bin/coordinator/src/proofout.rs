@@ -1,17 +1,36 @@
 //! Output for provers
+//!
+//! Each [ProofOutputMethod] is backed by a [ProofOutputSink], which receives
+//! every [GeneratedBlockProof] as soon as it has been produced.  The sinks also
+//! store other objects of a job, such as its dead letters (see
+//! [crate::deadletter]).
 // std imports
-use std::{env::VarError, fs::create_dir_all, path::PathBuf};
+use std::{
+    collections::HashMap, env::VarError, fs::create_dir_all, io::Write, path::PathBuf,
+    time::Duration,
+};
 
-use proof_gen::proof_types::GeneratedBlockProof;
-use serde_json::to_writer;
 // 3rd party imports
+use async_trait::async_trait;
+use proof_gen::proof_types::GeneratedBlockProof;
 use tracing::{debug, error, info, warn};
+use zero_bin_common::proof_format::{ProofEncoder, ProofFormat};
+
+use crate::{
+    config::ConfigError,
+    objstore::{ObjectStore, ObjectStoreConfig, ObjectStoreError},
+};
 
 #[derive(Debug)]
 pub enum ProofOutputError {
     FileCreationError(anyhow::Error),
     FileWritingError(anyhow::Error),
-    MethodDataMismatch,
+    /// Returned when we fail to serialize the proof
+    SerializationError(anyhow::Error),
+    /// Returned when we fail to upload the proof to the object store
+    ObjectStoreError(ObjectStoreError),
+    /// Returned when we fail to deliver the proof to the webhook
+    WebhookError(anyhow::Error),
 }
 
 impl std::fmt::Display for ProofOutputError {
@@ -25,6 +44,16 @@ impl std::error::Error for ProofOutputError {}
 #[derive(Debug)]
 pub enum ProofOutputBuildError {
     DirectoryCreation(anyhow::Error),
+    /// Returned when the path exists but is not a directory
+    NotADirectory(PathBuf),
+    /// Returned when we fail to connect to the object store
+    ObjectStore(ObjectStoreError),
+    /// Returned when the webhook url is invalid
+    InvalidWebhook(String, anyhow::Error),
+    /// Returned when we fail to build the HTTP client of the webhook
+    WebhookClient(anyhow::Error),
+    /// Returned when the configuration of the coordinator is not available
+    Config(ConfigError),
 }
 
 impl std::fmt::Display for ProofOutputBuildError {
@@ -42,101 +71,585 @@ pub enum ProofOutputMethod {
         /// The prefix to attach to the proofs, where it would be prefix_1 for
         /// block 1, prefix_n for block n, etc
         prefix: String,
+        /// The directory to store the proofs in.  If not provided, the
+        /// directory is taken from [PROOF_OUT_LOCAL_DIR_ENVKEY], otherwise
+        /// [PROOF_OUT_LOCAL_DIR_DFLT].
+        path: Option<String>,
+        /// The format of the proofs.  Default is [ProofFormat::Json].
+        #[serde(default)]
+        format: ProofFormat,
+    },
+    /// Uploads the proofs to an object store (i.e. GCS or S3)
+    ObjectStore {
+        /// The object store to upload the proofs to
+        store: ObjectStoreConfig,
+        /// The prefix to attach to the proofs, where it would be prefix_1 for
+        /// block 1, prefix_n for block n, etc.  May contain `/` to place the
+        /// proofs in a "directory".
+        prefix: String,
+        /// The format of the proofs.  Default is [ProofFormat::Json].
+        #[serde(default)]
+        format: ProofFormat,
+    },
+    /// POSTs each [GeneratedBlockProof] to the url
+    Webhook {
+        /// The url to POST the proofs to
+        url: String,
+        /// Additional headers to send along with the proofs (i.e.
+        /// `Authorization`)
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        /// The format of the proofs.  Default is [ProofFormat::Json].
+        #[serde(default)]
+        format: ProofFormat,
     },
 }
 
 unsafe impl Send for ProofOutputMethod {}
 
-#[derive(Debug, Clone)]
-pub enum ProofOutputData {
-    LocalDirectory { dirpath: PathBuf },
-    NotApplicable,
+/// A destination for the generated proofs
+#[async_trait]
+pub trait ProofOutputSink: Send + Sync {
+    /// Outputs the proof, returning where it was output to (i.e. its path or
+    /// url)
+    async fn write(&self, proof: &GeneratedBlockProof) -> Result<String, ProofOutputError>;
+
+    /// Outputs the data as `<prefix>/<key>`, where `key` may contain `/`,
+    /// returning where it was output to
+    async fn write_object(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String, ProofOutputError>;
 }
 
-#[derive(Debug)]
 pub struct ProofOutput {
     /// The configuration of the ProofOutput
     pub method: ProofOutputMethod,
-    /// The proof outpudata data dependent on the config.
-    pub data: ProofOutputData,
+    /// The sink the proofs are written to, dependent on the config.
+    pub sink: Box<dyn ProofOutputSink>,
+}
+
+impl std::fmt::Debug for ProofOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProofOutput")
+            .field("method", &self.method)
+            .finish_non_exhaustive()
+    }
 }
 
 pub const PROOF_OUT_LOCAL_DIR_ENVKEY: &str = "PROOF_OUT_LOCAL_DIRPATH";
 pub const PROOF_OUT_LOCAL_DIR_DFLT: &str = "proofs_out/";
 
 impl ProofOutput {
-    pub fn from_method(method: &ProofOutputMethod) -> Result<Self, ProofOutputBuildError> {
-        match method {
-            ProofOutputMethod::LocalDirectory { prefix: _ } => {
-                let dirpath = match std::env::var(PROOF_OUT_LOCAL_DIR_ENVKEY) {
-                    Ok(dirpath_str) => {
-                        info!("Using {} for proof output directory path", dirpath_str);
-                        PathBuf::from(dirpath_str)
-                    }
-                    Err(VarError::NotPresent) => {
-                        warn!(
-                            "No proof out directory, using default: {}",
-                            PROOF_OUT_LOCAL_DIR_DFLT
-                        );
-                        PathBuf::from(PROOF_OUT_LOCAL_DIR_DFLT)
-                    }
-                    Err(VarError::NotUnicode(os_str)) => {
-                        panic!("Non-Unicode proof out local directory: {:?}", os_str);
-                    }
-                };
+    pub async fn from_method(method: &ProofOutputMethod) -> Result<Self, ProofOutputBuildError> {
+        // The circuits are those of the configuration, as for the prover state
+        let circuit_config = crate::config::get()
+            .map_err(ProofOutputBuildError::Config)?
+            .circuit_config();
+        let encoder = |format: &ProofFormat| ProofEncoder::new(*format, &circuit_config);
 
-                match (dirpath.exists(), dirpath.is_dir()) {
-                    (true, true) => debug!("`{:?}` is pre-existing directory", dirpath),
-                    (true, false) => {
-                        panic!("ProofOutput directory is not a directory: {:?}", dirpath)
-                    }
-                    (false, _) => {
-                        info!("Creating directory: {:?}", dirpath);
-                        match create_dir_all(dirpath.clone()) {
-                            Ok(_) => debug!("Successfully created directory: {:?}", dirpath),
-                            Err(err) => {
-                                error!("Failed to create directory {:?}: {}", dirpath, err);
-                                return Err(ProofOutputBuildError::DirectoryCreation(err.into()));
-                            }
-                        }
-                    }
-                };
+        let sink: Box<dyn ProofOutputSink> = match method {
+            ProofOutputMethod::LocalDirectory {
+                prefix,
+                path,
+                format,
+            } => Box::new(LocalDirectorySink::new(
+                prefix.clone(),
+                path.as_ref().map(PathBuf::from),
+                encoder(format),
+            )?),
+            ProofOutputMethod::ObjectStore {
+                store,
+                prefix,
+                format,
+            } => Box::new(ObjectStoreSink {
+                store: ObjectStore::from_config(store)
+                    .await
+                    .map_err(ProofOutputBuildError::ObjectStore)?,
+                prefix: prefix.clone(),
+                encoder: encoder(format),
+            }),
+            ProofOutputMethod::Webhook {
+                url,
+                headers,
+                format,
+            } => Box::new(WebhookSink::new(url, headers, encoder(format))?),
+        };
 
-                Ok(Self {
-                    method: method.clone(),
-                    data: ProofOutputData::LocalDirectory { dirpath },
-                })
-            }
-        }
+        Ok(Self {
+            method: method.clone(),
+            sink,
+        })
     }
 
-    pub fn write(&self, proof: &GeneratedBlockProof) -> Result<(), ProofOutputError> {
+    /// Outputs the proof, returning where it was output to
+    pub async fn write(&self, proof: &GeneratedBlockProof) -> Result<String, ProofOutputError> {
         debug!("Attempting to output proof for block {}", proof.b_height);
-        match (&self.method, &self.data) {
-            (
-                ProofOutputMethod::LocalDirectory { prefix },
-                ProofOutputData::LocalDirectory { dirpath },
-            ) => {
-                let filepath = dirpath.join(format!("{}_{}.json", prefix, proof.b_height));
-                // Attempt to create the file
-                let file = match std::fs::File::create(filepath.clone()) {
-                    Ok(file) => file,
-                    Err(err) => {
-                        error!("Failed to create file `{:?}`: {}", filepath, err);
-                        return Err(ProofOutputError::FileCreationError(err.into()));
-                    }
-                };
-                // Attempt to write to file
-                match to_writer(file, &proof.intern) {
-                    Ok(_) => info!("Sucessfully wrote proof to {:?}", filepath),
+        self.sink.write(proof).await
+    }
+}
+
+//==================================================================================
+// Sinks
+//==================================================================================
+
+/// Encodes the proof, returning its bytes along with their file extension and
+/// content type.  JSON proofs hold the whole [GeneratedBlockProof], while
+/// binary proofs are self-describing envelopes (see
+/// [zero_bin_common::proof_format]).  Every sink outputs the same bytes for the
+/// same format.
+fn encode_proof(
+    proof: &GeneratedBlockProof,
+    encoder: &ProofEncoder,
+) -> Result<(Vec<u8>, &'static str, &'static str), ProofOutputError> {
+    let (extension, content_type) = match encoder.format {
+        ProofFormat::Json => ("json", "application/json"),
+        ProofFormat::Binary | ProofFormat::BinaryZstd => ("zkproof", "application/octet-stream"),
+    };
+    encoder
+        .encode_block_proof(proof)
+        .map(|data| (data, extension, content_type))
+        .map_err(ProofOutputError::SerializationError)
+}
+
+/// Writes each proof to `<dirpath>/<prefix>_<block_number>.<json|zkproof>`
+pub struct LocalDirectorySink {
+    prefix: String,
+    dirpath: PathBuf,
+    encoder: ProofEncoder,
+}
+
+impl LocalDirectorySink {
+    /// Returns the [LocalDirectorySink], creating the directory if needed.  If
+    /// no directory is provided, it is taken from the environment.
+    pub fn new(
+        prefix: String,
+        dirpath: Option<PathBuf>,
+        encoder: ProofEncoder,
+    ) -> Result<Self, ProofOutputBuildError> {
+        let dirpath = match dirpath {
+            Some(dirpath) => {
+                info!("Using {:?} for proof output directory path", dirpath);
+                dirpath
+            }
+            None => match std::env::var(PROOF_OUT_LOCAL_DIR_ENVKEY) {
+                Ok(dirpath_str) => {
+                    info!("Using {} for proof output directory path", dirpath_str);
+                    PathBuf::from(dirpath_str)
+                }
+                Err(VarError::NotPresent) => {
+                    warn!(
+                        "No proof out directory, using default: {}",
+                        PROOF_OUT_LOCAL_DIR_DFLT
+                    );
+                    PathBuf::from(PROOF_OUT_LOCAL_DIR_DFLT)
+                }
+                Err(VarError::NotUnicode(os_str)) => {
+                    panic!("Non-Unicode proof out local directory: {:?}", os_str);
+                }
+            },
+        };
+
+        match (dirpath.exists(), dirpath.is_dir()) {
+            (true, true) => debug!("`{:?}` is pre-existing directory", dirpath),
+            (true, false) => {
+                error!("ProofOutput directory is not a directory: {:?}", dirpath);
+                return Err(ProofOutputBuildError::NotADirectory(dirpath));
+            }
+            (false, _) => {
+                info!("Creating directory: {:?}", dirpath);
+                match create_dir_all(dirpath.clone()) {
+                    Ok(_) => debug!("Successfully created directory: {:?}", dirpath),
                     Err(err) => {
-                        error!("Failed to write to file `{:?}`: {}", filepath, err);
-                        return Err(ProofOutputError::FileWritingError(err.into()));
+                        error!("Failed to create directory {:?}: {}", dirpath, err);
+                        return Err(ProofOutputBuildError::DirectoryCreation(err.into()));
                     }
                 }
-                Ok(())
             }
-            (_, _) => Err(ProofOutputError::MethodDataMismatch),
+        };
+
+        Ok(Self {
+            prefix,
+            dirpath,
+            encoder,
+        })
+    }
+}
+
+#[async_trait]
+impl ProofOutputSink for LocalDirectorySink {
+    async fn write(&self, proof: &GeneratedBlockProof) -> Result<String, ProofOutputError> {
+        let (data, extension, _) = encode_proof(proof, &self.encoder)?;
+        let filepath = self
+            .dirpath
+            .join(format!("{}_{}.{}", self.prefix, proof.b_height, extension));
+        // Attempt to create the file
+        let mut file = match std::fs::File::create(filepath.clone()) {
+            Ok(file) => file,
+            Err(err) => {
+                error!("Failed to create file `{:?}`: {}", filepath, err);
+                return Err(ProofOutputError::FileCreationError(err.into()));
+            }
+        };
+        // Attempt to write to file
+        match file.write_all(&data) {
+            Ok(_) => info!("Sucessfully wrote proof to {:?}", filepath),
+            Err(err) => {
+                error!("Failed to write to file `{:?}`: {}", filepath, err);
+                return Err(ProofOutputError::FileWritingError(err.into()));
+            }
         }
+        Ok(filepath.display().to_string())
+    }
+
+    async fn write_object(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<String, ProofOutputError> {
+        let filepath = self.dirpath.join(&self.prefix).join(key);
+        if let Some(parent) = filepath.parent() {
+            if let Err(err) = create_dir_all(parent) {
+                error!("Failed to create directory {:?}: {}", parent, err);
+                return Err(ProofOutputError::FileCreationError(err.into()));
+            }
+        }
+        match std::fs::write(&filepath, data) {
+            Ok(_) => info!("Sucessfully wrote {:?}", filepath),
+            Err(err) => {
+                error!("Failed to write to file `{:?}`: {}", filepath, err);
+                return Err(ProofOutputError::FileWritingError(err.into()));
+            }
+        }
+        Ok(filepath.display().to_string())
+    }
+}
+
+/// Uploads each proof as `<prefix>_<block_number>.<json|zkproof>` to an
+/// [ObjectStore]
+pub struct ObjectStoreSink {
+    store: ObjectStore,
+    prefix: String,
+    encoder: ProofEncoder,
+}
+
+#[async_trait]
+impl ProofOutputSink for ObjectStoreSink {
+    async fn write(&self, proof: &GeneratedBlockProof) -> Result<String, ProofOutputError> {
+        let (data, extension, content_type) = encode_proof(proof, &self.encoder)?;
+        let object = format!("{}_{}.{}", self.prefix, proof.b_height, extension);
+        self.store
+            .put(&object, data, content_type)
+            .await
+            .map_err(ProofOutputError::ObjectStoreError)?;
+        info!("Sucessfully uploaded proof to {}", object);
+        Ok(self.store.location(&object))
+    }
+
+    async fn write_object(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String, ProofOutputError> {
+        let object = format!("{}/{}", self.prefix, key);
+        self.store
+            .put(&object, data, content_type)
+            .await
+            .map_err(ProofOutputError::ObjectStoreError)?;
+        info!("Sucessfully uploaded {}", object);
+        Ok(self.store.location(&object))
+    }
+}
+
+/// The header holding the key of the objects POSTed by the [WebhookSink]
+pub const OBJECT_KEY_HEADER: &str = "X-Object-Key";
+/// The timeout of each request of the [WebhookSink], long enough to transfer
+/// large proofs
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// POSTs each [GeneratedBlockProof] to a url, either as JSON or as a binary
+/// envelope
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: reqwest::Url,
+    headers: reqwest::header::HeaderMap,
+    encoder: ProofEncoder,
+}
+
+impl WebhookSink {
+    pub fn new(
+        url: &str,
+        headers: &HashMap<String, String>,
+        encoder: ProofEncoder,
+    ) -> Result<Self, ProofOutputBuildError> {
+        let invalid =
+            |err: anyhow::Error| ProofOutputBuildError::InvalidWebhook(url.to_string(), err);
+
+        let headers = headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|err| invalid(err.into()))?,
+                    reqwest::header::HeaderValue::from_str(value)
+                        .map_err(|err| invalid(err.into()))?,
+                ))
+            })
+            .collect::<Result<_, ProofOutputBuildError>>()?;
+
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|err| ProofOutputBuildError::WebhookClient(err.into()))?;
+
+        Ok(Self {
+            client,
+            url: reqwest::Url::parse(url).map_err(|err| invalid(err.into()))?,
+            headers,
+            encoder,
+        })
+    }
+
+    /// Sends the request, returning the url it was sent to
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<String, ProofOutputError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|err| ProofOutputError::WebhookError(err.into()))?;
+
+        match response.error_for_status() {
+            Ok(_) => {
+                info!("Sucessfully posted to {}", self.url);
+                Ok(self.url.to_string())
+            }
+            Err(err) => {
+                error!("Failed to post to {}: {}", self.url, err);
+                Err(ProofOutputError::WebhookError(err.into()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ProofOutputSink for WebhookSink {
+    async fn write(&self, proof: &GeneratedBlockProof) -> Result<String, ProofOutputError> {
+        let (data, _, content_type) = encode_proof(proof, &self.encoder)?;
+        let request = self
+            .client
+            .post(self.url.clone())
+            .headers(self.headers.clone())
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(data);
+        self.send(request).await
+    }
+
+    /// POSTs the data, along with its key in the [OBJECT_KEY_HEADER] header
+    async fn write_object(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String, ProofOutputError> {
+        let request = self
+            .client
+            .post(self.url.clone())
+            .headers(self.headers.clone())
+            .header(OBJECT_KEY_HEADER, key)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(data);
+        self.send(request).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use plonky2::{
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::{CircuitConfig, CommonCircuitData},
+        },
+    };
+    use proof_gen::types::{Config, Field, EXTENSION_DEGREE};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::mpsc::{self, UnboundedReceiver},
+    };
+    use zero_bin_common::proof_format::decode_block_proofs;
+
+    use super::*;
+
+    /// Returns the proof of a trivial circuit standing in for a block proof,
+    /// along with the circuit data it is read back with
+    fn block_proof(
+        b_height: u64,
+    ) -> (
+        GeneratedBlockProof,
+        CommonCircuitData<Field, EXTENSION_DEGREE>,
+    ) {
+        let mut builder = CircuitBuilder::<Field, EXTENSION_DEGREE>::new(
+            CircuitConfig::standard_recursion_config(),
+        );
+        let one = builder.one();
+        builder.register_public_input(one);
+        let circuit = builder.build::<Config>();
+        let intern = circuit.prove(PartialWitness::new()).unwrap();
+        (GeneratedBlockProof { b_height, intern }, circuit.common)
+    }
+
+    fn proof_out_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "coordinator-proofout-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn binary_encoder() -> ProofEncoder {
+        ProofEncoder {
+            format: ProofFormat::Binary,
+            circuit_config_digest: [0; 32],
+        }
+    }
+
+    #[tokio::test]
+    async fn local_directory_round_trips_the_proofs() {
+        let dirpath = proof_out_dir("local");
+        let (proof, common) = block_proof(7);
+
+        let json = LocalDirectorySink::new(
+            String::from("proof"),
+            Some(dirpath.clone()),
+            ProofEncoder::default(),
+        )
+        .unwrap();
+        let binary = LocalDirectorySink::new(
+            String::from("proof"),
+            Some(dirpath.clone()),
+            binary_encoder(),
+        )
+        .unwrap();
+        let json_path = json.write(&proof).await.unwrap();
+        let binary_path = binary.write(&proof).await.unwrap();
+        let json_bytes = std::fs::read(&json_path).unwrap();
+        let binary_bytes = std::fs::read(&binary_path).unwrap();
+        std::fs::remove_dir_all(&dirpath).unwrap();
+
+        assert_eq!(
+            json_path,
+            dirpath.join("proof_7.json").display().to_string()
+        );
+        assert_eq!(
+            binary_path,
+            dirpath.join("proof_7.zkproof").display().to_string()
+        );
+        // The JSON proof is the whole block proof, as posted by the webhook
+        let read: GeneratedBlockProof = serde_json::from_slice(&json_bytes).unwrap();
+        assert_eq!(read.b_height, 7);
+        assert_eq!(read.intern, proof.intern);
+        for bytes in [json_bytes, binary_bytes] {
+            let decoded = decode_block_proofs(&bytes, &common, None).unwrap();
+            assert_eq!(decoded.len(), 1);
+            assert_eq!(decoded[0].b_height, 7);
+            assert_eq!(decoded[0].intern, proof.intern);
+        }
+    }
+
+    /// Reads a request, returning its lowercased headers and its body
+    async fn read_request(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut request = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let n = stream.read(&mut chunk).await.unwrap();
+            request.extend_from_slice(&chunk[..n]);
+            if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |length| length.trim().parse::<usize>().unwrap());
+                let body = end + 4..end + 4 + length;
+                if request.len() >= body.end {
+                    return (headers, request[body].to_vec());
+                }
+            }
+            assert!(n > 0, "the connection was closed mid-request");
+        }
+    }
+
+    /// Serves a webhook on a local port answering with `status`, returning its
+    /// url and the headers and bodies of the requests it received
+    async fn serve(status: &'static str) -> (String, UnboundedReceiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/proofs", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut stream).await;
+                let _ = sender.send(request);
+                // Closing the connection keeps one request per connection
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_the_proofs() {
+        let (url, mut requests) = serve("200 OK").await;
+        let headers =
+            HashMap::from([(String::from("Authorization"), String::from("Bearer token"))]);
+        let (proof, common) = block_proof(7);
+
+        let json = WebhookSink::new(&url, &headers, ProofEncoder::default()).unwrap();
+        assert_eq!(json.write(&proof).await.unwrap(), url);
+        let (request_headers, body) = requests.recv().await.unwrap();
+        assert!(request_headers.starts_with("post /proofs "));
+        assert!(request_headers.contains("authorization: bearer token"));
+        assert!(request_headers.contains("content-type: application/json"));
+        // The same payload as written by the other sinks
+        assert_eq!(
+            body,
+            encode_proof(&proof, &ProofEncoder::default()).unwrap().0
+        );
+
+        let binary = WebhookSink::new(&url, &headers, binary_encoder()).unwrap();
+        binary.write(&proof).await.unwrap();
+        let (request_headers, body) = requests.recv().await.unwrap();
+        assert!(request_headers.contains("content-type: application/octet-stream"));
+        let decoded = decode_block_proofs(&body, &common, None).unwrap();
+        assert_eq!(decoded[0].intern, proof.intern);
+
+        json.write_object("1/7.json", b"{}".to_vec(), "application/json")
+            .await
+            .unwrap();
+        let (request_headers, body) = requests.recv().await.unwrap();
+        assert!(request_headers.contains("x-object-key: 1/7.json"));
+        assert_eq!(body, b"{}");
+    }
+
+    #[tokio::test]
+    async fn webhook_reports_failed_deliveries() {
+        let (url, _requests) = serve("500 Internal Server Error").await;
+        let sink = WebhookSink::new(&url, &HashMap::new(), ProofEncoder::default()).unwrap();
+
+        assert!(matches!(
+            sink.write(&block_proof(7).0).await,
+            Err(ProofOutputError::WebhookError(_))
+        ));
+        assert!(matches!(
+            WebhookSink::new("not a url", &HashMap::new(), ProofEncoder::default()),
+            Err(ProofOutputBuildError::InvalidWebhook(..))
+        ));
     }
 }
@@ -0,0 +1,172 @@
+//! This module contains the [Scheduler], which decides which queued job runs
+//! next and how many jobs may run at the same time.
+//!
+//! Jobs with a higher priority are started first, jobs with equal priority are
+//! started in the order they were received.
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+use zero_bin_common::metrics::QUEUE_DEPTH;
+
+use crate::jobs::JobId;
+
+/// The default maximum number of jobs proven concurrently
+pub const DFLT_MAX_CONCURRENT_JOBS: usize = 4;
+/// The priority of a job if none was provided in the request
+pub const DFLT_JOB_PRIORITY: i32 = 0;
+
+/// A job waiting in the [Scheduler]'s queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QueuedJob {
+    id: JobId,
+    priority: i32,
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher priority first, then the oldest (lowest id) job first
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The [Scheduler] holds the queue of jobs waiting to be proven and limits
+/// the number of jobs running concurrently against the shared runtime.
+#[derive(Debug)]
+pub struct Scheduler {
+    /// The jobs waiting to be started
+    queue: Mutex<BinaryHeap<QueuedJob>>,
+    /// Notified whenever a job is queued
+    queued: Notify,
+    /// The permits of the running jobs
+    permits: Arc<Semaphore>,
+    /// The maximum number of jobs running concurrently
+    max_concurrency: usize,
+}
+
+impl Scheduler {
+    /// Returns a [Scheduler] running at most `max_concurrency` jobs at a time.
+    pub fn new(max_concurrency: usize) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+        Self {
+            queue: Mutex::new(BinaryHeap::new()),
+            queued: Notify::new(),
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+            max_concurrency,
+        }
+    }
+
+    /// Returns the maximum number of jobs running concurrently
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Returns the number of jobs waiting to be started
+    pub fn queue_len(&self) -> usize {
+        self.queue.lock().expect("Scheduler lock poisoned").len()
+    }
+
+    /// Adds the job to the queue
+    pub fn enqueue(&self, id: JobId, priority: i32) {
+        debug!("Queueing job {} with priority {}", id, priority);
+        let mut queue = self.queue.lock().expect("Scheduler lock poisoned");
+        queue.push(QueuedJob { id, priority });
+        QUEUE_DEPTH.set(queue.len() as i64);
+        drop(queue);
+        self.queued.notify_one();
+    }
+
+    /// Waits until a job may be started and returns it along with its permit.
+    /// The permit must be held until the job has finished.
+    pub async fn next(&self) -> (JobId, OwnedSemaphorePermit) {
+        // Wait for a free slot first, so that the job picked is the highest
+        // priority one at the time it actually starts.
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Scheduler semaphore is never closed");
+
+        loop {
+            let next = {
+                let mut queue = self.queue.lock().expect("Scheduler lock poisoned");
+                let next = queue.pop();
+                QUEUE_DEPTH.set(queue.len() as i64);
+                next
+            };
+            match next {
+                Some(job) => return (job.id, permit),
+                None => self.queued.notified().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn starts_the_highest_priority_then_oldest_job_first() {
+        let scheduler = Scheduler::new(4);
+        scheduler.enqueue(1, 0);
+        scheduler.enqueue(2, 5);
+        scheduler.enqueue(3, -1);
+        scheduler.enqueue(4, 5);
+        scheduler.enqueue(5, 0);
+
+        let mut started = Vec::new();
+        let mut permits = Vec::new();
+        for _ in 0..4 {
+            let (id, permit) = scheduler.next().await;
+            started.push(id);
+            permits.push(permit);
+        }
+        assert_eq!(started, vec![2, 4, 1, 5]);
+        assert_eq!(scheduler.queue_len(), 1);
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_running_job_to_finish() {
+        let scheduler = Scheduler::new(1);
+        scheduler.enqueue(1, 0);
+        scheduler.enqueue(2, 0);
+
+        let (id, permit) = scheduler.next().await;
+        assert_eq!(id, 1);
+        let next = tokio::time::timeout(Duration::from_millis(50), scheduler.next()).await;
+        assert!(next.is_err(), "a second job started while the first runs");
+
+        drop(permit);
+        let (id, _permit) = scheduler.next().await;
+        assert_eq!(id, 2);
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_job_to_be_queued() {
+        let scheduler = Arc::new(Scheduler::new(1));
+        let next = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.next().await.0 }
+        });
+        tokio::task::yield_now().await;
+
+        scheduler.enqueue(7, 0);
+        assert_eq!(next.await.unwrap(), 7);
+    }
+}
@@ -1,9 +1,11 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use alloy::transports::http::reqwest::Url;
 use clap::{Parser, Subcommand, ValueHint};
-use rpc::RpcType;
-use zero_bin_common::prover_state::cli::CliProverStateConfig;
+use rpc::{cache::CacheArgs, throttle::ThrottleArgs, RpcType};
+use zero_bin_common::{proof_format::ProofFormat, prover_state::cli::CliProverStateConfig};
+
+use crate::http::DFLT_MAX_BODY_BYTES;
 
 /// zero-bin leader config
 #[derive(Parser)]
@@ -18,6 +20,21 @@ pub(crate) struct Cli {
     // mode.
     #[clap(flatten)]
     pub(crate) prover_state_config: CliProverStateConfig,
+
+    /// The format of the proofs written to disk.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        env = "ZERO_BIN_PROOF_FORMAT",
+        default_value_t = ProofFormat::Json
+    )]
+    pub(crate) proof_format: ProofFormat,
+
+    /// If provided, the address on which the Prometheus metrics are served at
+    /// `/metrics`.
+    #[arg(long, global = true, env = "ZERO_BIN_METRICS_ADDR")]
+    pub(crate) metrics_addr: Option<SocketAddr>,
 }
 
 #[derive(Subcommand)]
@@ -33,7 +50,8 @@ pub(crate) enum Command {
     },
     /// Reads input from a node rpc and writes output to stdout.
     Rpc {
-        // The node RPC URL.
+        // The node RPC URL: `http(s)://`, `ws(s)://` or `ipc://` followed by
+        // the path of the socket.
         #[arg(long, short = 'u', value_hint = ValueHint::Url)]
         rpc_url: Url,
         // The node RPC type (jerigon / native).
@@ -68,12 +86,17 @@ pub(crate) enum Command {
             default_value_t = false
         )]
         keep_intermediate_proofs: bool,
-        /// Backoff in milliseconds for request retries
+        /// Backoff in milliseconds before the first retry of a failed request,
+        /// doubled on every retry
         #[arg(long, default_value_t = 0)]
         backoff: u64,
-        /// The maximum number of retries
+        /// The maximum number of retries of a failed request
         #[arg(long, default_value_t = 0)]
         max_retries: u32,
+        #[command(flatten)]
+        cache: CacheArgs,
+        #[command(flatten)]
+        throttle: ThrottleArgs,
     },
     /// Reads input from HTTP and writes output to a directory.
     Http {
@@ -86,5 +109,18 @@ pub(crate) enum Command {
         /// If true, save the public inputs to disk on error.
         #[arg(short, long, default_value_t = false)]
         save_inputs_on_error: bool,
+        /// If provided, the requests must hold this bearer token in their
+        /// `Authorization` header.
+        #[arg(long, env = "ZERO_BIN_AUTH_TOKEN", hide_env_values = true)]
+        auth_token: Option<String>,
+        /// If provided, the requests must hold the HMAC-SHA256 signature of
+        /// their method, path, timestamp and body with this secret in their
+        /// `X-Signature-256` header, and when they were signed in their
+        /// `X-Signature-Timestamp` header.
+        #[arg(long, env = "ZERO_BIN_HMAC_SECRET", hide_env_values = true)]
+        hmac_secret: Option<String>,
+        /// The maximum size of a request body, in bytes.
+        #[arg(long, default_value_t = DFLT_MAX_BODY_BYTES)]
+        max_body_bytes: usize,
     },
 }
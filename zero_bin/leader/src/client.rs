@@ -5,10 +5,16 @@ use alloy::transports::http::reqwest::Url;
 use anyhow::Result;
 use paladin::runtime::Runtime;
 use proof_gen::proof_types::GeneratedBlockProof;
-use rpc::{retry::build_http_retry_provider, RpcType};
+use rpc::{
+    cache::{CacheConfig, RpcCache},
+    provider::build_retry_provider,
+    throttle::{Throttle, ThrottleConfig},
+    RpcType,
+};
 use tracing::{error, info, warn};
 use zero_bin_common::block_interval::BlockInterval;
 use zero_bin_common::fs::generate_block_proof_file_name;
+use zero_bin_common::proof_format::ProofEncoder;
 
 #[derive(Debug)]
 pub struct RpcParams {
@@ -16,6 +22,8 @@ pub struct RpcParams {
     pub rpc_type: RpcType,
     pub backoff: u64,
     pub max_retries: u32,
+    pub cache: Option<CacheConfig>,
+    pub throttle: ThrottleConfig,
 }
 
 #[derive(Debug, Default)]
@@ -25,6 +33,7 @@ pub struct ProofParams {
     pub proof_output_dir: Option<PathBuf>,
     pub save_inputs_on_error: bool,
     pub keep_intermediate_proofs: bool,
+    pub proof_encoder: ProofEncoder,
 }
 
 /// The main function for the client.
@@ -34,12 +43,16 @@ pub(crate) async fn client_main(
     block_interval: BlockInterval,
     mut params: ProofParams,
 ) -> Result<()> {
+    let cache = rpc_params.cache.map(RpcCache::open).transpose()?;
     let prover_input = rpc::prover_input(
-        &build_http_retry_provider(
+        &build_retry_provider(
             rpc_params.rpc_url,
             rpc_params.backoff,
             rpc_params.max_retries,
-        ),
+            cache,
+            Throttle::new(rpc_params.throttle),
+        )
+        .await?,
         block_interval,
         params.checkpoint_block_number.into(),
         rpc_params.rpc_type,
@@ -55,6 +68,7 @@ pub(crate) async fn client_main(
             params.previous_proof.take(),
             params.save_inputs_on_error,
             params.proof_output_dir.clone(),
+            params.proof_encoder,
         )
         .await;
     runtime.close().await?;
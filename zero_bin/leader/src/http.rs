@@ -1,14 +1,29 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{io::Write, net::SocketAddr, path::PathBuf, sync::Arc};
 
 use alloy::primitives::U256;
 use anyhow::{bail, Result};
-use axum::{http::StatusCode, routing::post, Json, Router};
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
 use paladin::runtime::Runtime;
 use proof_gen::proof_types::GeneratedBlockProof;
 use prover::BlockProverInput;
 use serde::{Deserialize, Serialize};
-use serde_json::to_writer;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+use zero_bin_common::{
+    auth::{AuthConfig, AuthRequest, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+    metrics,
+    proof_format::{ProofEncoder, ProofFormat},
+};
+
+/// The default maximum size of a request body, in bytes.
+pub(crate) const DFLT_MAX_BODY_BYTES: usize = 512 * 1024 * 1024;
 
 /// The main function for the HTTP mode.
 pub(crate) async fn http_main(
@@ -16,22 +31,90 @@ pub(crate) async fn http_main(
     port: u16,
     output_dir: PathBuf,
     save_inputs_on_error: bool,
+    proof_encoder: ProofEncoder,
+    auth: AuthConfig,
+    max_body_bytes: usize,
 ) -> Result<()> {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     debug!("listening on {}", addr);
+    if !auth.is_enabled() {
+        warn!("No authentication configured, accepting requests from anyone");
+    }
 
     let runtime = Arc::new(runtime);
-    let app = Router::new().route(
-        "/prove",
-        post({
-            let runtime = runtime.clone();
-            move |body| prove(body, runtime, output_dir.clone(), save_inputs_on_error)
-        }),
-    );
+    let app = Router::new()
+        .route(
+            "/prove",
+            post({
+                let runtime = runtime.clone();
+                move |body| {
+                    prove(
+                        body,
+                        runtime,
+                        output_dir.clone(),
+                        save_inputs_on_error,
+                        proof_encoder,
+                    )
+                }
+            }),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new((auth, max_body_bytes)),
+            authenticate,
+        ))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .route("/metrics", get(metrics::metrics_handler));
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     Ok(axum::serve(listener, app).await?)
 }
 
+/// Rejects the requests which are not authenticated (see
+/// [zero_bin_common::auth]) with `401 Unauthorized`, and the requests whose
+/// body is larger than the limit with `413 Payload Too Large`.
+async fn authenticate(
+    State(state): State<Arc<(AuthConfig, usize)>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (auth, max_body_bytes) = state.as_ref();
+    if !auth.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, *max_body_bytes).await {
+        Ok(body) => body,
+        Err(err) => {
+            debug!("Rejected request body: {}", err);
+            return (StatusCode::PAYLOAD_TOO_LARGE, "request body too large").into_response();
+        }
+    };
+    let header = |name| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let path = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), |path| path.as_str());
+    if let Err(err) = auth.verify(&AuthRequest {
+        method: parts.method.as_str(),
+        path,
+        authorization: header(AUTHORIZATION.as_str()),
+        signature: header(SIGNATURE_HEADER),
+        timestamp: header(TIMESTAMP_HEADER),
+        body: &body,
+    }) {
+        warn!("Rejected unauthenticated request: {}", err);
+        metrics::record_failure("auth");
+        return (StatusCode::UNAUTHORIZED, err.to_string()).into_response();
+    }
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
 /// Writes the generated block proof to a file.
 ///
 /// Returns the fully qualified file name.
@@ -39,14 +122,19 @@ fn write_to_file(
     output_dir: PathBuf,
     block_number: U256,
     generated_block_proof: &GeneratedBlockProof,
+    proof_encoder: &ProofEncoder,
 ) -> Result<PathBuf> {
-    let file_name = format!("proof-{}.json", block_number);
+    let extension = match proof_encoder.format {
+        ProofFormat::Json => "json",
+        ProofFormat::Binary | ProofFormat::BinaryZstd => "zkproof",
+    };
+    let file_name = format!("proof-{}.{}", block_number, extension);
     let fully_qualified_file_name = output_dir.join(file_name);
     let file = std::fs::File::create(fully_qualified_file_name.clone());
 
     match file {
-        Ok(file) => {
-            to_writer(file, &generated_block_proof)?;
+        Ok(mut file) => {
+            file.write_all(&proof_encoder.encode_block_proof(generated_block_proof)?)?;
             Ok(fully_qualified_file_name)
         }
         Err(e) => {
@@ -66,6 +154,7 @@ async fn prove(
     runtime: Arc<Runtime>,
     output_dir: PathBuf,
     save_inputs_on_error: bool,
+    proof_encoder: ProofEncoder,
 ) -> StatusCode {
     debug!("Received payload: {:#?}", payload);
 
@@ -80,18 +169,20 @@ async fn prove(
         )
         .await
     {
-        Ok(b_proof) => match write_to_file(output_dir, block_number, &b_proof) {
+        Ok(b_proof) => match write_to_file(output_dir, block_number, &b_proof, &proof_encoder) {
             Ok(file) => {
                 info!("Successfully wrote proof to {}", file.display());
                 StatusCode::OK
             }
             Err(e) => {
                 error!("{e}");
+                metrics::record_failure("proof_output");
                 StatusCode::INTERNAL_SERVER_ERROR
             }
         },
         Err(e) => {
             error!("Error while proving block {block_number}: {e:#?}");
+            metrics::record_failure("proof");
             StatusCode::INTERNAL_SERVER_ERROR
         }
     }
@@ -1,7 +1,7 @@
+use std::path::PathBuf;
 use std::{env, io};
-use std::{fs::File, path::PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use clap::Parser;
 use cli::Command;
 use client::RpcParams;
@@ -10,7 +10,13 @@ use ops::register;
 use paladin::runtime::Runtime;
 use proof_gen::proof_types::GeneratedBlockProof;
 use tracing::{info, warn};
-use zero_bin_common::block_interval::BlockInterval;
+use zero_bin_common::{
+    auth::AuthConfig,
+    block_interval::BlockInterval,
+    metrics,
+    proof_format::{decode_block_proofs, is_binary, ProofEncoder},
+    prover_state::ProverStateManager,
+};
 
 use crate::client::{client_main, ProofParams};
 use crate::utils::get_package_version;
@@ -22,15 +28,31 @@ mod init;
 mod stdio;
 mod utils;
 
-fn get_previous_proof(path: Option<PathBuf>) -> Result<Option<GeneratedBlockProof>> {
+fn get_previous_proof(
+    path: Option<PathBuf>,
+    prover_state_manager: &ProverStateManager,
+) -> Result<Option<GeneratedBlockProof>> {
     if path.is_none() {
         return Ok(None);
     }
 
     let path = path.unwrap();
-    let file = File::open(path)?;
-    let des = &mut serde_json::Deserializer::from_reader(&file);
-    let proof: GeneratedBlockProof = serde_path_to_error::deserialize(des)?;
+    let bytes = std::fs::read(path)?;
+    if !is_binary(&bytes) {
+        let des = &mut serde_json::Deserializer::from_slice(&bytes);
+        let proof: GeneratedBlockProof = serde_path_to_error::deserialize(des)?;
+        return Ok(Some(proof));
+    }
+
+    // Binary proofs can only be deserialized against the block circuit
+    let verifier = prover_state_manager.verifier()?;
+    let proof = decode_block_proofs(
+        &bytes,
+        &verifier.state.common,
+        Some(&prover_state_manager.circuit_config),
+    )?
+    .pop()
+    .context("previous proof file contains no proof")?;
     Ok(Some(proof))
 }
 
@@ -59,13 +81,21 @@ async fn main() -> Result<()> {
     }
 
     let args = cli::Cli::parse();
+    metrics::init();
+    if let Some(metrics_addr) = args.metrics_addr {
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(metrics_addr).await {
+                warn!("Failed to serve the metrics: {err:#}");
+            }
+        });
+    }
+    let prover_state_manager = args.prover_state_config.into_prover_state_manager();
     if let paladin::config::Runtime::InMemory = args.paladin.runtime {
         // If running in emulation mode, we'll need to initialize the prover
         // state here.
-        args.prover_state_config
-            .into_prover_state_manager()
-            .initialize()?;
+        prover_state_manager.initialize()?;
     }
+    let proof_encoder = ProofEncoder::new(args.proof_format, &prover_state_manager.circuit_config);
 
     let runtime = Runtime::from_config(&args.paladin, register()).await?;
 
@@ -74,13 +104,16 @@ async fn main() -> Result<()> {
             previous_proof,
             save_inputs_on_error,
         } => {
-            let previous_proof = get_previous_proof(previous_proof)?;
-            stdio::stdio_main(runtime, previous_proof, save_inputs_on_error).await?;
+            let previous_proof = get_previous_proof(previous_proof, &prover_state_manager)?;
+            stdio::stdio_main(runtime, previous_proof, save_inputs_on_error, proof_encoder).await?;
         }
         Command::Http {
             port,
             output_dir,
             save_inputs_on_error,
+            auth_token,
+            hmac_secret,
+            max_body_bytes,
         } => {
             // check if output_dir exists, is a directory, and is writable
             let output_dir_metadata = std::fs::metadata(&output_dir);
@@ -91,7 +124,16 @@ async fn main() -> Result<()> {
                 panic!("output-dir is not a writable directory");
             }
 
-            http::http_main(runtime, port, output_dir, save_inputs_on_error).await?;
+            http::http_main(
+                runtime,
+                port,
+                output_dir,
+                save_inputs_on_error,
+                proof_encoder,
+                AuthConfig::new(auth_token.as_deref(), hmac_secret.as_deref()),
+                max_body_bytes,
+            )
+            .await?;
         }
         Command::Rpc {
             rpc_url,
@@ -105,8 +147,10 @@ async fn main() -> Result<()> {
             keep_intermediate_proofs,
             backoff,
             max_retries,
+            cache,
+            throttle,
         } => {
-            let previous_proof = get_previous_proof(previous_proof)?;
+            let previous_proof = get_previous_proof(previous_proof, &prover_state_manager)?;
             let mut block_interval = BlockInterval::new(&block_interval)?;
 
             if let BlockInterval::FollowFrom {
@@ -125,6 +169,8 @@ async fn main() -> Result<()> {
                     rpc_type,
                     backoff,
                     max_retries,
+                    cache: cache.config(),
+                    throttle: throttle.config(),
                 },
                 block_interval,
                 ProofParams {
@@ -133,6 +179,7 @@ async fn main() -> Result<()> {
                     proof_output_dir,
                     save_inputs_on_error,
                     keep_intermediate_proofs,
+                    proof_encoder,
                 },
             )
             .await?;
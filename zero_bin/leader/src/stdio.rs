@@ -5,12 +5,14 @@ use paladin::runtime::Runtime;
 use proof_gen::proof_types::GeneratedBlockProof;
 use prover::ProverInput;
 use tracing::info;
+use zero_bin_common::proof_format::{ProofEncoder, ProofFormat};
 
 /// The main function for the stdio mode.
 pub(crate) async fn stdio_main(
     runtime: Runtime,
     previous: Option<GeneratedBlockProof>,
     save_inputs_on_error: bool,
+    proof_encoder: ProofEncoder,
 ) -> Result<()> {
     let mut buffer = String::new();
     std::io::stdin().read_to_string(&mut buffer)?;
@@ -21,7 +23,13 @@ pub(crate) async fn stdio_main(
     };
 
     let proved_blocks = prover_input
-        .prove(&runtime, previous, save_inputs_on_error, None)
+        .prove(
+            &runtime,
+            previous,
+            save_inputs_on_error,
+            None,
+            proof_encoder,
+        )
         .await;
     runtime.close().await?;
     let proved_blocks = proved_blocks?;
@@ -36,7 +44,16 @@ pub(crate) async fn stdio_main(
         .into_iter()
         .filter_map(|(_, proof)| proof)
         .collect();
-    std::io::stdout().write_all(&serde_json::to_vec(&proofs)?)?;
+    // JSON proofs are written as a list, binary ones as consecutive envelopes
+    let bytes = match proof_encoder.format {
+        ProofFormat::Json => serde_json::to_vec(&proofs)?,
+        ProofFormat::Binary | ProofFormat::BinaryZstd => proofs
+            .iter()
+            .map(|proof| proof_encoder.encode_block_proof(proof))
+            .collect::<Result<Vec<_>>>()?
+            .concat(),
+    };
+    std::io::stdout().write_all(&bytes)?;
 
     Ok(())
 }
@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use evm_arithmetization::{proof::PublicValues, GenerationInputs};
 use keccak_hash::keccak;
@@ -12,7 +12,12 @@ use proof_gen::{
 };
 use serde::{Deserialize, Serialize};
 use tracing::{error, event, info_span, Level};
-use zero_bin_common::{debug_utils::save_inputs_to_disk, prover_state::p_state};
+use zero_bin_common::{
+    debug_utils::save_inputs_to_disk,
+    failure::{tag_txn_index, FailureClass},
+    metrics::{record_failure, AGG_PROOF_DURATION, TXN_PROOF_DURATION},
+    prover_state::{circuit::NUM_TABLES, p_state, TraceSizes},
+};
 
 registry!();
 
@@ -28,32 +33,131 @@ impl Operation for TxProof {
 
     fn execute(&self, input: Self::Input) -> Result<Self::Output> {
         let _span = TxProofSpan::new(&input);
-        let proof = if self.save_inputs_on_error {
-            zero_bin_common::prover_state::p_manager()
-                .generate_txn_proof(input.clone())
-                .map_err(|err| {
-                    if let Err(write_err) = save_inputs_to_disk(
-                        format!(
-                            "b{}_txn_{}_input.log",
-                            input.block_metadata.block_number, input.txn_number_before
-                        ),
-                        input,
-                    ) {
-                        error!("Failed to save txn proof input to disk: {:?}", write_err);
-                    }
-
-                    FatalError::from_anyhow(err, FatalStrategy::Terminate)
-                })?
-        } else {
-            zero_bin_common::prover_state::p_manager()
-                .generate_txn_proof(input)
-                .map_err(|err| FatalError::from_anyhow(err, FatalStrategy::Terminate))?
-        };
+        let (proof, _) = generate_txn_proof(input, self.save_inputs_on_error)?;
 
         Ok(proof.into())
     }
 }
 
+/// Generates the proof of a transaction along with the [TraceSizes] of its
+/// STARK tables, saving the inputs to disk on failure if requested.
+///
+/// Failures are tagged with their [FailureClass], and terminate the directive
+/// so that the block as a whole is retried or skipped by the coordinator.
+#[cfg(not(feature = "test_only"))]
+fn generate_txn_proof(
+    input: GenerationInputs,
+    save_inputs_on_error: bool,
+) -> Result<(proof_gen::proof_types::GeneratedTxnProof, TraceSizes)> {
+    let txn_index = input.txn_number_before.low_u64() as usize;
+    // The inputs are only kept around when they are saved on failure
+    let saved_input = save_inputs_on_error.then(|| input.clone());
+
+    zero_bin_common::prover_state::p_manager()
+        .generate_txn_proof_with_trace_sizes(input)
+        .map_err(|err| {
+            // The failure to generate the witness is tagged where it is raised,
+            // anything else failed to prove the transaction
+            let err = match FailureClass::of(&err) {
+                FailureClass::WitnessGeneration => err,
+                _ => FailureClass::Proving.tag(err),
+            };
+
+            if let Some(input) = saved_input {
+                if let Err(write_err) = save_inputs_to_disk(
+                    format!(
+                        "b{}_txn_{}_input.log",
+                        input.block_metadata.block_number, input.txn_number_before
+                    ),
+                    input,
+                ) {
+                    error!("Failed to save txn proof input to disk: {:?}", write_err);
+                }
+            }
+
+            record_failure("txn_proof");
+            FatalError::from_anyhow(tag_txn_index(err, txn_index), FatalStrategy::Terminate)
+        })
+}
+
+/// The statistics gathered while proving a single transaction.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TxProofStats {
+    /// The number of the block holding the transaction
+    pub block_number: u64,
+    /// The index of the transaction within its block
+    pub txn_number: u64,
+    /// The hex-encoded hash of the transaction, or "Dummy" for padding
+    /// transactions
+    pub txn_hash: String,
+    /// The gas used by the transaction in the original chain
+    pub gas_used: u64,
+    /// The time taken by the worker to prove the transaction
+    pub proof_duration: Duration,
+    /// The number of CPU cycles run by the transaction, i.e. the length of the
+    /// CPU trace before it is padded to a power of two
+    pub cpu_cycles: usize,
+    /// The degree bits (i.e. the log2 of the trace length) of each STARK
+    /// table, indexed by [zero_bin_common::prover_state::circuit::Circuit]
+    pub degree_bits: [usize; NUM_TABLES],
+}
+
+impl TxProofStats {
+    /// Returns the (padded) trace length of each STARK table.
+    pub fn trace_lengths(&self) -> [usize; NUM_TABLES] {
+        self.degree_bits.map(|bits| 1 << bits)
+    }
+}
+
+/// An [AggregatableProof] along with the [TxProofStats] of all the
+/// transactions it covers, in order.
+///
+/// The proof is `None` for the [Monoid::empty] element of
+/// [BenchmarkedAggProof], which covers no transaction.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BenchmarkedAggregatableProof {
+    pub proof: Option<AggregatableProof>,
+    pub txn_stats: Vec<TxProofStats>,
+}
+
+/// Like [TxProof], additionally returning the [TxProofStats] of the
+/// transaction.
+#[cfg(not(feature = "test_only"))]
+#[derive(Deserialize, Serialize, RemoteExecute)]
+pub struct BenchmarkedTxProof {
+    pub save_inputs_on_error: bool,
+}
+
+#[cfg(not(feature = "test_only"))]
+impl Operation for BenchmarkedTxProof {
+    type Input = GenerationInputs;
+    type Output = BenchmarkedAggregatableProof;
+
+    fn execute(&self, input: Self::Input) -> Result<Self::Output> {
+        let _span = TxProofSpan::new(&input);
+        let block_number = input.block_metadata.block_number.low_u64();
+        let txn_number = input.txn_number_before.low_u64();
+        let txn_hash = TxProofSpan::get_descriptor(&input);
+        let gas_used = (input.gas_used_after - input.gas_used_before).low_u64();
+
+        let proof_start = Instant::now();
+        let (proof, trace_sizes) = generate_txn_proof(input, self.save_inputs_on_error)?;
+
+        Ok(BenchmarkedAggregatableProof {
+            proof: Some(proof.into()),
+            txn_stats: vec![TxProofStats {
+                block_number,
+                txn_number,
+                txn_hash,
+                gas_used,
+                proof_duration: proof_start.elapsed(),
+                cpu_cycles: trace_sizes.cpu_cycles,
+                degree_bits: trace_sizes.degree_bits,
+            }],
+        })
+    }
+}
+
 #[cfg(feature = "test_only")]
 impl Operation for TxProof {
     type Input = GenerationInputs;
@@ -61,6 +165,7 @@ impl Operation for TxProof {
 
     fn execute(&self, input: Self::Input) -> Result<Self::Output> {
         let _span = TxProofSpan::new(&input);
+        let txn_index = input.txn_number_before.low_u64() as usize;
 
         if self.save_inputs_on_error {
             evm_arithmetization::prover::testing::simulate_execution::<proof_gen::types::Field>(
@@ -77,13 +182,21 @@ impl Operation for TxProof {
                     error!("Failed to save txn proof input to disk: {:?}", write_err);
                 }
 
-                FatalError::from_anyhow(err, FatalStrategy::Terminate)
+                FatalError::from_anyhow(
+                    tag_txn_index(FailureClass::WitnessGeneration.tag(err), txn_index),
+                    FatalStrategy::Terminate,
+                )
             })?;
         } else {
             evm_arithmetization::prover::testing::simulate_execution::<proof_gen::types::Field>(
                 input,
             )
-            .map_err(|err| FatalError::from_anyhow(err, FatalStrategy::Terminate))?;
+            .map_err(|err| {
+                FatalError::from_anyhow(
+                    tag_txn_index(FailureClass::WitnessGeneration.tag(err), txn_index),
+                    FatalStrategy::Terminate,
+                )
+            })?;
         }
 
         Ok(())
@@ -138,6 +251,7 @@ impl TxProofSpan {
 
 impl Drop for TxProofSpan {
     fn drop(&mut self) {
+        TXN_PROOF_DURATION.observe(self.start.elapsed().as_secs_f64());
         event!(
             Level::INFO,
             "txn proof ({}) took {:?}",
@@ -163,6 +277,7 @@ impl Monoid for AggProof {
     type Elem = AggregatableProof;
 
     fn combine(&self, a: Self::Elem, b: Self::Elem) -> Result<Self::Elem> {
+        let _timer = AGG_PROOF_DURATION.with_label_values(&["agg"]).start_timer();
         let result = generate_agg_proof(p_state(), &a, &b).map_err(|e| {
             if self.save_inputs_on_error {
                 let pv = vec![
@@ -180,7 +295,11 @@ impl Monoid for AggProof {
                 }
             }
 
-            FatalError::from(e)
+            record_failure("agg_proof");
+            FatalError::from_anyhow(
+                FailureClass::Proving.tag(e.into()),
+                FatalStrategy::Terminate,
+            )
         })?;
 
         Ok(result.into())
@@ -192,6 +311,41 @@ impl Monoid for AggProof {
     }
 }
 
+/// Like [AggProof], additionally gathering the [TxProofStats] of the combined
+/// proofs.
+#[derive(Deserialize, Serialize, RemoteExecute)]
+pub struct BenchmarkedAggProof {
+    pub save_inputs_on_error: bool,
+}
+
+impl Monoid for BenchmarkedAggProof {
+    type Elem = BenchmarkedAggregatableProof;
+
+    fn combine(&self, a: Self::Elem, b: Self::Elem) -> Result<Self::Elem> {
+        let proof = match (a.proof, b.proof) {
+            (Some(a), Some(b)) => Some(
+                AggProof {
+                    save_inputs_on_error: self.save_inputs_on_error,
+                }
+                .combine(a, b)?,
+            ),
+            (proof, None) | (None, proof) => proof,
+        };
+
+        let mut txn_stats = a.txn_stats;
+        txn_stats.extend(b.txn_stats);
+
+        Ok(BenchmarkedAggregatableProof { proof, txn_stats })
+    }
+
+    fn empty(&self) -> Self::Elem {
+        BenchmarkedAggregatableProof {
+            proof: None,
+            txn_stats: Vec::new(),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, RemoteExecute)]
 pub struct BlockProof {
     pub prev: Option<GeneratedBlockProof>,
@@ -203,6 +357,9 @@ impl Operation for BlockProof {
     type Output = GeneratedBlockProof;
 
     fn execute(&self, input: Self::Input) -> Result<Self::Output> {
+        let _timer = AGG_PROOF_DURATION
+            .with_label_values(&["block"])
+            .start_timer();
         Ok(
             generate_block_proof(p_state(), self.prev.as_ref(), &input).map_err(|e| {
                 if self.save_inputs_on_error {
@@ -217,7 +374,11 @@ impl Operation for BlockProof {
                     }
                 }
 
-                FatalError::from(e)
+                record_failure("block_proof");
+                FatalError::from_anyhow(
+                    FailureClass::Proving.tag(e.into()),
+                    FatalStrategy::Terminate,
+                )
             })?,
         )
     }
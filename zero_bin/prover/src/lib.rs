@@ -1,13 +1,21 @@
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Instant;
 use std::{future::Future, time::Duration};
 
 use alloy::primitives::{BlockNumber, U256};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use futures::{future::BoxFuture, stream::FuturesOrdered, FutureExt, TryFutureExt, TryStreamExt};
+use evm_arithmetization::prover::check_abort_signal;
+use futures::{
+    future::{BoxFuture, Shared},
+    stream::FuturesOrdered,
+    FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt,
+};
+use mpt_trie::partial_trie::PartialTrie as _;
 use num_traits::ToPrimitive as _;
-use ops::TxProof;
+use ops::{BenchmarkedAggregatableProof, TxProof, TxProofStats};
 use paladin::{
     directive::{Directive, IndexedStream},
     runtime::Runtime,
@@ -21,10 +29,15 @@ use trace_decoder::{
     trace_protocol::BlockTrace,
     types::{CodeHash, OtherBlockData},
 };
-use tracing::info;
-use zero_bin_common::fs::generate_block_proof_file_name;
+use tracing::{info, warn};
+use zero_bin_common::{
+    failure::{txn_index_of, FailureClass, RetryPolicies},
+    fs::generate_block_proof_file_name,
+    metrics,
+    proof_format::ProofEncoder,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BlockProverInput {
     pub block_trace: BlockTrace,
     pub other_data: OtherBlockData,
@@ -33,6 +46,95 @@ fn resolve_code_hash_fn(_: &CodeHash) -> Vec<u8> {
     todo!()
 }
 
+/// Returned by [BlockProverInput::prove_and_benchmark] when the proof of the
+/// previous block never came, i.e. the previous block was skipped.  The block
+/// can then only be proven as the start of a new chain.
+#[derive(Debug, Clone, Copy)]
+pub struct PreviousBlockMissing;
+
+impl std::fmt::Display for PreviousBlockMissing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the proof of the previous block is missing")
+    }
+}
+
+/// A block which could not be proven, even after being retried as per the
+/// [RetryPolicy](zero_bin_common::failure::RetryPolicy) of its
+/// [FailureClass].
+pub struct BlockFailure<B = BlockProverInput> {
+    /// The number of the block
+    pub block_number: u64,
+    /// The input of the block, so that it can be replayed
+    pub input: B,
+    /// The index of the failing transaction within the block, if a
+    /// transaction failed
+    pub txn_index: Option<usize>,
+    /// The class of the last failure
+    pub class: FailureClass,
+    /// The number of times the block was attempted
+    pub attempts: u32,
+    /// Whether the block was skipped, in which case the following blocks are
+    /// still proven
+    pub skipped: bool,
+    /// The last failure
+    pub error: anyhow::Error,
+}
+
+impl<B> std::fmt::Debug for BlockFailure<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockFailure")
+            .field("block_number", &self.block_number)
+            .field("txn_index", &self.txn_index)
+            .field("class", &self.class)
+            .field("attempts", &self.attempts)
+            .field("skipped", &self.skipped)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B> std::fmt::Display for BlockFailure<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "block {} failed ({}) after {} attempt(s): {:#}",
+            self.block_number, self.class, self.attempts, self.error
+        )
+    }
+}
+
+impl<B> std::error::Error for BlockFailure<B> {}
+
+/// The interval at which an in-flight directive checks the abort signal.
+const ABORT_SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs `fut` to completion unless the abort signal is raised first, in which
+/// case `fut` is dropped and no further tasks are dispatched to the runtime.
+async fn run_abortable<T>(
+    fut: impl Future<Output = Result<T>>,
+    abort_signal: Option<Arc<AtomicBool>>,
+) -> Result<T> {
+    check_abort_signal(abort_signal.clone())?;
+    let signal = match abort_signal {
+        Some(signal) => signal,
+        None => return fut.await,
+    };
+
+    let aborted = async {
+        loop {
+            tokio::time::sleep(ABORT_SIGNAL_POLL_INTERVAL).await;
+            if let Err(err) = check_abort_signal(Some(signal.clone())) {
+                return err;
+            }
+        }
+    };
+
+    tokio::select! {
+        res = fut => res,
+        err = aborted => Err(err),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BenchmarkedGeneratedBlockProof {
     pub proof: GeneratedBlockProof,
@@ -47,6 +149,8 @@ pub struct BenchmarkedGeneratedBlockProof {
     pub difficulty: u64,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
+    /// The statistics of each transaction proof, in order
+    pub txn_stats: Vec<TxProofStats>,
 }
 
 unsafe impl Send for BenchmarkedGeneratedBlockProof {}
@@ -62,13 +166,22 @@ impl BlockProverInput {
         self.other_data.b_data.b_meta.block_number.into()
     }
 
+    /// Proves the block upon the proof of the `previous` block, if any.
+    ///
+    /// If `restart_chain` is set, the block is proven as the start of a new
+    /// chain, its checkpoint being the state before the block.  Fails with
+    /// [PreviousBlockMissing] if the `previous` proof never comes.
     #[cfg(not(feature = "test_only"))]
     pub async fn prove_and_benchmark(
         self,
         runtime: &Runtime,
         previous: Option<impl Future<Output = Result<BenchmarkedGeneratedBlockProof>>>,
+        restart_chain: bool,
         save_inputs_on_error: bool,
+        abort_signal: Option<Arc<AtomicBool>>,
     ) -> Result<BenchmarkedGeneratedBlockProof> {
+        check_abort_signal(abort_signal.clone())?;
+
         // Start timing for preparation
         let prep_start = Instant::now();
         let start_time: DateTime<Utc> = Utc::now();
@@ -77,10 +190,21 @@ impl BlockProverInput {
         use anyhow::Context as _;
         let block_number = self.get_block_number();
         let other_data = self.other_data;
-        let txs = self.block_trace.into_txn_proof_gen_ir(
-            &ProcessingMeta::new(resolve_code_hash_fn),
-            other_data.clone(),
-        )?;
+        let mut txs = self
+            .block_trace
+            .into_txn_proof_gen_ir(
+                &ProcessingMeta::new(resolve_code_hash_fn),
+                other_data.clone(),
+            )
+            .map_err(|err| FailureClass::TraceDecoding.tag(err.into()))?;
+        if restart_chain {
+            if let Some(checkpoint) = txs.first().map(|tx| tx.tries.state_trie.hash()) {
+                info!("Block {block_number} starts a new chain from checkpoint {checkpoint:x}");
+                for tx in txs.iter_mut() {
+                    tx.checkpoint_state_trie_root = checkpoint;
+                }
+            }
+        }
 
         let n_txs = txs.len();
         let gas_used = u64::try_from(other_data.b_data.b_meta.block_gas_used).expect("Overflow");
@@ -102,16 +226,28 @@ impl BlockProverInput {
         );
 
         let proof_start = Instant::now();
-        let agg_proof = IndexedStream::from(txs)
-            .map(&TxProof {
-                save_inputs_on_error,
-            })
-            .fold(&ops::AggProof {
-                save_inputs_on_error,
-            })
-            .run(runtime)
-            .await?;
+        let BenchmarkedAggregatableProof {
+            proof: agg_proof,
+            mut txn_stats,
+        } = run_abortable(
+            async {
+                Ok(IndexedStream::from(txs)
+                    .map(&ops::BenchmarkedTxProof {
+                        save_inputs_on_error,
+                    })
+                    .fold(&ops::BenchmarkedAggProof {
+                        save_inputs_on_error,
+                    })
+                    .run(runtime)
+                    .await?)
+            },
+            abort_signal.clone(),
+        )
+        .await?;
         let proof_dur = proof_start.elapsed();
+        // The statistics are gathered as the proofs are aggregated, which does
+        // not guarantee the order of the transactions
+        txn_stats.sort_by_key(|stats| stats.txn_number);
 
         info!(
             "Completed tx proofs for block {} in {} secs",
@@ -119,25 +255,32 @@ impl BlockProverInput {
             proof_dur.as_secs_f64()
         );
 
-        if let proof_gen::proof_types::AggregatableProof::Agg(proof) = agg_proof {
+        if let Some(proof_gen::proof_types::AggregatableProof::Agg(proof)) = agg_proof {
             let agg_wait_start = Instant::now();
             let block_number = block_number
                 .to_u64()
-                .context("block number overflows u64")?;
+                .context("block number overflows u64")
+                .map_err(|err| FailureClass::Proving.tag(err))?;
             let prev = match previous {
-                Some(it) => Some(it.await?),
+                Some(it) => Some(it.await.context(PreviousBlockMissing)?),
                 None => None,
             };
             let agg_wait_dur = agg_wait_start.elapsed();
 
             let agg_start = Instant::now();
-            let block_proof = paladin::directive::Literal(proof)
-                .map(&ops::BlockProof {
-                    prev: prev.map(|p| p.proof),
-                    save_inputs_on_error,
-                })
-                .run(runtime)
-                .await?;
+            let block_proof = run_abortable(
+                async {
+                    Ok(paladin::directive::Literal(proof)
+                        .map(&ops::BlockProof {
+                            prev: prev.map(|p| p.proof),
+                            save_inputs_on_error,
+                        })
+                        .run(runtime)
+                        .await?)
+                },
+                abort_signal,
+            )
+            .await?;
             let agg_dur = agg_start.elapsed();
             info!(
                 "Completed tx proof agg for block {} in {} secs",
@@ -150,6 +293,7 @@ impl BlockProverInput {
                 "Successfully proved block {block_number} (in {} secs)",
                 total_dur.as_secs_f64()
             );
+            metrics::record_block_proved(total_dur);
             // Return the block proof
             Ok(BenchmarkedGeneratedBlockProof {
                 proof: block_proof.0,
@@ -164,9 +308,10 @@ impl BlockProverInput {
                 difficulty: u64::try_from(difficulty).expect("Difficulty overflow"),
                 start_time,
                 end_time,
+                txn_stats,
             })
         } else {
-            anyhow::bail!("AggProof is is not GeneratedAggProof")
+            Err(FailureClass::Proving.tag(anyhow::anyhow!("AggProof is not GeneratedAggProof")))
         }
     }
 
@@ -179,6 +324,7 @@ impl BlockProverInput {
     ) -> Result<GeneratedBlockProof> {
         use anyhow::Context as _;
 
+        let start = Instant::now();
         let block_number = self.get_block_number();
 
         let other_data = self.other_data;
@@ -200,7 +346,8 @@ impl BlockProverInput {
         if let proof_gen::proof_types::AggregatableProof::Agg(proof) = agg_proof {
             let block_number = block_number
                 .to_u64()
-                .context("block number overflows u64")?;
+                .context("block number overflows u64")
+                .map_err(|err| FailureClass::Proving.tag(err))?;
             let prev = match previous {
                 Some(it) => Some(it.await?),
                 None => None,
@@ -215,9 +362,10 @@ impl BlockProverInput {
                 .await?;
 
             info!("Successfully proved block {block_number}");
+            metrics::record_block_proved(start.elapsed());
             Ok(block_proof.0)
         } else {
-            anyhow::bail!("AggProof is is not GeneratedAggProof")
+            Err(FailureClass::Proving.tag(anyhow::anyhow!("AggProof is not GeneratedAggProof")))
         }
     }
 
@@ -256,6 +404,145 @@ impl BlockProverInput {
     }
 }
 
+/// Prove the blocks as they are yielded by the `blocks` stream, each block
+/// proof building upon the previous one.
+///
+/// Returns a stream of the block proofs in the same order as `blocks`, where
+/// each proof is yielded as soon as it (and the proofs before it) completed,
+/// rather than once every block has been proved.
+///
+/// A failing block is attempted again as per the
+/// [RetryPolicy](zero_bin_common::failure::RetryPolicy) of its [FailureClass].
+/// Once its retries are exhausted, a [BlockFailure] is yielded instead of its
+/// proof.  If the block was skipped, the following blocks are still proven, the
+/// chain starting over at the next block.
+///
+/// If the `abort_signal` is raised, the remaining blocks are not proved and
+/// an error is yielded instead.
+pub fn prove_and_benchmark_stream<'a>(
+    blocks: impl Stream<Item = BlockProverInput> + 'a,
+    runtime: &'a Runtime,
+    previous_proof: Option<BenchmarkedGeneratedBlockProof>,
+    save_inputs_on_error: bool,
+    retry_policies: RetryPolicies,
+    abort_signal: Option<Arc<AtomicBool>>,
+) -> impl Stream<Item = Result<BenchmarkedGeneratedBlockProof, BlockFailure>> + 'a {
+    let prove_abort_signal = abort_signal.clone();
+    prove_chain(
+        blocks,
+        |block: &BlockProverInput| block.other_data.b_data.b_meta.block_number.low_u64(),
+        previous_proof,
+        move |block: BlockProverInput, previous, restart_chain| {
+            block.prove_and_benchmark(
+                runtime,
+                previous,
+                restart_chain,
+                save_inputs_on_error,
+                prove_abort_signal.clone(),
+            )
+        },
+        retry_policies,
+        abort_signal,
+    )
+}
+
+/// Chains the proofs of the `blocks` with `prove`, which is given the block,
+/// the proof of the previous block (if the block does not start the chain)
+/// and whether the block restarts the chain, see [prove_and_benchmark_stream].
+fn prove_chain<'a, B, P, F, Fut>(
+    blocks: impl Stream<Item = B> + 'a,
+    block_number_of: impl Fn(&B) -> u64 + 'a,
+    previous_proof: Option<P>,
+    prove: F,
+    retry_policies: RetryPolicies,
+    abort_signal: Option<Arc<AtomicBool>>,
+) -> impl Stream<Item = Result<P, BlockFailure<B>>> + 'a
+where
+    B: Clone + Send + 'a,
+    P: Clone + Send + Sync + 'a,
+    F: Fn(B, Option<BoxFuture<'a, Result<P>>>, bool) -> Fut + Clone + Send + 'a,
+    Fut: Future<Output = Result<P>> + Send + 'a,
+{
+    // The proof of the previous block resolves to `None` if it was not proven
+    let mut prev: Option<Shared<BoxFuture<'a, Option<P>>>> =
+        previous_proof.map(|proof| futures::future::ready(Some(proof)).boxed().shared());
+    let mut prev_block_number: Option<u64> = None;
+
+    blocks
+        .map(move |block| {
+            let block_number = block_number_of(&block);
+            info!("Proving block {block_number}");
+
+            // The chain starts over after a gap in the blocks
+            let mut restart_chain =
+                prev_block_number.is_some_and(|prev_number| prev_number + 1 != block_number);
+            prev_block_number = Some(block_number);
+            let previous = prev.take().filter(|_| !restart_chain);
+
+            let (tx, rx) = oneshot::channel::<P>();
+            prev = Some(rx.map(Result::ok).boxed().shared());
+
+            let prove = prove.clone();
+            let abort_signal = abort_signal.clone();
+            async move {
+                let mut attempts = 0;
+                loop {
+                    attempts += 1;
+                    let previous = previous.clone().filter(|_| !restart_chain).map(|previous| {
+                        previous
+                            .map(|proof| proof.ok_or_else(|| anyhow::anyhow!("no proof")))
+                            .boxed()
+                    });
+                    let err = match prove(block.clone(), previous, restart_chain).await {
+                        Ok(proof) => {
+                            // The next block may have been skipped meanwhile
+                            let _ = tx.send(proof.clone());
+                            return Ok(proof);
+                        }
+                        Err(err) if err.downcast_ref::<PreviousBlockMissing>().is_some() => {
+                            warn!(
+                                "Block {block_number} starts a new chain, as the previous \
+                                 block was not proven"
+                            );
+                            restart_chain = true;
+                            attempts -= 1;
+                            continue;
+                        }
+                        Err(err) => err,
+                    };
+
+                    let class = FailureClass::of(&err);
+                    let policy = retry_policies.get(class);
+                    // A cancelled job is not retried
+                    let aborted = check_abort_signal(abort_signal.clone()).is_err();
+                    if !aborted && attempts <= policy.max_retries {
+                        warn!(
+                            "Failed to prove block {block_number} ({class}), retrying in {:?}: {err:#}",
+                            policy.backoff()
+                        );
+                        metrics::record_retry(class);
+                        tokio::time::sleep(policy.backoff()).await;
+                        continue;
+                    }
+
+                    return Err(BlockFailure {
+                        block_number,
+                        input: block,
+                        txn_index: txn_index_of(&err),
+                        class,
+                        attempts,
+                        skipped: policy.skip && !aborted,
+                        error: err,
+                    });
+                }
+            }
+            .boxed()
+        })
+        // Every block is started as soon as it is available, the chaining of
+        // the proofs is handled by `prev`.
+        .buffered(usize::MAX)
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ProverInput {
     pub blocks: Vec<BlockProverInput>,
@@ -265,12 +552,15 @@ impl ProverInput {
     /// Prove all the blocks in the input.
     /// Return the list of block numbers that are proved and if the proof data
     /// is not saved to disk, return the generated block proofs as well.
+    ///
+    /// Proofs saved to disk are encoded with `proof_encoder`.
     pub async fn prove(
         self,
         runtime: &Runtime,
         previous_proof: Option<GeneratedBlockProof>,
         save_inputs_on_error: bool,
         proof_output_dir: Option<PathBuf>,
+        proof_encoder: ProofEncoder,
     ) -> Result<Vec<(BlockNumber, Option<GeneratedBlockProof>)>> {
         let mut prev: Option<BoxFuture<Result<GeneratedBlockProof>>> =
             previous_proof.map(|proof| Box::pin(futures::future::ok(proof)) as BoxFuture<_>);
@@ -295,7 +585,8 @@ impl ProverInput {
                         // Write latest generated proof to disk if proof_output_dir is provided
                         let return_proof: Option<GeneratedBlockProof> =
                             if proof_output_dir.is_some() {
-                                ProverInput::write_proof(proof_output_dir, &proof).await?;
+                                ProverInput::write_proof(proof_output_dir, &proof, &proof_encoder)
+                                    .await?;
                                 None
                             } else {
                                 Some(proof.clone())
@@ -321,66 +612,60 @@ impl ProverInput {
     /// Prove all the blocks in the input.
     /// Return the list of block numbers that are proved and if the proof data
     /// is not saved to disk, return the generated block proofs as well.
+    ///
+    /// Proofs saved to disk are encoded with `proof_encoder`.
+    ///
+    /// If the `abort_signal` is raised, the remaining blocks are not proved
+    /// and an error is returned.
+    #[allow(clippy::too_many_arguments)]
     pub async fn prove_and_benchmark(
         self,
         runtime: &Runtime,
         previous_proof: Option<BenchmarkedGeneratedBlockProof>,
         save_inputs_on_error: bool,
         proof_output_dir: Option<PathBuf>,
+        proof_encoder: ProofEncoder,
+        abort_signal: Option<Arc<AtomicBool>>,
     ) -> Result<Vec<(BlockNumber, Option<BenchmarkedGeneratedBlockProof>)>> {
-        let mut prev: Option<BoxFuture<Result<BenchmarkedGeneratedBlockProof>>> =
-            previous_proof.map(|proof| Box::pin(futures::future::ok(proof)) as BoxFuture<_>);
-
-        let results: FuturesOrdered<_> = self
-            .blocks
-            .into_iter()
-            .map(|block| {
-                let block_number = block.get_block_number();
-                info!("Proving block {block_number}");
-
-                let (tx, rx) = oneshot::channel::<BenchmarkedGeneratedBlockProof>();
-
-                // Prove the block
-                let proof_output_dir = proof_output_dir.clone();
-                let fut = block
-                    .prove_and_benchmark(runtime, prev.take(), save_inputs_on_error)
-                    .then(move |benchmarkproof| async move {
-                        let benchmarkproof = benchmarkproof?;
-                        let block_number = benchmarkproof.proof.b_height;
-
-                        // Write latest generated proof to disk if proof_output_dir is provided
-                        let return_proof: Option<BenchmarkedGeneratedBlockProof> =
-                            if proof_output_dir.is_some() {
-                                ProverInput::write_proof(proof_output_dir, &benchmarkproof.proof)
-                                    .await?;
-                                None
-                            } else {
-                                Some(benchmarkproof.clone())
-                            };
-
-                        if tx.send(benchmarkproof).is_err() {
-                            anyhow::bail!("Failed to send proof");
-                        }
-
-                        Ok((block_number, return_proof))
-                    })
-                    .boxed();
-
-                prev = Some(Box::pin(rx.map_err(anyhow::Error::new)));
-
-                fut
-            })
-            .collect();
-
-        results.try_collect().await
+        prove_and_benchmark_stream(
+            futures::stream::iter(self.blocks),
+            runtime,
+            previous_proof,
+            save_inputs_on_error,
+            RetryPolicies::FAIL,
+            abort_signal,
+        )
+        .map_err(anyhow::Error::from)
+        .and_then(|benchmarkproof| {
+            let proof_output_dir = proof_output_dir.clone();
+            async move {
+                let block_number = benchmarkproof.proof.b_height;
+
+                // Write latest generated proof to disk if proof_output_dir is provided
+                if proof_output_dir.is_some() {
+                    ProverInput::write_proof(
+                        proof_output_dir,
+                        &benchmarkproof.proof,
+                        &proof_encoder,
+                    )
+                    .await?;
+                    Ok((block_number, None))
+                } else {
+                    Ok((block_number, Some(benchmarkproof)))
+                }
+            }
+        })
+        .try_collect()
+        .await
     }
 
     /// Write the proof to the disk (if `output_dir` is provided) or stdout.
     pub(crate) async fn write_proof(
         output_dir: Option<PathBuf>,
         proof: &GeneratedBlockProof,
+        proof_encoder: &ProofEncoder,
     ) -> Result<()> {
-        let proof_serialized = serde_json::to_vec(proof)?;
+        let proof_serialized = proof_encoder.encode_block_proof(proof)?;
         let block_proof_file_path =
             output_dir.map(|path| generate_block_proof_file_name(&path.to_str(), proof.b_height));
         match block_proof_file_path {
@@ -401,3 +686,222 @@ impl ProverInput {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use zero_bin_common::failure::RetryPolicy;
+
+    use super::*;
+
+    type Chain = Vec<u64>;
+
+    /// Proves each block as the chain of the blocks it builds upon, failing
+    /// the blocks of `failures` with their class as many times as given
+    fn prover(
+        failures: Vec<(u64, FailureClass, u32)>,
+    ) -> impl Fn(
+        u64,
+        Option<BoxFuture<'static, Result<Chain>>>,
+        bool,
+    ) -> BoxFuture<'static, Result<Chain>>
+           + Clone
+           + Send {
+        let failures: Arc<Mutex<HashMap<u64, (FailureClass, u32)>>> = Arc::new(Mutex::new(
+            failures
+                .into_iter()
+                .map(|(block, class, times)| (block, (class, times)))
+                .collect(),
+        ));
+        move |block: u64,
+              previous: Option<BoxFuture<'static, Result<Chain>>>,
+              restart_chain: bool| {
+            assert!(!restart_chain || previous.is_none());
+            let failure = match failures.lock().unwrap().get_mut(&block) {
+                Some((class, times)) if *times > 0 => {
+                    *times -= 1;
+                    Some(*class)
+                }
+                _ => None,
+            };
+            async move {
+                if let Some(class) = failure {
+                    return Err(class.tag(anyhow::anyhow!("block {block} failed")));
+                }
+                let mut chain = match previous {
+                    Some(previous) => previous.await.context(PreviousBlockMissing)?,
+                    None => Vec::new(),
+                };
+                chain.push(block);
+                Ok(chain)
+            }
+            .boxed()
+        }
+    }
+
+    /// The outcome of a block: its chain, or the class of its failure, the
+    /// number of attempts and whether it was skipped
+    type Outcome = Result<Chain, (u64, FailureClass, u32, bool)>;
+
+    async fn prove(
+        blocks: Vec<u64>,
+        previous_proof: Option<Chain>,
+        failures: Vec<(u64, FailureClass, u32)>,
+        retry_policies: RetryPolicies,
+    ) -> Vec<Outcome> {
+        prove_chain(
+            futures::stream::iter(blocks),
+            |block: &u64| *block,
+            previous_proof,
+            prover(failures),
+            retry_policies,
+            None,
+        )
+        .map(|result| {
+            result.map_err(|failure| {
+                (
+                    failure.block_number,
+                    failure.class,
+                    failure.attempts,
+                    failure.skipped,
+                )
+            })
+        })
+        .collect()
+        .await
+    }
+
+    fn policies(class: FailureClass, policy: RetryPolicy) -> RetryPolicies {
+        let mut policies = RetryPolicies::default();
+        match class {
+            FailureClass::Fetch => policies.fetch = policy,
+            FailureClass::TraceDecoding => policies.trace_decoding = policy,
+            FailureClass::WitnessGeneration => policies.witness_generation = policy,
+            FailureClass::Proving => policies.proving = policy,
+            FailureClass::WorkerLost => policies.worker_lost = policy,
+        }
+        policies
+    }
+
+    #[tokio::test]
+    async fn chains_consecutive_blocks() {
+        assert_eq!(
+            prove(vec![1, 2, 3], None, vec![], RetryPolicies::default()).await,
+            vec![Ok(vec![1]), Ok(vec![1, 2]), Ok(vec![1, 2, 3])]
+        );
+    }
+
+    #[tokio::test]
+    async fn builds_upon_the_previous_proof() {
+        assert_eq!(
+            prove(vec![1, 2], Some(vec![0]), vec![], RetryPolicies::default()).await,
+            vec![Ok(vec![0, 1]), Ok(vec![0, 1, 2])]
+        );
+    }
+
+    #[tokio::test]
+    async fn restarts_the_chain_after_a_gap() {
+        assert_eq!(
+            prove(vec![1, 2, 5, 6], None, vec![], RetryPolicies::default()).await,
+            vec![Ok(vec![1]), Ok(vec![1, 2]), Ok(vec![5]), Ok(vec![5, 6])]
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_failed_blocks() {
+        let retry_policies = policies(
+            FailureClass::Proving,
+            RetryPolicy {
+                max_retries: 2,
+                backoff_ms: 0,
+                skip: false,
+            },
+        );
+        assert_eq!(
+            prove(
+                vec![1, 2, 3],
+                None,
+                vec![(2, FailureClass::Proving, 2)],
+                retry_policies
+            )
+            .await,
+            vec![Ok(vec![1]), Ok(vec![1, 2]), Ok(vec![1, 2, 3])]
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_once_the_retries_are_exhausted() {
+        let outcomes = prove(
+            vec![1, 2],
+            None,
+            vec![(2, FailureClass::Proving, 3)],
+            RetryPolicies::default(),
+        )
+        .await;
+        assert_eq!(outcomes[1], Err((2, FailureClass::Proving, 2, false)));
+    }
+
+    #[tokio::test]
+    async fn skips_the_deterministic_failures_by_default() {
+        let outcomes = prove(
+            vec![1, 2, 3],
+            None,
+            vec![(2, FailureClass::WitnessGeneration, 1)],
+            RetryPolicies::default(),
+        )
+        .await;
+        assert_eq!(
+            outcomes,
+            vec![
+                Ok(vec![1]),
+                Err((2, FailureClass::WitnessGeneration, 1, true)),
+                Ok(vec![3]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_skip_when_the_policy_fails() {
+        let outcomes = prove(
+            vec![1, 2, 3],
+            None,
+            vec![(2, FailureClass::WitnessGeneration, 1)],
+            policies(FailureClass::WitnessGeneration, RetryPolicy::FAIL),
+        )
+        .await;
+        assert_eq!(outcomes[0], Ok(vec![1]));
+        assert_eq!(
+            outcomes[1],
+            Err((2, FailureClass::WitnessGeneration, 1, false))
+        );
+    }
+
+    #[tokio::test]
+    async fn restarts_the_chain_after_a_skipped_block() {
+        let retry_policies = policies(
+            FailureClass::WitnessGeneration,
+            RetryPolicy {
+                max_retries: 0,
+                backoff_ms: 0,
+                skip: true,
+            },
+        );
+        assert_eq!(
+            prove(
+                vec![1, 2, 3, 4],
+                None,
+                vec![(2, FailureClass::WitnessGeneration, 1)],
+                retry_policies
+            )
+            .await,
+            vec![
+                Ok(vec![1]),
+                Err((2, FailureClass::WitnessGeneration, 1, true)),
+                Ok(vec![3]),
+                Ok(vec![3, 4]),
+            ]
+        );
+    }
+}
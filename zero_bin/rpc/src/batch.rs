@@ -0,0 +1,513 @@
+//! Coalesces the concurrent JSON-RPC requests into batch requests.
+//!
+//! The requests of a provider are queued to a background task, which sends
+//! every request queued by the time it runs (up to the batch size) as one
+//! batch.  The requests issued together, such as the `eth_getProof` of every
+//! account of a block or the previous block hashes, are therefore sent in a
+//! handful of HTTP requests rather than one each.
+//!
+//! The queue is bounded, and so are the batches in flight: once both are full,
+//! the services are not ready until a batch completes.
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+};
+
+use alloy::{
+    rpc::json_rpc::{Id, RequestPacket, Response, ResponsePacket, SerializedRequest},
+    transports::{TransportError, TransportErrorKind},
+};
+use futures::{
+    future::{poll_fn, BoxFuture},
+    FutureExt as _,
+};
+use tokio::sync::{
+    mpsc::{self, error::SendError, OwnedPermit},
+    oneshot, Semaphore,
+};
+use tower::{Layer, Service};
+use tracing::debug;
+
+/// The number of batches worth of requests which may be queued, beyond which
+/// the services are not ready until the queue is drained
+const QUEUED_BATCHES: usize = 4;
+/// The maximum number of batches in flight, beyond which the queued requests
+/// wait for a batch to complete
+const MAX_BATCHES_IN_FLIGHT: usize = 16;
+
+/// A queued request, along with where to send its response
+type Pending = (
+    SerializedRequest,
+    oneshot::Sender<Result<Response, TransportError>>,
+);
+
+/// The reservation of a slot in the queue
+type Reserve = BoxFuture<'static, Result<OwnedPermit<Pending>, SendError<()>>>;
+
+/// BatchLayer
+pub struct BatchLayer {
+    batch_size: usize,
+}
+
+impl BatchLayer {
+    /// Returns the layer batching up to `batch_size` requests, or passing them
+    /// through if `batch_size` is 1 or less
+    pub const fn new(batch_size: usize) -> Self {
+        Self { batch_size }
+    }
+}
+
+impl<S> Layer<S> for BatchLayer
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + 'static
+        + Clone,
+    S::Future: Send + 'static,
+{
+    type Service = BatchService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let queue = (self.batch_size > 1).then(|| {
+            let (sender, requests) = mpsc::channel(self.batch_size * QUEUED_BATCHES);
+            Arc::new(Queue {
+                sender,
+                task: Mutex::new(Some(
+                    run_batches(inner.clone(), requests, self.batch_size).boxed(),
+                )),
+            })
+        });
+        BatchService {
+            inner,
+            queue,
+            reserve: Mutex::new(None),
+            permit: None,
+        }
+    }
+}
+
+/// The queue of the requests to batch, shared by the clones of a
+/// [BatchService]
+struct Queue {
+    sender: mpsc::Sender<Pending>,
+    /// The task sending the batches, until it is spawned by the first request
+    /// so that the layer can be built outside of a Tokio runtime
+    task: Mutex<Option<BoxFuture<'static, ()>>>,
+}
+
+impl Queue {
+    /// Spawns the task sending the batches, unless it is already running
+    fn start(&self) {
+        if let Some(task) = self.task.lock().expect("batch queue lock poisoned").take() {
+            tokio::spawn(task);
+        }
+    }
+}
+
+/// BatchService
+pub struct BatchService<S> {
+    inner: S,
+    queue: Option<Arc<Queue>>,
+    /// The pending reservation of a slot in the queue, behind a mutex so that
+    /// the service is `Sync`
+    reserve: Mutex<Option<Reserve>>,
+    /// The slot in the queue reserved by [Service::poll_ready]
+    permit: Option<OwnedPermit<Pending>>,
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for BatchService<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchService")
+            .field("inner", &self.inner)
+            .field("batched", &self.queue.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: Clone> Clone for BatchService<S> {
+    /// The clone has to reserve its own slot in the queue
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            queue: self.queue.clone(),
+            reserve: Mutex::new(None),
+            permit: None,
+        }
+    }
+}
+
+impl<S> Service<RequestPacket> for BatchService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + 'static
+        + Clone,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Ready once a slot in the queue is reserved, so that the callers wait
+    /// while the queue is full
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let Some(queue) = &self.queue else {
+            return self.inner.poll_ready(cx);
+        };
+        if self.permit.is_some() {
+            return Poll::Ready(Ok(()));
+        }
+
+        let reserve = self.reserve.get_mut().expect("batch reserve lock poisoned");
+        let reserved = ready!(reserve
+            .get_or_insert_with(|| queue.sender.clone().reserve_owned().boxed())
+            .as_mut()
+            .poll(cx));
+        *reserve = None;
+        match reserved {
+            Ok(permit) => {
+                self.permit = Some(permit);
+                Poll::Ready(Ok(()))
+            }
+            Err(_) => Poll::Ready(Err(TransportErrorKind::custom_str(
+                "the batching task stopped",
+            ))),
+        }
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let (queue, request) = match (&self.queue, req) {
+            (Some(queue), RequestPacket::Single(request)) => (queue.clone(), request),
+            (None, req) => return Box::pin(self.inner.call(req)),
+            // Batches are sent as is, through a service of their own as the
+            // readiness of this one is a slot in the queue
+            (Some(_), req) => {
+                let mut inner = self.inner.clone();
+                return Box::pin(async move {
+                    poll_fn(|cx| inner.poll_ready(cx)).await?;
+                    inner.call(req).await
+                });
+            }
+        };
+
+        let permit = self
+            .permit
+            .take()
+            .expect("BatchService::poll_ready must be called before call");
+        let (tx, rx) = oneshot::channel();
+        permit.send((request, tx));
+        Box::pin(async move {
+            queue.start();
+            match rx.await {
+                Ok(response) => response.map(ResponsePacket::Single),
+                Err(_) => Err(TransportErrorKind::custom_str(
+                    "the batch of the request was dropped",
+                )),
+            }
+        })
+    }
+}
+
+/// Sends the queued requests in batches of up to `batch_size`, until every
+/// [BatchService] is dropped
+async fn run_batches<S>(inner: S, mut requests: mpsc::Receiver<Pending>, batch_size: usize)
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + 'static
+        + Clone,
+    S::Future: Send + 'static,
+{
+    let in_flight = Arc::new(Semaphore::new(MAX_BATCHES_IN_FLIGHT));
+    let mut next = None;
+    loop {
+        let first = match next.take() {
+            Some(first) => first,
+            None => match requests.recv().await {
+                Some(first) => first,
+                None => return,
+            },
+        };
+        // Let the requests issued along with the first one be queued
+        tokio::task::yield_now().await;
+
+        // The responses are matched by id, so a batch never holds the same id
+        // twice
+        let mut ids = HashSet::from([first.0.id().clone()]);
+        let mut batch = vec![first];
+        while batch.len() < batch_size {
+            let Ok(pending) = requests.try_recv() else {
+                break;
+            };
+            if !ids.insert(pending.0.id().clone()) {
+                next = Some(pending);
+                break;
+            }
+            batch.push(pending);
+        }
+
+        // The requests are left in the queue while too many batches are in
+        // flight
+        let permit = in_flight
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let inner = inner.clone();
+        tokio::spawn(async move {
+            send_batch(inner, batch).await;
+            drop(permit);
+        });
+    }
+}
+
+/// Sends the requests as one batch, and dispatches the responses
+async fn send_batch<S>(mut inner: S, batch: Vec<Pending>)
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>,
+{
+    let (requests, senders): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+    let ids = requests
+        .iter()
+        .map(|request| request.id().clone())
+        .collect::<Vec<Id>>();
+    let packet = match <[_; 1]>::try_from(requests) {
+        Ok([request]) => RequestPacket::Single(request),
+        Err(requests) => {
+            debug!("Sending a batch of {} RPC requests", requests.len());
+            RequestPacket::Batch(requests)
+        }
+    };
+
+    let result = match poll_fn(|cx| inner.poll_ready(cx)).await {
+        Ok(()) => inner.call(packet).await,
+        Err(err) => Err(err),
+    };
+    let mut responses = match result {
+        Ok(ResponsePacket::Single(response)) => HashMap::from([(response.id.clone(), response)]),
+        Ok(ResponsePacket::Batch(responses)) => responses
+            .into_iter()
+            .map(|response| (response.id.clone(), response))
+            .collect(),
+        Err(err) if senders.len() == 1 => {
+            let _ = senders
+                .into_iter()
+                .next()
+                .map(|sender| sender.send(Err(err)));
+            return;
+        }
+        Err(err) => {
+            // The error is shared by every request of the batch
+            let message = err.to_string();
+            for sender in senders {
+                let _ = sender.send(Err(TransportErrorKind::custom_str(&message)));
+            }
+            return;
+        }
+    };
+    for (id, sender) in ids.into_iter().zip(senders) {
+        let response = responses.remove(&id).ok_or(TransportError::Transport(
+            TransportErrorKind::MissingBatchResponse(id),
+        ));
+        let _ = sender.send(response);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloy::rpc::json_rpc::{Request, ResponsePayload};
+    use futures::{future::join_all, FutureExt as _};
+    use serde_json::value::RawValue;
+
+    use super::*;
+
+    /// Answers every request with its method, the batches in reverse order
+    /// and without the responses of the `unanswered` ids, checking that it
+    /// was polled ready before every call
+    #[derive(Clone, Default)]
+    struct Node {
+        /// The number of requests of each packet received
+        packets: Arc<Mutex<Vec<usize>>>,
+        unanswered: Vec<Id>,
+        down: bool,
+        ready: bool,
+    }
+
+    impl Service<RequestPacket> for Node {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.ready = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: RequestPacket) -> Self::Future {
+            assert!(std::mem::take(&mut self.ready), "called before ready");
+            let answer = |request: &SerializedRequest| Response {
+                id: request.id().clone(),
+                payload: ResponsePayload::Success(
+                    RawValue::from_string(format!("\"{}\"", request.method())).unwrap(),
+                ),
+            };
+            let response = match req {
+                RequestPacket::Single(request) => {
+                    self.packets.lock().unwrap().push(1);
+                    ResponsePacket::Single(answer(&request))
+                }
+                RequestPacket::Batch(requests) => {
+                    self.packets.lock().unwrap().push(requests.len());
+                    ResponsePacket::Batch(
+                        requests
+                            .iter()
+                            .rev()
+                            .filter(|request| !self.unanswered.contains(request.id()))
+                            .map(answer)
+                            .collect(),
+                    )
+                }
+            };
+            let down = self.down;
+            Box::pin(async move {
+                match down {
+                    true => Err(TransportErrorKind::custom_str("the node is down")),
+                    false => Ok(response),
+                }
+            })
+        }
+    }
+
+    fn request(id: u64) -> SerializedRequest {
+        Request::new(format!("method_{id}"), Id::Number(id), ())
+            .serialize()
+            .unwrap()
+    }
+
+    /// Queues the requests, returning where their responses are sent
+    fn pending(
+        ids: impl IntoIterator<Item = u64>,
+    ) -> (
+        Vec<Pending>,
+        Vec<oneshot::Receiver<Result<Response, TransportError>>>,
+    ) {
+        ids.into_iter()
+            .map(|id| {
+                let (tx, rx) = oneshot::channel();
+                ((request(id), tx), rx)
+            })
+            .unzip()
+    }
+
+    /// Returns the method answered to the request
+    fn method(response: Result<Response, TransportError>) -> String {
+        match response.unwrap().payload {
+            ResponsePayload::Success(result) => serde_json::from_str(result.get()).unwrap(),
+            ResponsePayload::Failure(err) => panic!("unexpected failure {err:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_the_responses_by_id() {
+        let node = Node {
+            unanswered: vec![Id::Number(3)],
+            ..Default::default()
+        };
+        let (batch, mut responses) = pending(1..=3);
+        send_batch(node.clone(), batch).await;
+
+        assert_eq!(*node.packets.lock().unwrap(), vec![3]);
+        let missing = responses.pop().unwrap().await.unwrap();
+        assert!(matches!(
+            missing,
+            Err(TransportError::Transport(
+                TransportErrorKind::MissingBatchResponse(Id::Number(3))
+            ))
+        ));
+        for (id, response) in (1..=2).zip(responses) {
+            assert_eq!(method(response.await.unwrap()), format!("method_{id}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_a_single_request_as_is() {
+        let node = Node::default();
+        let (batch, responses) = pending([7]);
+        send_batch(node.clone(), batch).await;
+
+        assert_eq!(*node.packets.lock().unwrap(), vec![1]);
+        for response in responses {
+            assert_eq!(method(response.await.unwrap()), "method_7");
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_every_request_of_a_failed_batch() {
+        let node = Node {
+            down: true,
+            ..Default::default()
+        };
+        let (batch, responses) = pending(1..=3);
+        send_batch(node, batch).await;
+
+        for response in responses {
+            let err = response.await.unwrap().unwrap_err();
+            assert!(err.to_string().contains("the node is down"));
+        }
+    }
+
+    #[test]
+    fn builds_outside_of_a_runtime() {
+        let _service = BatchLayer::new(10).layer(Node::default());
+    }
+
+    /// Readies and calls a clone of the service, which must have a slot in
+    /// the queue
+    fn call(
+        service: &BatchService<Node>,
+        id: u64,
+    ) -> <BatchService<Node> as Service<RequestPacket>>::Future {
+        let mut service = service.clone();
+        poll_fn(|cx| service.poll_ready(cx))
+            .now_or_never()
+            .expect("the queue is full")
+            .unwrap();
+        service.call(RequestPacket::Single(request(id)))
+    }
+
+    #[tokio::test]
+    async fn batches_the_concurrent_requests() {
+        let node = Node::default();
+        let service = BatchLayer::new(10).layer(node.clone());
+        let responses = (1..=3).map(|id| call(&service, id)).collect::<Vec<_>>();
+
+        for (id, response) in (1..=3).zip(join_all(responses).await) {
+            let ResponsePacket::Single(response) = response.unwrap() else {
+                panic!("unexpected batch");
+            };
+            assert_eq!(method(Ok(response)), format!("method_{id}"));
+        }
+        assert_eq!(*node.packets.lock().unwrap(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_slot_in_the_queue() {
+        let node = Node::default();
+        let service = BatchLayer::new(2).layer(node.clone());
+        // The batches are only sent once a response is awaited
+        let responses = (0..2 * QUEUED_BATCHES as u64)
+            .map(|id| call(&service, id))
+            .collect::<Vec<_>>();
+        let mut waiting = service.clone();
+        assert!(poll_fn(|cx| waiting.poll_ready(cx))
+            .now_or_never()
+            .is_none());
+
+        assert!(join_all(responses).await.iter().all(Result::is_ok));
+        poll_fn(|cx| waiting.poll_ready(cx)).await.unwrap();
+        assert_eq!(*node.packets.lock().unwrap(), vec![2; QUEUED_BATCHES]);
+    }
+}
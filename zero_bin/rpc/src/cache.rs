@@ -0,0 +1,736 @@
+//! A content-addressed on-disk cache of the RPC responses, so that fetching the
+//! same blocks again (i.e. for repeated benchmarks) does not query the node.
+//!
+//! Responses are keyed by the SHA-256 of the method and its params, and are
+//! only cached once they can no longer change: the request must be pinned to a
+//! finalized block (at least `finality_depth` blocks below the chain tip), to
+//! a block hash, or to a transaction of a finalized block.  The least recently
+//! used responses are evicted once the cache grows beyond `max_bytes`.
+//!
+//! In offline mode, requests are only served from the cache, and a miss fails
+//! the request rather than querying the node.
+use std::{
+    collections::HashSet,
+    fs::File,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
+};
+
+use alloy::{
+    rpc::json_rpc::{Id, Request, RequestPacket, Response, ResponsePacket, ResponsePayload},
+    transports::{TransportError, TransportErrorKind},
+};
+use anyhow::Context as _;
+use clap::{Args, ValueHint};
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Value};
+use sha2::{Digest, Sha256};
+use tower::{Layer, Service};
+use tracing::{debug, info, warn};
+
+/// The default maximum size of the cache, 10 GiB
+pub const DFLT_CACHE_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+/// The default number of blocks below the chain tip after which a block is
+/// considered finalized
+pub const DFLT_FINALITY_DEPTH: u64 = 64;
+/// How often the chain tip is queried again to find the finalized blocks
+const HEAD_REFRESH_INTERVAL: Duration = Duration::from_secs(12);
+/// The size the cache is brought back to when evicting, as a fraction of
+/// `max_bytes`
+const EVICTION_TARGET: f64 = 0.9;
+
+//==============================================================================
+// Configuration
+//==============================================================================
+
+/// The configuration of the [RpcCache]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// The directory the responses are stored in
+    pub dir: PathBuf,
+    /// The maximum size of the cache in bytes.  Default is
+    /// [DFLT_CACHE_MAX_BYTES].
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    /// Whether the requests are only served from the cache
+    #[serde(default)]
+    pub offline: bool,
+    /// The number of blocks below the chain tip after which a block is
+    /// considered finalized.  Default is [DFLT_FINALITY_DEPTH].
+    #[serde(default = "default_finality_depth")]
+    pub finality_depth: u64,
+}
+
+const fn default_max_bytes() -> u64 {
+    DFLT_CACHE_MAX_BYTES
+}
+
+const fn default_finality_depth() -> u64 {
+    DFLT_FINALITY_DEPTH
+}
+
+/// The command line arguments configuring the [RpcCache]
+#[derive(Args, Debug, Clone)]
+pub struct CacheArgs {
+    /// If provided, the RPC responses of finalized blocks are cached in this
+    /// directory.
+    #[arg(long, env = "ZERO_BIN_RPC_CACHE_DIR", value_hint = ValueHint::DirPath)]
+    pub rpc_cache_dir: Option<PathBuf>,
+    /// The maximum size of the RPC cache in bytes, beyond which the least
+    /// recently used responses are evicted.
+    #[arg(long, default_value_t = DFLT_CACHE_MAX_BYTES)]
+    pub rpc_cache_max_bytes: u64,
+    /// Only serve the RPC requests from the cache, failing on a miss rather
+    /// than querying the node.
+    #[arg(long, requires = "rpc_cache_dir")]
+    pub rpc_offline: bool,
+    /// The number of blocks below the chain tip after which a block is
+    /// considered finalized, and its responses cached.
+    #[arg(long, default_value_t = DFLT_FINALITY_DEPTH)]
+    pub rpc_cache_finality_depth: u64,
+}
+
+impl CacheArgs {
+    /// Returns the [CacheConfig], if the cache is enabled
+    pub fn config(&self) -> Option<CacheConfig> {
+        self.rpc_cache_dir.as_ref().map(|dir| CacheConfig {
+            dir: dir.clone(),
+            max_bytes: self.rpc_cache_max_bytes,
+            offline: self.rpc_offline,
+            finality_depth: self.rpc_cache_finality_depth,
+        })
+    }
+}
+
+//==============================================================================
+// Cache
+//==============================================================================
+
+/// What the response of a request depends on, deciding when it may be cached
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pinned {
+    /// The response never changes (i.e. it is pinned to a block hash)
+    Always,
+    /// The response is pinned to the block number
+    Block(u64),
+    /// The response is pinned to the transaction hash
+    Txn(String),
+    /// The response may change, and is never cached
+    Never,
+}
+
+impl Pinned {
+    /// Returns what the request is pinned to, given its method and params
+    fn of(method: &str, params: Option<&RawValue>) -> Self {
+        let params: Vec<Value> = params
+            .and_then(|params| serde_json::from_str(params.get()).ok())
+            .unwrap_or_default();
+        match method {
+            "eth_chainId" | "eth_getBlockByHash" => Self::Always,
+            "eth_getBlockByNumber" | "debug_traceBlockByNumber" | "eth_getWitness" => {
+                Self::of_block(params.first())
+            }
+            "eth_getProof" => Self::of_block(params.get(2)),
+            "debug_traceTransaction" | "eth_getTransactionReceipt" => params
+                .first()
+                .and_then(Value::as_str)
+                .map(|hash| Self::Txn(hash.to_lowercase()))
+                .unwrap_or(Self::Never),
+            _ => Self::Never,
+        }
+    }
+
+    /// Returns what a block parameter (a number, a tag or an EIP-1898 object)
+    /// is pinned to
+    fn of_block(param: Option<&Value>) -> Self {
+        match param {
+            Some(Value::String(tag)) if tag == "earliest" => Self::Block(0),
+            Some(Value::String(number)) => parse_quantity(number)
+                .map(Self::Block)
+                .unwrap_or(Self::Never),
+            Some(Value::Object(object)) if object.contains_key("blockHash") => Self::Always,
+            Some(Value::Object(object)) => Self::of_block(object.get("blockNumber")),
+            _ => Self::Never,
+        }
+    }
+}
+
+/// Parses a hex-encoded quantity, i.e. `0x1b4`
+fn parse_quantity(quantity: &str) -> Option<u64> {
+    u64::from_str_radix(quantity.strip_prefix("0x")?, 16).ok()
+}
+
+/// The on-disk cache of the RPC responses, shared by every [CacheService]
+/// built from it.
+#[derive(Debug, Clone)]
+pub struct RpcCache {
+    inner: Arc<CacheInner>,
+}
+
+#[derive(Debug)]
+struct CacheInner {
+    config: CacheConfig,
+    /// The current size of the cache, in bytes
+    size: AtomicU64,
+    /// Whether an eviction is in progress
+    evicting: AtomicBool,
+    /// The last finalized block number, along with when it was determined
+    finalized: tokio::sync::Mutex<(u64, Option<Instant>)>,
+    /// The hashes of the transactions of the finalized blocks seen so far
+    finalized_txns: Mutex<HashSet<String>>,
+}
+
+impl RpcCache {
+    /// Opens the cache in its directory, creating the directory if needed
+    pub fn open(config: CacheConfig) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.dir)
+            .with_context(|| format!("failed to create the RPC cache {:?}", config.dir))?;
+        let size = cached_files(&config.dir)?
+            .iter()
+            .map(|(_, size, _)| size)
+            .sum();
+        info!(
+            "Opened the RPC cache {:?} ({} bytes{})",
+            config.dir,
+            size,
+            if config.offline { ", offline" } else { "" }
+        );
+
+        Ok(Self {
+            inner: Arc::new(CacheInner {
+                config,
+                size: AtomicU64::new(size),
+                evicting: AtomicBool::new(false),
+                finalized: tokio::sync::Mutex::new((0, None)),
+                finalized_txns: Mutex::new(HashSet::new()),
+            }),
+        })
+    }
+
+    /// Returns true if the requests are only served from the cache
+    pub fn is_offline(&self) -> bool {
+        self.inner.config.offline
+    }
+
+    /// Returns the key of the request, the SHA-256 of its method and params
+    fn key(method: &str, params: Option<&RawValue>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(method.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(params.map(RawValue::get).unwrap_or_default().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Returns the path of the response of the key, sharded by the first byte
+    /// of the key
+    fn path(&self, key: &str) -> PathBuf {
+        self.inner
+            .config
+            .dir
+            .join(&key[..2])
+            .join(format!("{}.json", key))
+    }
+
+    /// Returns the cached response of the key, if any
+    async fn get(&self, key: &str) -> Option<Box<RawValue>> {
+        let path = self.path(key);
+        let data = tokio::fs::read_to_string(&path).await.ok()?;
+        // Refresh the modification time, which orders the eviction
+        let touched = tokio::task::spawn_blocking(move || {
+            if let Err(err) = File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()))
+            {
+                debug!("Failed to touch {:?}: {}", path, err);
+            }
+        });
+        let _ = touched.await;
+        RawValue::from_string(data).ok()
+    }
+
+    /// Caches the response of the key
+    async fn put(&self, key: &str, result: &RawValue) {
+        let path = self.path(key);
+        let tmp_path = path.with_extension("json.tmp");
+        let data = result.get().as_bytes();
+        let written = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&tmp_path, data).await?;
+            tokio::fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if let Err(err) = written {
+            warn!("Failed to cache the RPC response {:?}: {}", path, err);
+            return;
+        }
+
+        let size = self
+            .inner
+            .size
+            .fetch_add(data.len() as u64, Ordering::SeqCst)
+            + data.len() as u64;
+        if size > self.inner.config.max_bytes && !self.inner.evicting.swap(true, Ordering::SeqCst) {
+            let cache = self.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(err) = cache.evict() {
+                    warn!("Failed to evict from the RPC cache: {:#}", err);
+                }
+                cache.inner.evicting.store(false, Ordering::SeqCst);
+            });
+        }
+    }
+
+    /// Removes the least recently used responses until the cache is back
+    /// below its [EVICTION_TARGET]
+    fn evict(&self) -> anyhow::Result<()> {
+        let mut files = cached_files(&self.inner.config.dir)?;
+        files.sort_by_key(|(_, _, modified)| *modified);
+
+        let target = (self.inner.config.max_bytes as f64 * EVICTION_TARGET) as u64;
+        let mut size: u64 = files.iter().map(|(_, size, _)| size).sum();
+        let mut evicted = 0;
+        for (path, file_size, _) in files {
+            if size <= target {
+                break;
+            }
+            std::fs::remove_file(&path)?;
+            size -= file_size;
+            evicted += 1;
+        }
+        self.inner.size.store(size, Ordering::SeqCst);
+        info!(
+            "Evicted {} response(s) from the RPC cache, now {} bytes",
+            evicted, size
+        );
+        Ok(())
+    }
+
+    /// Records the transactions of a finalized block response, so that the
+    /// responses pinned to them can be cached
+    fn learn_txns(&self, method: &str, result: &RawValue) {
+        if method != "eth_getBlockByNumber" {
+            return;
+        }
+        let Ok(block) = serde_json::from_str::<Value>(result.get()) else {
+            return;
+        };
+        let hashes = block
+            .get("transactions")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|txn| match txn {
+                Value::String(hash) => Some(hash.to_lowercase()),
+                txn => txn.get("hash")?.as_str().map(str::to_lowercase),
+            });
+        self.inner
+            .finalized_txns
+            .lock()
+            .expect("RPC cache lock poisoned")
+            .extend(hashes);
+    }
+
+    /// Returns true if the response of a request pinned to `pinned` can no
+    /// longer change, querying the chain tip through `service` if needed,
+    /// once it is ready
+    async fn is_final<S>(&self, pinned: &Pinned, service: &mut S) -> bool
+    where
+        S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>,
+    {
+        match pinned {
+            Pinned::Always => true,
+            Pinned::Never => false,
+            Pinned::Txn(hash) => self
+                .inner
+                .finalized_txns
+                .lock()
+                .expect("RPC cache lock poisoned")
+                .contains(hash),
+            Pinned::Block(block_number) => {
+                let mut finalized = self.inner.finalized.lock().await;
+                let stale = finalized
+                    .1
+                    .map_or(true, |updated| updated.elapsed() > HEAD_REFRESH_INTERVAL);
+                if *block_number > finalized.0 && stale {
+                    match chain_tip(service).await {
+                        Ok(tip) => {
+                            *finalized = (
+                                tip.saturating_sub(self.inner.config.finality_depth),
+                                Some(Instant::now()),
+                            );
+                            debug!("Blocks up to {} are finalized", finalized.0);
+                        }
+                        Err(err) => warn!("Failed to query the chain tip: {}", err),
+                    }
+                }
+                *block_number <= finalized.0
+            }
+        }
+    }
+}
+
+/// Returns the path, size and modification time of every cached response
+fn cached_files(dir: &Path) -> anyhow::Result<Vec<(PathBuf, u64, SystemTime)>> {
+    let mut files = Vec::new();
+    for shard in std::fs::read_dir(dir)?.flatten() {
+        if !shard.path().is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(shard.path())?.flatten() {
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                files.push((entry.path(), metadata.len(), metadata.modified()?));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Queries the number of the latest block
+async fn chain_tip<S>(service: &mut S) -> Result<u64, TransportError>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>,
+{
+    let request = Request::new("eth_blockNumber", Id::Number(0), ())
+        .serialize()
+        .map_err(TransportError::ser_err)?;
+    futures::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+    match service.call(RequestPacket::Single(request)).await? {
+        ResponsePacket::Single(Response {
+            payload: ResponsePayload::Success(result),
+            ..
+        }) => serde_json::from_str::<String>(result.get())
+            .ok()
+            .and_then(|number| parse_quantity(&number))
+            .ok_or_else(|| TransportErrorKind::custom_str("invalid `eth_blockNumber` response")),
+        _ => Err(TransportErrorKind::custom_str(
+            "unexpected `eth_blockNumber` response",
+        )),
+    }
+}
+
+//==============================================================================
+// Layer
+//==============================================================================
+
+/// CacheLayer
+pub struct CacheLayer {
+    cache: Option<RpcCache>,
+}
+
+impl CacheLayer {
+    /// Returns the layer serving the requests from the cache, or passing them
+    /// through if there is none
+    pub const fn new(cache: Option<RpcCache>) -> Self {
+        Self { cache }
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = CacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CacheService {
+            inner,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+/// CacheService
+#[derive(Debug, Clone)]
+pub struct CacheService<S> {
+    inner: S,
+    cache: Option<RpcCache>,
+}
+
+impl<S> Service<RequestPacket> for CacheService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + 'static
+        + Clone,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        // The chain tip is queried through a service of its own, as `inner`
+        // was only made ready for `req`
+        let mut tip_service = self.inner.clone();
+        let cache = self.cache.clone();
+
+        Box::pin(async move {
+            let Some(cache) = cache else {
+                return inner.call(req).await;
+            };
+            let request = match &req {
+                RequestPacket::Single(request) => request.clone(),
+                RequestPacket::Batch(_) if cache.is_offline() => {
+                    return Err(TransportErrorKind::custom_str(
+                        "batch requests cannot be served from the RPC cache",
+                    ))
+                }
+                RequestPacket::Batch(_) => return inner.call(req).await,
+            };
+
+            let method = request.method().to_string();
+            let key = RpcCache::key(&method, request.params());
+            if let Some(result) = cache.get(&key).await {
+                debug!("Serving {} from the RPC cache", method);
+                cache.learn_txns(&method, &result);
+                return Ok(ResponsePacket::Single(Response {
+                    id: request.id().clone(),
+                    payload: ResponsePayload::Success(result),
+                }));
+            }
+            if cache.is_offline() {
+                return Err(TransportErrorKind::custom_str(&format!(
+                    "{} {} is not in the RPC cache",
+                    method,
+                    request.params().map(RawValue::get).unwrap_or_default()
+                )));
+            }
+
+            let pinned = Pinned::of(&method, request.params());
+            let response = inner.call(req).await?;
+            if let ResponsePacket::Single(Response {
+                payload: ResponsePayload::Success(result),
+                ..
+            }) = &response
+            {
+                // A `null` result is a block or transaction that does not
+                // exist yet
+                if result.get() != "null" && cache.is_final(&pinned, &mut tip_service).await {
+                    cache.learn_txns(&method, result);
+                    cache.put(&key, result).await;
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    fn params(json: &str) -> Box<RawValue> {
+        RawValue::from_string(json.to_string()).unwrap()
+    }
+
+    fn pinned(method: &str, json: &str) -> Pinned {
+        Pinned::of(method, Some(&*params(json)))
+    }
+
+    /// Opens an empty cache in a temporary directory
+    fn cache(name: &str, max_bytes: u64) -> RpcCache {
+        let dir = std::env::temp_dir().join(format!("rpc-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        RpcCache::open(CacheConfig {
+            dir,
+            max_bytes,
+            offline: false,
+            finality_depth: 10,
+        })
+        .unwrap()
+    }
+
+    /// Writes a response of `size` bytes (a JSON string) last used at
+    /// `modified`
+    fn write(cache: &RpcCache, key: &str, size: usize, modified: SystemTime) -> PathBuf {
+        let path = cache.path(key);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, format!("\"{}\"", "0".repeat(size - 2))).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+        path
+    }
+
+    /// Answers `eth_blockNumber` with the chain tip, checking that it was
+    /// polled ready before every call
+    #[derive(Clone)]
+    struct ChainTip {
+        tip: u64,
+        ready: bool,
+        calls: Arc<AtomicU64>,
+    }
+
+    impl Service<RequestPacket> for ChainTip {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.ready = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: RequestPacket) -> Self::Future {
+            assert!(std::mem::take(&mut self.ready), "called before ready");
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let RequestPacket::Single(request) = req else {
+                panic!("unexpected batch");
+            };
+            assert_eq!(request.method(), "eth_blockNumber");
+            let result = params(&format!("\"0x{:x}\"", self.tip));
+            Box::pin(async move {
+                Ok(ResponsePacket::Single(Response {
+                    id: request.id().clone(),
+                    payload: ResponsePayload::Success(result),
+                }))
+            })
+        }
+    }
+
+    #[test]
+    fn parses_quantities() {
+        assert_eq!(parse_quantity("0x1b4"), Some(436));
+        assert_eq!(parse_quantity("0x0"), Some(0));
+        assert_eq!(parse_quantity("1b4"), None);
+        assert_eq!(parse_quantity("0xzz"), None);
+    }
+
+    #[test]
+    fn pins_requests() {
+        assert_eq!(Pinned::of("eth_chainId", None), Pinned::Always);
+        assert_eq!(
+            pinned("eth_getBlockByHash", r#"["0xab", true]"#),
+            Pinned::Always
+        );
+        assert_eq!(
+            pinned("eth_getBlockByNumber", r#"["0x10", true]"#),
+            Pinned::Block(16)
+        );
+        assert_eq!(
+            pinned("debug_traceBlockByNumber", r#"["earliest", {}]"#),
+            Pinned::Block(0)
+        );
+        assert_eq!(
+            pinned("eth_getBlockByNumber", r#"["latest", true]"#),
+            Pinned::Never
+        );
+        assert_eq!(
+            pinned("eth_getProof", r#"["0x01", [], {"blockHash": "0xab"}]"#),
+            Pinned::Always
+        );
+        assert_eq!(
+            pinned("eth_getProof", r#"["0x01", [], {"blockNumber": "0x2"}]"#),
+            Pinned::Block(2)
+        );
+        assert_eq!(
+            pinned("debug_traceTransaction", r#"["0xAB", {}]"#),
+            Pinned::Txn(String::from("0xab"))
+        );
+        assert_eq!(Pinned::of("eth_blockNumber", None), Pinned::Never);
+    }
+
+    #[test]
+    fn keys_requests_by_method_and_params() {
+        let key = RpcCache::key("eth_getBlockByNumber", Some(&*params(r#"["0x10"]"#)));
+        assert_eq!(key.len(), 64);
+        assert_eq!(
+            key,
+            RpcCache::key("eth_getBlockByNumber", Some(&*params(r#"["0x10"]"#)))
+        );
+        assert_ne!(
+            key,
+            RpcCache::key("eth_getBlockByNumber", Some(&*params(r#"["0x11"]"#)))
+        );
+        assert_ne!(
+            key,
+            RpcCache::key("eth_getBlockByHash", Some(&*params(r#"["0x10"]"#)))
+        );
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_responses() {
+        let cache = cache("evict", 100);
+        let paths: Vec<_> = (0..4)
+            .map(|i| {
+                let key = RpcCache::key(&format!("method{i}"), None);
+                write(&cache, &key, 40, UNIX_EPOCH + Duration::from_secs(i))
+            })
+            .collect();
+
+        cache.evict().unwrap();
+        // Evicted down to 90% of the maximum size
+        assert!(!paths[0].exists());
+        assert!(!paths[1].exists());
+        assert!(paths[2].exists());
+        assert!(paths[3].exists());
+        assert_eq!(cache.inner.size.load(Ordering::SeqCst), 80);
+    }
+
+    #[tokio::test]
+    async fn refreshes_the_responses_it_serves() {
+        let cache = cache("get", 100);
+        let key = RpcCache::key("eth_chainId", None);
+        let path = write(&cache, &key, 4, UNIX_EPOCH);
+
+        assert_eq!(cache.get(&key).await.unwrap().get(), r#""00""#);
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert!(modified > UNIX_EPOCH + Duration::from_secs(1));
+        assert!(cache
+            .get(&RpcCache::key("eth_blockNumber", None))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn finds_the_final_responses() {
+        let cache = cache("final", 100);
+        let calls = Arc::new(AtomicU64::new(0));
+        let mut service = ChainTip {
+            tip: 100,
+            ready: false,
+            calls: calls.clone(),
+        };
+
+        assert!(cache.is_final(&Pinned::Always, &mut service).await);
+        assert!(!cache.is_final(&Pinned::Never, &mut service).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // Blocks are final at the finality depth below the chain tip, which
+        // is only queried again once stale
+        assert!(cache.is_final(&Pinned::Block(90), &mut service).await);
+        assert!(!cache.is_final(&Pinned::Block(91), &mut service).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let txn = Pinned::Txn(String::from("0xab"));
+        assert!(!cache.is_final(&txn, &mut service).await);
+        cache.learn_txns(
+            "eth_getBlockByNumber",
+            &params(r#"{"transactions": ["0xAB", {"hash": "0xCD"}]}"#),
+        );
+        assert!(cache.is_final(&txn, &mut service).await);
+        assert!(
+            cache
+                .is_final(&Pinned::Txn(String::from("0xcd")), &mut service)
+                .await
+        );
+    }
+}
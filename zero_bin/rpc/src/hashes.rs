@@ -0,0 +1,133 @@
+//! A rolling window of block hashes, so that the previous block hashes of
+//! consecutive blocks are not fetched again for every block.
+//!
+//! The window records the hash and parent hash of every block fetched.  The
+//! previous hashes of a block are read by following the parent hashes from
+//! its own parent, so a block recorded from another fork (i.e. before a reorg)
+//! is never used: the walk stops at the first block missing from the window,
+//! or whose hash does not match the parent hash of its child, and only the
+//! blocks below it need to be fetched.
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use alloy::primitives::B256;
+
+use crate::PREVIOUS_HASHES_COUNT;
+
+/// The default number of blocks held by a [BlockHashWindow], enough for a few
+/// jobs proving distinct ranges at once
+pub const DFLT_WINDOW_CAPACITY: usize = 4 * PREVIOUS_HASHES_COUNT;
+
+/// The hash and parent hash of the recorded blocks, by block number, shared by
+/// the clones of the window
+#[derive(Debug, Clone)]
+pub struct BlockHashWindow {
+    capacity: usize,
+    blocks: Arc<Mutex<BTreeMap<u64, (B256, B256)>>>,
+}
+
+impl Default for BlockHashWindow {
+    fn default() -> Self {
+        Self::new(DFLT_WINDOW_CAPACITY)
+    }
+}
+
+impl BlockHashWindow {
+    /// Returns an empty window holding up to `capacity` blocks, evicting the
+    /// lowest block numbers first
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            blocks: Default::default(),
+        }
+    }
+
+    /// Records the hash and parent hash of a block
+    pub fn insert(&self, block_number: u64, hash: B256, parent_hash: B256) {
+        let mut blocks = self.blocks.lock().expect("block hash window lock poisoned");
+        blocks.insert(block_number, (hash, parent_hash));
+        while blocks.len() > self.capacity {
+            blocks.pop_first();
+        }
+    }
+
+    /// Returns the hashes of up to `count` blocks preceding the block
+    /// `block_number` with the given parent hash, starting with its parent and
+    /// going back for as long as the window holds the chain of parents
+    pub fn ancestors(&self, block_number: u64, parent_hash: B256, count: usize) -> Vec<B256> {
+        let count = count.min(block_number as usize);
+        let blocks = self.blocks.lock().expect("block hash window lock poisoned");
+
+        let mut hashes = Vec::with_capacity(count);
+        let mut hash = parent_hash;
+        while hashes.len() < count {
+            hashes.push(hash);
+            let ancestor = block_number - hashes.len() as u64;
+            match blocks.get(&ancestor) {
+                Some((recorded, parent_hash)) if *recorded == hash => hash = *parent_hash,
+                _ => break,
+            }
+        }
+        hashes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hash(block_number: u64) -> B256 {
+        B256::left_padding_from(&block_number.to_be_bytes())
+    }
+
+    fn window_of(block_numbers: std::ops::Range<u64>) -> BlockHashWindow {
+        let window = BlockHashWindow::default();
+        for block_number in block_numbers {
+            window.insert(block_number, hash(block_number), hash(block_number - 1));
+        }
+        window
+    }
+
+    #[test]
+    fn follows_the_chain_of_parents() {
+        let window = window_of(700..1000);
+        let ancestors = window.ancestors(1000, hash(999), PREVIOUS_HASHES_COUNT);
+        assert_eq!(ancestors, (744..1000).rev().map(hash).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_at_a_missing_block() {
+        let window = window_of(900..1000);
+        let ancestors = window.ancestors(1000, hash(999), PREVIOUS_HASHES_COUNT);
+        // The hash of block 899 is known as the parent of block 900
+        assert_eq!(ancestors, (899..1000).rev().map(hash).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_at_a_block_of_another_fork() {
+        let window = window_of(700..1000);
+        window.insert(995, B256::repeat_byte(0xff), hash(994));
+        let ancestors = window.ancestors(1000, hash(999), PREVIOUS_HASHES_COUNT);
+        assert_eq!(ancestors, (995..1000).rev().map(hash).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_at_genesis() {
+        let window = window_of(1..10);
+        window.insert(0, hash(0), B256::ZERO);
+        let ancestors = window.ancestors(10, hash(9), PREVIOUS_HASHES_COUNT);
+        assert_eq!(ancestors, (0..10).rev().map(hash).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn evicts_the_lowest_blocks() {
+        let window = BlockHashWindow::new(10);
+        for block_number in 1..=20 {
+            window.insert(block_number, hash(block_number), hash(block_number - 1));
+        }
+        let ancestors = window.ancestors(21, hash(20), PREVIOUS_HASHES_COUNT);
+        assert_eq!(ancestors, (10..=20).rev().map(hash).collect::<Vec<_>>());
+    }
+}
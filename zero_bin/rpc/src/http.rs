@@ -0,0 +1,172 @@
+//! The HTTP transport of the providers.
+//!
+//! Unlike alloy's own HTTP transport, a response with an error status is
+//! returned as an [HttpStatusError] holding the status and the `Retry-After`
+//! of the response, so that the [crate::retry::RetryService] can tell a
+//! rate-limited request from a permanent failure.
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use alloy::{
+    rpc::json_rpc::{RequestPacket, ResponsePacket},
+    transports::{
+        http::reqwest::{self, header::RETRY_AFTER, StatusCode},
+        RpcError, TransportError, TransportErrorKind,
+    },
+};
+use chrono::{DateTime, Utc};
+use tower::Service;
+use url::Url;
+
+/// The maximum length of the body kept in an [HttpStatusError]
+const MAX_ERROR_BODY_LEN: usize = 512;
+
+/// Returned when the node answers with an error status
+#[derive(Debug)]
+pub struct HttpStatusError {
+    /// The status of the response
+    pub status: StatusCode,
+    /// How long to wait before retrying, as per the `Retry-After` of the
+    /// response
+    pub retry_after: Option<Duration>,
+    /// The beginning of the body of the response
+    pub body: String,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}", self.status)?;
+        if let Some(retry_after) = self.retry_after {
+            write!(f, " (retry after {:?})", retry_after)?;
+        }
+        if !self.body.is_empty() {
+            write!(f, ": {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Returns the error of type `E` held by a custom transport error, i.e. an
+/// [HttpStatusError] or a [reqwest::Error]
+pub(crate) fn custom_error<E: std::error::Error + 'static>(err: &TransportError) -> Option<&E> {
+    match err {
+        RpcError::Transport(TransportErrorKind::Custom(err)) => err.downcast_ref(),
+        _ => None,
+    }
+}
+
+/// Parses a `Retry-After` header, either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    match value.trim().parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => DateTime::parse_from_rfc2822(value.trim()).ok().map(|date| {
+            (date.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        }),
+    }
+}
+
+/// HttpTransport
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl HttpTransport {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    /// Returns true if the url points to the local machine
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self.url.host_str(),
+            Some("localhost" | "127.0.0.1" | "[::1]")
+        )
+    }
+
+    async fn send(self, req: RequestPacket) -> Result<ResponsePacket, TransportError> {
+        let response = self
+            .client
+            .post(self.url)
+            .json(&req)
+            .send()
+            .await
+            .map_err(TransportErrorKind::custom)?;
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.bytes().await.map_err(TransportErrorKind::custom)?;
+
+        if !status.is_success() {
+            let body = String::from_utf8_lossy(&body);
+            return Err(TransportErrorKind::custom(HttpStatusError {
+                status,
+                retry_after,
+                body: body.chars().take(MAX_ERROR_BODY_LEN).collect(),
+            }));
+        }
+        serde_json::from_slice(&body)
+            .map_err(|err| TransportError::deser_err(err, String::from_utf8_lossy(&body)))
+    }
+}
+
+impl Service<RequestPacket> for HttpTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().send(req))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 5 "), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn parses_retry_after_dates() {
+        let date = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let retry_after = parse_retry_after(&date).unwrap();
+        assert!(retry_after <= Duration::from_secs(60));
+        assert!(retry_after >= Duration::from_secs(58));
+
+        // A date in the past does not delay the retry
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_retry_after() {
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after(""), None);
+    }
+}
@@ -9,6 +9,7 @@ use trace_decoder::trace_protocol::{
 };
 
 use super::fetch_other_block_data;
+use crate::hashes::BlockHashWindow;
 
 /// Transaction traces retrieved from Erigon zeroTracer.
 #[derive(Debug, Deserialize)]
@@ -26,6 +27,7 @@ pub async fn block_prover_input<ProviderT, TransportT>(
     provider: ProviderT,
     target_block_id: BlockId,
     checkpoint_state_trie_root: B256,
+    hash_window: &BlockHashWindow,
 ) -> anyhow::Result<BlockProverInput>
 where
     ProviderT: Provider<TransportT>,
@@ -44,8 +46,13 @@ where
         .raw_request::<_, ZeroBlockWitness>("eth_getWitness".into(), vec![target_block_id])
         .await?;
 
-    let other_data =
-        fetch_other_block_data(provider, target_block_id, checkpoint_state_trie_root).await?;
+    let other_data = fetch_other_block_data(
+        provider,
+        target_block_id,
+        checkpoint_state_trie_root,
+        hash_window,
+    )
+    .await?;
 
     // Assemble
     Ok(BlockProverInput {
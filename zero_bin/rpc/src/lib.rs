@@ -11,23 +11,83 @@ use clap::ValueEnum;
 use compat::Compat;
 use evm_arithmetization::proof::{BlockHashes, BlockMetadata};
 use futures::{StreamExt as _, TryStreamExt as _};
-use prover::ProverInput;
+use prover::{BlockProverInput, ProverInput};
+use serde::{Deserialize, Serialize};
 use trace_decoder::types::{BlockLevelData, OtherBlockData};
+use tracing::debug;
 use zero_bin_common::block_interval::BlockInterval;
 
+use crate::hashes::BlockHashWindow;
+
+pub mod batch;
+pub mod cache;
+pub mod hashes;
+pub mod http;
 pub mod jerigon;
 pub mod native;
+pub mod provider;
 pub mod retry;
+pub mod throttle;
 
 const PREVIOUS_HASHES_COUNT: usize = 256;
 
 /// The RPC type.
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RpcType {
+    #[default]
     Jerigon,
     Native,
 }
 
+/// Obtain the state trie root of the checkpoint block
+pub async fn checkpoint_state_trie_root<ProviderT, TransportT>(
+    provider: &ProviderT,
+    checkpoint_block_id: BlockId,
+) -> anyhow::Result<B256>
+where
+    ProviderT: Provider<TransportT>,
+    TransportT: Transport + Clone,
+{
+    Ok(provider
+        .get_block(checkpoint_block_id, BlockTransactionsKind::Hashes)
+        .await?
+        .context("block does not exist")?
+        .header
+        .state_root)
+}
+
+/// Obtain the prover input for a single block.  The previous block hashes are
+/// read from the window where possible, and the fetched ones are recorded in
+/// it.
+pub async fn block_prover_input<ProviderT, TransportT>(
+    provider: &ProviderT,
+    block_id: BlockId,
+    checkpoint_state_trie_root: B256,
+    rpc_type: &RpcType,
+    hash_window: &BlockHashWindow,
+) -> anyhow::Result<BlockProverInput>
+where
+    ProviderT: Provider<TransportT>,
+    TransportT: Transport + Clone,
+{
+    match rpc_type {
+        RpcType::Jerigon => {
+            jerigon::block_prover_input(
+                &provider,
+                block_id,
+                checkpoint_state_trie_root,
+                hash_window,
+            )
+            .await
+        }
+        RpcType::Native => {
+            native::block_prover_input(&provider, block_id, checkpoint_state_trie_root, hash_window)
+                .await
+        }
+    }
+}
+
 /// Obtain the prover input for a given block interval
 pub async fn prover_input<ProviderT, TransportT>(
     provider: &ProviderT,
@@ -40,26 +100,24 @@ where
     TransportT: Transport + Clone,
 {
     // Grab interval checkpoint block state trie
-    let checkpoint_state_trie_root = provider
-        .get_block(checkpoint_block_id, BlockTransactionsKind::Hashes)
-        .await?
-        .context("block does not exist")?
-        .header
-        .state_root;
+    let checkpoint_state_trie_root =
+        checkpoint_state_trie_root(provider, checkpoint_block_id).await?;
 
+    // Consecutive blocks share most of their previous block hashes
+    let hash_window = BlockHashWindow::default();
     let mut block_proofs = Vec::new();
     let mut block_interval = block_interval.into_bounded_stream()?;
 
     while let Some(block_num) = block_interval.next().await {
         let block_id = BlockId::Number(BlockNumberOrTag::Number(block_num));
-        let block_prover_input = match rpc_type {
-            RpcType::Jerigon => {
-                jerigon::block_prover_input(&provider, block_id, checkpoint_state_trie_root).await?
-            }
-            RpcType::Native => {
-                native::block_prover_input(&provider, block_id, checkpoint_state_trie_root).await?
-            }
-        };
+        let block_prover_input = block_prover_input(
+            provider,
+            block_id,
+            checkpoint_state_trie_root,
+            &rpc_type,
+            &hash_window,
+        )
+        .await?;
 
         block_proofs.push(block_prover_input);
     }
@@ -84,13 +142,11 @@ where
     TransportT: Transport + Clone,
 {
     // Grab interval checkpoint block state trie
-    let checkpoint_state_trie_root = provider
-        .get_block(checkpoint_block_id, BlockTransactionsKind::Hashes)
-        .await?
-        .context("block does not exist")?
-        .header
-        .state_root;
+    let checkpoint_state_trie_root =
+        checkpoint_state_trie_root(provider, checkpoint_block_id).await?;
 
+    // Consecutive blocks share most of their previous block hashes
+    let hash_window = BlockHashWindow::default();
     let mut block_proofs = Vec::new();
     let mut block_interval = block_interval.into_bounded_stream()?;
     let mut fetch_times = Vec::new();
@@ -98,14 +154,14 @@ where
     while let Some(block_num) = block_interval.next().await {
         let start = Instant::now();
         let block_id = BlockId::Number(BlockNumberOrTag::Number(block_num));
-        let block_prover_input = match rpc_type {
-            RpcType::Jerigon => {
-                jerigon::block_prover_input(&provider, block_id, checkpoint_state_trie_root).await?
-            }
-            RpcType::Native => {
-                native::block_prover_input(&provider, block_id, checkpoint_state_trie_root).await?
-            }
-        };
+        let block_prover_input = block_prover_input(
+            provider,
+            block_id,
+            checkpoint_state_trie_root,
+            &rpc_type,
+            &hash_window,
+        )
+        .await?;
         fetch_times.push(start.elapsed());
         block_proofs.push(block_prover_input);
     }
@@ -117,11 +173,13 @@ where
     })
 }
 
-/// Fetches other block data
+/// Fetches other block data, reading the previous block hashes from the
+/// window where possible
 async fn fetch_other_block_data<ProviderT, TransportT>(
     provider: ProviderT,
     target_block_id: BlockId,
     checkpoint_state_trie_root: B256,
+    hash_window: &BlockHashWindow,
 ) -> anyhow::Result<OtherBlockData>
 where
     ProviderT: Provider<TransportT>,
@@ -137,54 +195,48 @@ where
         .context("target block is missing field `number`")?;
     let chain_id = provider.get_chain_id().await?;
 
-    let previous_block_numbers =
-        std::iter::successors(Some(target_block_number as i128 - 1), |&it| Some(it - 1))
-            .take(PREVIOUS_HASHES_COUNT)
-            .filter(|i| *i >= 0)
-            .collect::<Vec<_>>();
-    let concurrency = previous_block_numbers.len();
-    let collected_hashes = futures::stream::iter(
-        previous_block_numbers
-            .chunks(2) // we get hash for previous and current block with one request
-            .map(|block_numbers| {
+    // Only the previous blocks missing from the window are fetched, i.e. a
+    // single block when the previous block was fetched before
+    let count = PREVIOUS_HASHES_COUNT.min(target_block_number as usize);
+    if let Some(hash) = target_block.header.hash {
+        hash_window.insert(target_block_number, hash, target_block.header.parent_hash);
+    }
+    let mut hashes =
+        hash_window.ancestors(target_block_number, target_block.header.parent_hash, count);
+    if hashes.len() < count {
+        // The hash of the lowest block reached is known, but not its parent
+        let lowest_known = target_block_number - hashes.len() as u64;
+        let lowest_needed = target_block_number - count as u64;
+        debug!(
+            "Fetching blocks {}..={} for the previous hashes of block {}",
+            lowest_needed, lowest_known, target_block_number
+        );
+        let fetched_hashes =
+            futures::stream::iter((lowest_needed..=lowest_known).rev().map(|block_number| {
                 let provider = &provider;
-                let block_num = &block_numbers[0];
-                let previos_block_num = if block_numbers.len() > 1 {
-                    Some(block_numbers[1])
-                } else {
-                    // For genesis block
-                    None
-                };
                 async move {
                     let block = provider
-                        .get_block((*block_num as u64).into(), BlockTransactionsKind::Hashes)
+                        .get_block(block_number.into(), BlockTransactionsKind::Hashes)
                         .await
                         .context("couldn't get block")?
                         .context("no such block")?;
-                    anyhow::Ok([
-                        (block.header.hash, Some(*block_num)),
-                        (Some(block.header.parent_hash), previos_block_num),
-                    ])
+                    let hash = block.header.hash.context("block is missing field `hash`")?;
+                    hash_window.insert(block_number, hash, block.header.parent_hash);
+                    anyhow::Ok(hash)
                 }
-            }),
-    )
-    .buffered(concurrency)
-    .try_collect::<Vec<_>>()
-    .await
-    .context("couldn't fill previous hashes")?;
+            }))
+            .buffered(PREVIOUS_HASHES_COUNT)
+            .try_collect::<Vec<_>>()
+            .await
+            .context("couldn't fill previous hashes")?;
+        hashes.extend(fetched_hashes.into_iter().skip(1));
+    }
 
+    // Most recent previous block hash is expected at the end of the array
     let mut prev_hashes = [B256::ZERO; PREVIOUS_HASHES_COUNT];
-    collected_hashes
-        .into_iter()
-        .flatten()
-        .for_each(|(hash, block_num)| {
-            if let (Some(hash), Some(block_num)) = (hash, block_num) {
-                // Most recent previous block hash is expected at the end of the array
-                prev_hashes
-                    [PREVIOUS_HASHES_COUNT - (target_block_number - block_num as u64) as usize] =
-                    hash;
-            }
-        });
+    for (i, hash) in hashes.into_iter().enumerate() {
+        prev_hashes[PREVIOUS_HASHES_COUNT - 1 - i] = hash;
+    }
 
     let other_data = OtherBlockData {
         b_data: BlockLevelData {
@@ -2,7 +2,12 @@ use std::io;
 
 use alloy::rpc::types::eth::BlockId;
 use clap::{Parser, ValueHint};
-use rpc::{retry::build_http_retry_provider, RpcType};
+use rpc::{
+    cache::{CacheArgs, RpcCache},
+    provider::build_retry_provider,
+    throttle::{Throttle, ThrottleArgs},
+    RpcType,
+};
 use tracing_subscriber::{prelude::*, EnvFilter};
 use url::Url;
 use zero_bin_common::block_interval::BlockInterval;
@@ -17,7 +22,8 @@ pub enum Cli {
         // End block of interval to fetch
         #[arg(short, long)]
         end_block: u64,
-        /// The RPC URL.
+        /// The RPC URL: `http(s)://`, `ws(s)://` or `ipc://` followed by the
+        /// path of the socket.
         #[arg(short = 'u', long, value_hint = ValueHint::Url)]
         rpc_url: Url,
         /// The RPC Tracer Type
@@ -27,12 +33,17 @@ pub enum Cli {
         /// block before the `start_block` is the checkpoint
         #[arg(short, long)]
         checkpoint_block_number: Option<BlockId>,
-        /// Backoff in milliseconds for request retries
+        /// Backoff in milliseconds before the first retry of a failed request,
+        /// doubled on every retry
         #[arg(long, default_value_t = 0)]
         backoff: u64,
-        /// The maximum number of retries
+        /// The maximum number of retries of a failed request
         #[arg(long, default_value_t = 0)]
         max_retries: u32,
+        #[command(flatten)]
+        cache: CacheArgs,
+        #[command(flatten)]
+        throttle: ThrottleArgs,
     },
 }
 
@@ -48,14 +59,25 @@ impl Cli {
                 checkpoint_block_number,
                 backoff,
                 max_retries,
+                cache,
+                throttle,
             } => {
                 let checkpoint_block_number =
                     checkpoint_block_number.unwrap_or((start_block - 1).into());
                 let block_interval = BlockInterval::Range(start_block..end_block + 1);
 
+                let cache = cache.config().map(RpcCache::open).transpose()?;
+
                 // Retrieve prover input from the Erigon node
                 let prover_input = rpc::prover_input(
-                    &build_http_retry_provider(rpc_url, backoff, max_retries),
+                    &build_retry_provider(
+                        rpc_url,
+                        backoff,
+                        max_retries,
+                        cache,
+                        Throttle::new(throttle.config()),
+                    )
+                    .await?,
                     block_interval,
                     checkpoint_block_number,
                     rpc_type,
@@ -11,6 +11,8 @@ use futures::try_join;
 use prover::BlockProverInput;
 use trace_decoder::trace_protocol::BlockTrace;
 
+use crate::hashes::BlockHashWindow;
+
 mod state;
 mod txn;
 
@@ -21,6 +23,7 @@ pub async fn block_prover_input<ProviderT, TransportT>(
     provider: &ProviderT,
     block_number: BlockId,
     checkpoint_state_trie_root: B256,
+    hash_window: &BlockHashWindow,
 ) -> anyhow::Result<BlockProverInput>
 where
     ProviderT: Provider<TransportT>,
@@ -28,7 +31,12 @@ where
 {
     let (block_trace, other_data) = try_join!(
         process_block_trace(&provider, block_number),
-        crate::fetch_other_block_data(&provider, block_number, checkpoint_state_trie_root,)
+        crate::fetch_other_block_data(
+            &provider,
+            block_number,
+            checkpoint_state_trie_root,
+            hash_window
+        )
     )?;
 
     Ok(BlockProverInput {
@@ -0,0 +1,203 @@
+//! Builds the providers of the RPC node, over HTTP, WebSocket or IPC as per
+//! the scheme of its url:
+//!
+//! - `http://` and `https://` for HTTP,
+//! - `ws://` and `wss://` for WebSocket,
+//! - `ipc://` followed by the path of the Unix socket for IPC, i.e.
+//!   `ipc:///var/lib/geth/geth.ipc`.
+//!
+//! Over WebSocket and IPC, the node can also notify the new blocks as they are
+//! mined (see [subscribe_new_heads]), rather than being polled.
+use std::{path::PathBuf, time::Duration};
+
+use alloy::{
+    providers::{Provider as _, ProviderBuilder, RootProvider},
+    pubsub::{PubSubConnect, PubSubFrontend},
+    rpc::client::{ClientBuilder, RpcClient},
+    transports::{ipc::IpcConnect, ws::WsConnect, BoxTransport, Transport},
+};
+use anyhow::{anyhow, Context as _};
+use futures::{stream::BoxStream, StreamExt as _};
+use tower::Layer;
+use tracing::debug;
+use url::Url;
+
+use crate::{
+    batch::BatchLayer,
+    cache::{CacheLayer, RpcCache},
+    retry::{build_http_retry_provider, RetryLayer, RetryPolicy},
+    throttle::{Throttle, ThrottleLayer},
+};
+
+/// The transport of an RPC node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcTransport {
+    Http,
+    Ws,
+    Ipc,
+}
+
+impl RpcTransport {
+    /// Returns the transport of the url, given its scheme
+    pub fn of(url: &Url) -> anyhow::Result<Self> {
+        match url.scheme() {
+            "http" | "https" => Ok(Self::Http),
+            "ws" | "wss" => Ok(Self::Ws),
+            "ipc" => Ok(Self::Ipc),
+            scheme => Err(anyhow!(
+                "unsupported scheme `{}`, expected `http`, `https`, `ws`, `wss` or `ipc`",
+                scheme
+            )),
+        }
+    }
+
+    /// Returns true if the node can notify the new blocks
+    pub fn supports_subscriptions(&self) -> bool {
+        matches!(self, Self::Ws | Self::Ipc)
+    }
+}
+
+/// Connects to the node over WebSocket or IPC with the given connector
+async fn connect<L, C>(
+    builder: ClientBuilder<L>,
+    connector: C,
+    rpc_url: &Url,
+) -> anyhow::Result<RpcClient<L::Service>>
+where
+    L: Layer<PubSubFrontend>,
+    L::Service: Transport,
+    C: PubSubConnect,
+{
+    builder
+        .pubsub(connector)
+        .await
+        .with_context(|| format!("failed to connect to {}", rpc_url))
+}
+
+/// Builds a provider of the node at the url, over the transport of its scheme.
+/// The requests are retried, served from the cache and throttled as by
+/// [build_http_retry_provider]; over WebSocket and IPC, the requests are not
+/// batched as they share a single connection.
+pub async fn build_retry_provider(
+    rpc_url: Url,
+    backoff: u64,
+    max_retries: u32,
+    cache: Option<RpcCache>,
+    throttle: Throttle,
+) -> anyhow::Result<RootProvider<BoxTransport>> {
+    let layers = |cache: Option<RpcCache>, throttle: Throttle| {
+        ClientBuilder::default()
+            .layer(CacheLayer::new(cache))
+            .layer(BatchLayer::new(1))
+            .layer(RetryLayer::new(RetryPolicy::new(
+                Duration::from_millis(backoff),
+                max_retries,
+            )))
+            .layer(ThrottleLayer::new(throttle))
+    };
+    let client = match RpcTransport::of(&rpc_url)? {
+        RpcTransport::Http => {
+            let provider =
+                build_http_retry_provider(rpc_url, backoff, max_retries, cache, throttle);
+            return Ok(provider.boxed());
+        }
+        RpcTransport::Ws => {
+            let connector = WsConnect::new(rpc_url.as_str());
+            connect(layers(cache, throttle), connector, &rpc_url).await?
+        }
+        RpcTransport::Ipc => {
+            let connector = IpcConnect::new(PathBuf::from(rpc_url.path()));
+            connect(layers(cache, throttle), connector, &rpc_url).await?
+        }
+    };
+    Ok(ProviderBuilder::new().on_client(client.boxed()))
+}
+
+/// Subscribes to the new blocks of the node, if the transport of the url
+/// supports it, returning a stream notified of every new head.  The connection
+/// of the subscription is only used for it: the other requests should be sent
+/// through a [build_retry_provider].
+pub async fn subscribe_new_heads(rpc_url: &Url) -> anyhow::Result<Option<BoxStream<'static, ()>>> {
+    let client = match RpcTransport::of(rpc_url)? {
+        RpcTransport::Http => return Ok(None),
+        RpcTransport::Ws => {
+            let connector = WsConnect::new(rpc_url.as_str());
+            connect(ClientBuilder::default(), connector, rpc_url).await?
+        }
+        RpcTransport::Ipc => {
+            let connector = IpcConnect::new(PathBuf::from(rpc_url.path()));
+            connect(ClientBuilder::default(), connector, rpc_url).await?
+        }
+    };
+    let provider = ProviderBuilder::new().on_client(client.boxed());
+    let subscription = match provider.subscribe_blocks().await {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            debug!("Polling for new blocks, could not subscribe to them: {e}");
+            return Ok(None);
+        }
+    };
+    // The connection is closed along with the provider, which must outlive the
+    // subscription
+    let new_heads = subscription.into_stream().map(move |_| {
+        let _ = &provider;
+    });
+    Ok(Some(new_heads.boxed()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transport(url: &str) -> anyhow::Result<RpcTransport> {
+        RpcTransport::of(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn finds_the_transport_of_the_url() {
+        assert_eq!(
+            transport("http://localhost:8545").unwrap(),
+            RpcTransport::Http
+        );
+        assert_eq!(
+            transport("https://rpc.example.com").unwrap(),
+            RpcTransport::Http
+        );
+        assert_eq!(transport("ws://localhost:8546").unwrap(), RpcTransport::Ws);
+        assert_eq!(
+            transport("wss://rpc.example.com").unwrap(),
+            RpcTransport::Ws
+        );
+        assert_eq!(
+            transport("ipc:///var/lib/geth/geth.ipc").unwrap(),
+            RpcTransport::Ipc
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_schemes() {
+        let err = transport("ftp://localhost").unwrap_err();
+        assert!(err.to_string().contains("unsupported scheme `ftp`"));
+    }
+
+    #[test]
+    fn supports_subscriptions_over_pubsub_transports() {
+        assert!(!RpcTransport::Http.supports_subscriptions());
+        assert!(RpcTransport::Ws.supports_subscriptions());
+        assert!(RpcTransport::Ipc.supports_subscriptions());
+    }
+
+    #[tokio::test]
+    async fn does_not_subscribe_over_http() {
+        let url = Url::parse("http://localhost:8545").unwrap();
+        assert!(subscribe_new_heads(&url).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn fails_to_subscribe_without_a_node() {
+        let path = std::env::temp_dir().join(format!("rpc-missing-{}.ipc", std::process::id()));
+        let url = Url::parse(&format!("ipc://{}", path.display())).unwrap();
+        let err = subscribe_new_heads(&url).await.unwrap_err();
+        assert!(err.to_string().contains("failed to connect"));
+    }
+}
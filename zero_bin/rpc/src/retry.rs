@@ -1,76 +1,234 @@
+//! Retries the requests failing for transient reasons.
+//!
+//! A request is retried when it could not be sent or answered (i.e. a
+//! connection failure or a timeout, over HTTP, WebSocket or IPC), when the node
+//! answers with HTTP 429 or a 5xx status, or when it answers with one of the
+//! [RETRYABLE_ERROR_CODES].  Any other error is returned straight away.  The
+//! delay between two attempts grows exponentially from the `backoff`, with
+//! jitter, and is at least the `Retry-After` of the node (up to
+//! [MAX_RETRY_AFTER]).  Only the requests of a batch which failed are sent
+//! again, and the responses of the others are kept even if the retries fail.
+//!
+//! Once the retries are exhausted, the error describes every attempt made.
 use std::{
+    collections::HashSet,
     future::Future,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use alloy::{
     providers::{ProviderBuilder, RootProvider},
     rpc::{
         client::ClientBuilder,
-        json_rpc::{RequestPacket, ResponsePacket},
+        json_rpc::{ErrorPayload, Id, RequestPacket, Response, ResponsePacket, ResponsePayload},
     },
-    transports::TransportError,
+    transports::{http::reqwest, RpcError, TransportError, TransportErrorKind},
 };
-use tower::{retry::Policy, Layer, Service};
+use futures::future::poll_fn;
+use rand::Rng;
+use tower::{Layer, Service};
+use tracing::warn;
 
-#[derive(Debug)]
+use crate::{
+    batch::{BatchLayer, BatchService},
+    cache::{CacheLayer, CacheService, RpcCache},
+    http::{custom_error, HttpStatusError, HttpTransport},
+    throttle::{Throttle, ThrottleLayer, ThrottleService},
+};
+
+/// The maximum delay between two attempts, unless the node asks for more
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// The maximum delay the node can ask for with `Retry-After`, so that a
+/// misbehaving node does not stall the requests
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(4 * 30);
+/// The JSON-RPC error codes of transient failures: rate limiting (`429` and
+/// `-32005`, limit exceeded) and internal errors (`-32603`)
+pub const RETRYABLE_ERROR_CODES: [i64; 3] = [429, -32005, -32603];
+
+//==============================================================================
+// Retry policy
+//==============================================================================
+
+#[derive(Debug, Clone)]
 pub struct RetryPolicy {
-    backoff: tokio::time::Duration,
-    retries: u32,
+    backoff: Duration,
     max_retries: u32,
 }
 
-impl Clone for RetryPolicy {
-    fn clone(&self) -> Self {
+impl RetryPolicy {
+    pub fn new(backoff: Duration, max_retries: u32) -> Self {
         Self {
-            backoff: self.backoff,
-            retries: self.retries,
-            max_retries: self.max_retries,
+            backoff,
+            max_retries,
         }
     }
+
+    /// Returns the delay before the given retry (starting from 0): the backoff
+    /// doubled on every retry up to [MAX_BACKOFF], randomly reduced by up to
+    /// half, and at least `retry_after` up to [MAX_RETRY_AFTER]
+    pub fn backoff(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_BACKOFF);
+        let jittered = delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+        jittered.max(retry_after.unwrap_or_default().min(MAX_RETRY_AFTER))
+    }
 }
 
-impl RetryPolicy {
-    pub fn new(backoff: tokio::time::Duration, max_retries: u32) -> Self {
-        Self {
-            backoff,
-            retries: 0,
-            max_retries,
+/// Why an attempt failed transiently
+#[derive(Debug)]
+struct Failure {
+    reason: String,
+    retry_after: Option<Duration>,
+}
+
+/// Returns the [Failure] of a transient error, or [None] if retrying would not
+/// help
+fn transient_error(err: &TransportError) -> Option<Failure> {
+    let transient = if let Some(err) = custom_error::<HttpStatusError>(err) {
+        err.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || err.status == reqwest::StatusCode::REQUEST_TIMEOUT
+            || err.status.is_server_error()
+    } else if let Some(err) = custom_error::<reqwest::Error>(err) {
+        err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+    } else {
+        match err {
+            RpcError::Transport(TransportErrorKind::BackendGone) => true,
+            // The WebSocket and IPC connections fail with an I/O error
+            RpcError::Transport(TransportErrorKind::Custom(err)) => std::iter::successors(
+                Some(err.as_ref() as &(dyn std::error::Error + 'static)),
+                |err| err.source(),
+            )
+            .any(|err| err.is::<std::io::Error>()),
+            _ => false,
         }
+    };
+
+    transient.then(|| Failure {
+        reason: err.to_string(),
+        retry_after: custom_error::<HttpStatusError>(err).and_then(|err| err.retry_after),
+    })
+}
+
+/// Returns the [Failure] of a response holding a transient JSON-RPC error
+fn transient_response(response: &Response) -> Option<Failure> {
+    match &response.payload {
+        ResponsePayload::Failure(err) if RETRYABLE_ERROR_CODES.contains(&err.code) => {
+            Some(Failure {
+                reason: format!("error {}: {}", err.code, err.message),
+                retry_after: None,
+            })
+        }
+        _ => None,
     }
+}
+
+/// Returned once the retries are exhausted, or when a retried request fails
+/// permanently
+#[derive(Debug)]
+pub struct RetryError {
+    /// Why each attempt failed, in order
+    pub failures: Vec<String>,
+}
 
-    pub fn backoff(&self) -> tokio::time::Sleep {
-        tokio::time::sleep(self.backoff)
+impl std::fmt::Display for RetryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "gave up after {} attempts", self.failures.len())?;
+        for (attempt, failure) in self.failures.iter().enumerate() {
+            write!(f, "; attempt {}: {}", attempt + 1, failure)?;
+        }
+        Ok(())
     }
 }
 
-impl Policy<RequestPacket, ResponsePacket, TransportError> for RetryPolicy {
-    type Future = Pin<Box<dyn Future<Output = Self> + Send + 'static>>;
+impl std::error::Error for RetryError {}
 
-    fn retry(
-        &self,
-        _req: &RequestPacket,
-        result: Result<&ResponsePacket, &TransportError>,
-    ) -> Option<Self::Future> {
-        // TODO: Use rate-limit specific errors/codes and retry accordingly.
-        if result.is_err() && self.retries < self.max_retries {
-            let mut policy = self.clone();
-            Some(Box::pin(async move {
-                policy.backoff().await;
-                policy.retries += 1;
-                policy
-            }))
-        } else {
-            None
+/// Returns the final result of a request given up on, describing every failed
+/// attempt if it was retried
+fn give_up(
+    result: Result<ResponsePacket, TransportError>,
+    mut failures: Vec<String>,
+) -> Result<ResponsePacket, TransportError> {
+    // A permanent error is not recorded as a failure yet
+    if let Err(err) = &result {
+        if transient_error(err).is_none() {
+            failures.push(err.to_string());
         }
     }
+    if failures.len() <= 1 {
+        return result;
+    }
+    match result {
+        Err(_) => Err(TransportErrorKind::custom(RetryError { failures })),
+        Ok(packet) => {
+            let summary = RetryError { failures }.to_string();
+            let annotate = |mut response: Response| {
+                if transient_response(&response).is_some() {
+                    if let ResponsePayload::Failure(err) = &mut response.payload {
+                        err.message = format!("{} ({})", err.message, summary).into();
+                    }
+                }
+                response
+            };
+            Ok(match packet {
+                ResponsePacket::Single(response) => ResponsePacket::Single(annotate(response)),
+                ResponsePacket::Batch(responses) => {
+                    ResponsePacket::Batch(responses.into_iter().map(annotate).collect())
+                }
+            })
+        }
+    }
+}
 
-    fn clone_request(&self, req: &RequestPacket) -> Option<RequestPacket> {
-        Some(req.clone())
+/// Adds the responses of a batch which settled in the previous attempts to the
+/// final `result` of its `pending` requests.  If the pending requests failed
+/// as a whole, each of them fails with the error, rather than the batch.
+fn settle(
+    settled: Vec<Response>,
+    pending: &RequestPacket,
+    result: Result<ResponsePacket, TransportError>,
+) -> Result<ResponsePacket, TransportError> {
+    if settled.is_empty() {
+        return result;
     }
+    let responses = match result {
+        Ok(ResponsePacket::Single(response)) => vec![response],
+        Ok(ResponsePacket::Batch(responses)) => responses,
+        Err(err) => {
+            let ids = match pending {
+                RequestPacket::Single(request) => vec![request.id().clone()],
+                RequestPacket::Batch(requests) => requests
+                    .iter()
+                    .map(|request| request.id().clone())
+                    .collect(),
+            };
+            let message = err.to_string();
+            // `-32603` is the code of an internal error, as the node could
+            // not answer
+            ids.into_iter()
+                .map(|id| Response {
+                    id,
+                    payload: ResponsePayload::Failure(ErrorPayload {
+                        code: -32603,
+                        message: message.clone().into(),
+                        data: None,
+                    }),
+                })
+                .collect()
+        }
+    };
+    Ok(ResponsePacket::Batch(
+        settled.into_iter().chain(responses).collect(),
+    ))
 }
 
+//==============================================================================
+// Retry service
+//==============================================================================
+
 /// RetryLayer
 pub struct RetryLayer {
     policy: RetryPolicy,
@@ -118,31 +276,396 @@ where
 
     fn call(&mut self, req: RequestPacket) -> Self::Future {
         let inner = self.inner.clone();
-        let mut policy = self.policy.clone();
+        let policy = self.policy.clone();
 
         let mut inner = std::mem::replace(&mut self.inner, inner);
         Box::pin(async move {
-            let mut res = inner.call(req.clone()).await;
+            let mut failures = Vec::new();
+            // The responses of the requests of a batch which succeeded, or
+            // failed permanently
+            let mut settled = Vec::new();
+            let mut pending = req;
+            loop {
+                let (failure, exhausted, retry) = match inner.call(pending.clone()).await {
+                    Err(err) => match transient_error(&err) {
+                        Some(failure) => (failure, Err(err), pending),
+                        None => return settle(settled, &pending, give_up(Err(err), failures)),
+                    },
+                    Ok(ResponsePacket::Single(response)) => match transient_response(&response) {
+                        Some(failure) => (failure, Ok(ResponsePacket::Single(response)), pending),
+                        None => {
+                            let result = give_up(Ok(ResponsePacket::Single(response)), failures);
+                            return settle(settled, &pending, result);
+                        }
+                    },
+                    Ok(ResponsePacket::Batch(responses)) => {
+                        let (failed, succeeded): (Vec<_>, Vec<_>) = responses
+                            .into_iter()
+                            .partition(|response| transient_response(response).is_some());
+                        settled.extend(succeeded);
+                        let Some(failure) = failed.first().and_then(transient_response) else {
+                            return Ok(ResponsePacket::Batch(settled));
+                        };
 
-            while let Some(new_policy) = policy.retry(&req, res.as_ref()) {
-                policy = new_policy.await;
-                res = inner.call(req.clone()).await;
-            }
+                        let failed_ids = failed
+                            .iter()
+                            .map(|response| response.id.clone())
+                            .collect::<HashSet<Id>>();
+                        let retry = match pending {
+                            RequestPacket::Batch(requests) => RequestPacket::Batch(
+                                requests
+                                    .into_iter()
+                                    .filter(|request| failed_ids.contains(request.id()))
+                                    .collect(),
+                            ),
+                            pending => pending,
+                        };
+                        (failure, Ok(ResponsePacket::Batch(failed)), retry)
+                    }
+                };
 
-            res
+                failures.push(failure.reason);
+                let retries = failures.len() as u32 - 1;
+                if retries >= policy.max_retries {
+                    return settle(settled, &retry, give_up(exhausted, failures));
+                }
+
+                let delay = policy.backoff(retries, failure.retry_after);
+                warn!(
+                    "RPC request failed ({}), retrying in {:?} ({}/{})",
+                    failures.last().map(String::as_str).unwrap_or_default(),
+                    delay,
+                    retries + 1,
+                    policy.max_retries
+                );
+                tokio::time::sleep(delay).await;
+                // The service was only made ready for the first attempt
+                if let Err(err) = poll_fn(|cx| inner.poll_ready(cx)).await {
+                    return settle(settled, &retry, Err(err));
+                }
+                pending = retry;
+            }
         })
     }
 }
 
+/// Builds a provider retrying the failed requests, and serving the responses
+/// of the finalized blocks from the cache if one is provided.  The concurrent
+/// requests are batched, and every attempt is subject to the limits of the
+/// [Throttle], which may be shared with other providers.
 pub fn build_http_retry_provider(
     rpc_url: url::Url,
     backoff: u64,
     max_retries: u32,
-) -> RootProvider<RetryService<alloy::transports::http::ReqwestTransport>> {
+    cache: Option<RpcCache>,
+    throttle: Throttle,
+) -> RootProvider<CacheService<BatchService<RetryService<ThrottleService<HttpTransport>>>>> {
     let retry_policy = RetryLayer::new(RetryPolicy::new(
-        tokio::time::Duration::from_millis(backoff),
+        Duration::from_millis(backoff),
         max_retries,
     ));
-    let client = ClientBuilder::default().layer(retry_policy).http(rpc_url);
+    let transport = HttpTransport::new(rpc_url);
+    let is_local = transport.is_local();
+    let client = ClientBuilder::default()
+        .layer(CacheLayer::new(cache))
+        .layer(BatchLayer::new(throttle.batch_size()))
+        .layer(retry_policy)
+        .layer(ThrottleLayer::new(throttle))
+        .transport(transport, is_local);
     ProviderBuilder::new().on_client(client)
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use alloy::rpc::json_rpc::{Request, SerializedRequest};
+    use serde_json::value::RawValue;
+
+    use super::*;
+
+    /// Answers the requests with the given results in order, recording the
+    /// ids of the requests of every packet
+    #[derive(Clone, Default)]
+    struct Node {
+        results: Arc<Mutex<VecDeque<Result<ResponsePacket, TransportError>>>>,
+        packets: Arc<Mutex<Vec<Vec<Id>>>>,
+    }
+
+    impl Node {
+        fn new(results: impl IntoIterator<Item = Result<ResponsePacket, TransportError>>) -> Self {
+            Self {
+                results: Arc::new(Mutex::new(results.into_iter().collect())),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Service<RequestPacket> for Node {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: RequestPacket) -> Self::Future {
+            let ids = match &req {
+                RequestPacket::Single(request) => vec![request.id().clone()],
+                RequestPacket::Batch(requests) => requests
+                    .iter()
+                    .map(|request| request.id().clone())
+                    .collect(),
+            };
+            self.packets.lock().unwrap().push(ids);
+            let result = self.results.lock().unwrap().pop_front().unwrap();
+            Box::pin(async move { result })
+        }
+    }
+
+    fn request(id: u64) -> SerializedRequest {
+        Request::new("eth_blockNumber", Id::Number(id), ())
+            .serialize()
+            .unwrap()
+    }
+
+    fn success(id: u64) -> Response {
+        Response {
+            id: Id::Number(id),
+            payload: ResponsePayload::Success(RawValue::from_string("\"0x1\"".into()).unwrap()),
+        }
+    }
+
+    fn failure(id: u64, code: i64) -> Response {
+        Response {
+            id: Id::Number(id),
+            payload: ResponsePayload::Failure(ErrorPayload {
+                code,
+                message: "failed".into(),
+                data: None,
+            }),
+        }
+    }
+
+    fn status_error(status: reqwest::StatusCode, retry_after: Option<Duration>) -> TransportError {
+        TransportErrorKind::custom(HttpStatusError {
+            status,
+            retry_after,
+            body: String::new(),
+        })
+    }
+
+    /// Returns the message of the error a response holds, if any
+    fn error_message(response: &Response) -> Option<String> {
+        match &response.payload {
+            ResponsePayload::Success(_) => None,
+            ResponsePayload::Failure(err) => Some(err.message.to_string()),
+        }
+    }
+
+    async fn send(
+        node: Node,
+        max_retries: u32,
+        req: RequestPacket,
+    ) -> Result<ResponsePacket, TransportError> {
+        let mut service =
+            RetryLayer::new(RetryPolicy::new(Duration::ZERO, max_retries)).layer(node);
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        service.call(req).await
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy::new(Duration::from_millis(100), 10);
+        for _ in 0..100 {
+            let first = policy.backoff(0, None);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let fourth = policy.backoff(3, None);
+            assert!(fourth >= Duration::from_millis(400) && fourth <= Duration::from_millis(800));
+            let last = policy.backoff(u32::MAX, None);
+            assert!(last >= MAX_BACKOFF / 2 && last <= MAX_BACKOFF);
+        }
+    }
+
+    #[test]
+    fn waits_for_the_retry_after_up_to_a_limit() {
+        let policy = RetryPolicy::new(Duration::from_millis(100), 10);
+        assert_eq!(
+            policy.backoff(0, Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            policy.backoff(0, Some(Duration::from_secs(3600))),
+            MAX_RETRY_AFTER
+        );
+        // A shorter `Retry-After` does not shorten the backoff
+        assert!(policy.backoff(3, Some(Duration::from_millis(1))) >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn retries_the_transient_errors() {
+        let retry_after = Some(Duration::from_secs(2));
+        let failure = transient_error(&status_error(
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            retry_after,
+        ))
+        .unwrap();
+        assert_eq!(failure.retry_after, retry_after);
+        for status in [
+            reqwest::StatusCode::REQUEST_TIMEOUT,
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            reqwest::StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(transient_error(&status_error(status, None)).is_some());
+        }
+
+        for status in [
+            reqwest::StatusCode::BAD_REQUEST,
+            reqwest::StatusCode::UNAUTHORIZED,
+            reqwest::StatusCode::NOT_FOUND,
+        ] {
+            assert!(transient_error(&status_error(status, None)).is_none());
+        }
+        assert!(transient_error(&TransportErrorKind::custom_str("unknown")).is_none());
+    }
+
+    #[test]
+    fn retries_the_failed_connections() {
+        assert!(transient_error(&TransportErrorKind::backend_gone()).is_some());
+        let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(transient_error(&TransportErrorKind::custom(reset)).is_some());
+
+        // The I/O error may be the source of the error of the connection
+        #[derive(Debug)]
+        struct ConnectionError(std::io::Error);
+        impl std::fmt::Display for ConnectionError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "connection failed: {}", self.0)
+            }
+        }
+        impl std::error::Error for ConnectionError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+        let closed = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed");
+        assert!(transient_error(&TransportErrorKind::custom(ConnectionError(closed))).is_some());
+
+        assert!(transient_error(&TransportErrorKind::pubsub_unavailable()).is_none());
+    }
+
+    #[test]
+    fn retries_the_transient_responses() {
+        for code in RETRYABLE_ERROR_CODES {
+            assert!(transient_response(&failure(1, code)).is_some());
+        }
+        assert!(transient_response(&failure(1, -32000)).is_none());
+        assert!(transient_response(&success(1)).is_none());
+    }
+
+    #[test]
+    fn describes_every_attempt_when_giving_up() {
+        let failures = vec!["first".to_string(), "second".to_string()];
+
+        // A request which was not retried is returned as is
+        let err = give_up(Err(TransportErrorKind::custom_str("permanent")), vec![]).unwrap_err();
+        assert!(custom_error::<RetryError>(&err).is_none());
+
+        let err = give_up(
+            Err(TransportErrorKind::custom_str("permanent")),
+            failures.clone(),
+        )
+        .unwrap_err();
+        let err = custom_error::<RetryError>(&err).unwrap();
+        assert_eq!(err.failures.len(), 3);
+        assert!(err.failures[2].contains("permanent"));
+
+        let packet = ResponsePacket::Batch(vec![success(1), failure(2, 429), failure(3, -32000)]);
+        let ResponsePacket::Batch(responses) = give_up(Ok(packet), failures).unwrap() else {
+            panic!("expected a batch");
+        };
+        assert_eq!(error_message(&responses[0]), None);
+        assert_eq!(
+            error_message(&responses[1]).unwrap(),
+            "failed (gave up after 2 attempts; attempt 1: first; attempt 2: second)"
+        );
+        assert_eq!(error_message(&responses[2]).unwrap(), "failed");
+    }
+
+    #[tokio::test]
+    async fn retries_the_failed_requests_of_a_batch() {
+        let node = Node::new([
+            Ok(ResponsePacket::Batch(vec![success(1), failure(2, 429)])),
+            Ok(ResponsePacket::Batch(vec![success(2)])),
+        ]);
+        let req = RequestPacket::Batch(vec![request(1), request(2)]);
+        let ResponsePacket::Batch(responses) = send(node.clone(), 3, req).await.unwrap() else {
+            panic!("expected a batch");
+        };
+
+        assert_eq!(
+            *node.packets.lock().unwrap(),
+            vec![vec![Id::Number(1), Id::Number(2)], vec![Id::Number(2)]]
+        );
+        assert_eq!(responses.len(), 2);
+        assert!(responses
+            .iter()
+            .all(|response| error_message(response).is_none()));
+    }
+
+    #[tokio::test]
+    async fn keeps_the_settled_responses_when_a_batch_retry_fails() {
+        let node = Node::new([
+            Ok(ResponsePacket::Batch(vec![
+                success(1),
+                failure(2, 429),
+                failure(3, 429),
+            ])),
+            Err(status_error(reqwest::StatusCode::BAD_GATEWAY, None)),
+        ]);
+        let req = RequestPacket::Batch(vec![request(1), request(2), request(3)]);
+        let ResponsePacket::Batch(responses) = send(node, 1, req).await.unwrap() else {
+            panic!("expected a batch");
+        };
+
+        let ids = responses
+            .iter()
+            .map(|response| response.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![Id::Number(1), Id::Number(2), Id::Number(3)]);
+        assert_eq!(error_message(&responses[0]), None);
+        for response in &responses[1..] {
+            assert!(error_message(response)
+                .unwrap()
+                .contains("gave up after 2 attempts"));
+        }
+    }
+
+    #[tokio::test]
+    async fn gives_up_once_the_retries_are_exhausted() {
+        let node = Node::new([
+            Err(status_error(reqwest::StatusCode::SERVICE_UNAVAILABLE, None)),
+            Err(status_error(reqwest::StatusCode::SERVICE_UNAVAILABLE, None)),
+            Err(status_error(reqwest::StatusCode::SERVICE_UNAVAILABLE, None)),
+        ]);
+        let req = RequestPacket::Single(request(1));
+        let err = send(node.clone(), 2, req).await.unwrap_err();
+
+        assert_eq!(node.packets.lock().unwrap().len(), 3);
+        assert_eq!(custom_error::<RetryError>(&err).unwrap().failures.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_the_permanent_errors() {
+        let node = Node::new([Err(status_error(reqwest::StatusCode::BAD_REQUEST, None))]);
+        let req = RequestPacket::Single(request(1));
+        let err = send(node.clone(), 3, req).await.unwrap_err();
+
+        assert_eq!(node.packets.lock().unwrap().len(), 1);
+        assert!(custom_error::<HttpStatusError>(&err).is_some());
+    }
+}
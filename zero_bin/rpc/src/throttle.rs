@@ -0,0 +1,508 @@
+//! Limits the load put on the RPC node, shared by every provider built from the
+//! same [Throttle].
+//!
+//! Each request (or each request of a batch) takes a token from a global
+//! token bucket refilled at `requests_per_second`, and at most `concurrency`
+//! HTTP requests are in flight at once.  The concurrency adapts to the node:
+//! it is halved whenever the node rate-limits a request or a request times
+//! out, and grows back by one for every `concurrency` successful requests, up
+//! to `max_concurrency`.
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use alloy::{
+    rpc::json_rpc::{RequestPacket, ResponsePacket, ResponsePayload},
+    transports::{
+        http::reqwest::{self, StatusCode},
+        TransportError,
+    },
+};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::Instant,
+};
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+use crate::http::{custom_error, HttpStatusError};
+
+/// The default maximum number of requests sent in one JSON-RPC batch
+pub const DFLT_BATCH_SIZE: usize = 100;
+/// The default maximum number of HTTP requests in flight
+pub const DFLT_MAX_CONCURRENCY: usize = 64;
+/// The minimum time between two reductions of the concurrency, so that the
+/// requests failing together only reduce it once
+const BACK_OFF_COOLDOWN: Duration = Duration::from_secs(1);
+
+//==============================================================================
+// Configuration
+//==============================================================================
+
+/// The configuration of the [Throttle]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// The maximum number of concurrent requests sent in one JSON-RPC batch,
+    /// 1 disabling batching.  Default is [DFLT_BATCH_SIZE].
+    pub batch_size: usize,
+    /// If provided, the maximum number of requests per second, shared by every
+    /// provider of the [Throttle]
+    pub requests_per_second: Option<u32>,
+    /// The maximum number of HTTP requests in flight.  Default is
+    /// [DFLT_MAX_CONCURRENCY].
+    pub max_concurrency: usize,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: DFLT_BATCH_SIZE,
+            requests_per_second: None,
+            max_concurrency: DFLT_MAX_CONCURRENCY,
+        }
+    }
+}
+
+/// The command line arguments configuring the [Throttle]
+#[derive(Args, Debug, Clone)]
+pub struct ThrottleArgs {
+    /// The maximum number of concurrent RPC requests sent in one JSON-RPC
+    /// batch, 1 disabling batching.
+    #[arg(long, default_value_t = DFLT_BATCH_SIZE)]
+    pub rpc_batch_size: usize,
+    /// If provided, the maximum number of RPC requests per second.
+    #[arg(long)]
+    pub rpc_requests_per_second: Option<u32>,
+    /// The maximum number of HTTP requests in flight to the node, reduced
+    /// whenever the node rate-limits a request or a request times out.
+    #[arg(long, default_value_t = DFLT_MAX_CONCURRENCY)]
+    pub rpc_max_concurrency: usize,
+}
+
+impl ThrottleArgs {
+    /// Returns the [ThrottleConfig]
+    pub fn config(&self) -> ThrottleConfig {
+        ThrottleConfig {
+            batch_size: self.rpc_batch_size,
+            requests_per_second: self.rpc_requests_per_second,
+            max_concurrency: self.rpc_max_concurrency,
+        }
+    }
+}
+
+//==============================================================================
+// Rate limiting
+//==============================================================================
+
+/// A token bucket holding up to one second worth of requests
+#[derive(Debug)]
+struct RateLimiter {
+    /// The number of tokens added per second
+    rate: f64,
+    /// The tokens left, negative when requests are waiting for tokens, along
+    /// with when they were last refilled
+    tokens: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32) -> Self {
+        let rate = requests_per_second.max(1) as f64;
+        Self {
+            rate,
+            tokens: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Takes `n` tokens, waiting for the bucket to refill if needed.  The
+    /// tokens are reserved straight away, so that the requests are served in
+    /// order.
+    async fn acquire(&self, n: usize) {
+        let wait = {
+            let mut tokens = self.tokens.lock().expect("rate limiter lock poisoned");
+            let now = Instant::now();
+            let refilled = tokens.0 + now.duration_since(tokens.1).as_secs_f64() * self.rate;
+            *tokens = (refilled.min(self.rate) - n as f64, now);
+            (tokens.0 < 0.0).then(|| Duration::from_secs_f64(-tokens.0 / self.rate))
+        };
+        if let Some(wait) = wait {
+            debug!("Rate limiting {} RPC request(s) for {:?}", n, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+//==============================================================================
+// Adaptive concurrency
+//==============================================================================
+
+#[derive(Debug)]
+struct ConcurrencyState {
+    /// The current maximum number of requests in flight
+    limit: usize,
+    /// The number of successful requests since the limit last changed
+    successes: usize,
+    /// When the limit was last reduced
+    last_back_off: Option<Instant>,
+    /// The permits to remove as the requests in flight release them, once
+    /// the limit was reduced below the requests in flight
+    excess: usize,
+}
+
+/// Limits the number of requests in flight, increasing the limit additively
+/// and decreasing it multiplicatively
+#[derive(Debug)]
+struct ConcurrencyLimiter {
+    max: usize,
+    semaphore: Semaphore,
+    state: Mutex<ConcurrencyState>,
+}
+
+impl ConcurrencyLimiter {
+    fn new(max_concurrency: usize) -> Self {
+        let max = max_concurrency.max(1);
+        Self {
+            max,
+            semaphore: Semaphore::new(max),
+            state: Mutex::new(ConcurrencyState {
+                limit: max,
+                successes: 0,
+                last_back_off: None,
+                excess: 0,
+            }),
+        }
+    }
+
+    async fn acquire(&self) -> ConcurrencyPermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        ConcurrencyPermit {
+            limiter: self,
+            permit: Some(permit),
+        }
+    }
+
+    /// Records a successful request, increasing the limit by one once the
+    /// current limit of requests succeeded
+    fn succeeded(&self) {
+        let mut state = self.state.lock().expect("concurrency lock poisoned");
+        state.successes += 1;
+        if state.successes >= state.limit && state.limit < self.max {
+            state.limit += 1;
+            state.successes = 0;
+            // A permit still to be removed is kept rather than added
+            if state.excess > 0 {
+                state.excess -= 1;
+            } else {
+                self.semaphore.add_permits(1);
+            }
+            debug!("Increased the RPC concurrency to {}", state.limit);
+        }
+    }
+
+    /// Records a rate-limited or timed-out request, halving the limit
+    fn back_off(&self) {
+        let mut state = self.state.lock().expect("concurrency lock poisoned");
+        let cooling_down = state
+            .last_back_off
+            .is_some_and(|last_back_off| last_back_off.elapsed() < BACK_OFF_COOLDOWN);
+        if cooling_down || state.limit == 1 {
+            return;
+        }
+
+        let limit = (state.limit / 2).max(1);
+        let removed = state.limit - limit;
+        // The available permits are removed straight away, the others as the
+        // requests in flight release them
+        let forgotten = self.semaphore.forget_permits(removed);
+        *state = ConcurrencyState {
+            limit,
+            successes: 0,
+            last_back_off: Some(Instant::now()),
+            excess: state.excess + removed - forgotten,
+        };
+        warn!(
+            "The RPC node is overloaded, reduced the concurrency to {}",
+            limit
+        );
+    }
+}
+
+/// A permit to have a request in flight, removed rather than released if the
+/// limit was reduced meanwhile
+struct ConcurrencyPermit<'a> {
+    limiter: &'a ConcurrencyLimiter,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self
+            .limiter
+            .state
+            .lock()
+            .expect("concurrency lock poisoned");
+        if state.excess > 0 {
+            state.excess -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// Returns true if the node rejected the request for exceeding its rate limit,
+/// or if the request timed out
+fn is_overloaded(result: &Result<ResponsePacket, TransportError>) -> bool {
+    let responses = match result {
+        Ok(ResponsePacket::Single(response)) => std::slice::from_ref(response),
+        Ok(ResponsePacket::Batch(responses)) => responses.as_slice(),
+        Err(err) => {
+            return custom_error::<HttpStatusError>(err).is_some_and(|err| {
+                matches!(
+                    err.status,
+                    StatusCode::TOO_MANY_REQUESTS
+                        | StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::GATEWAY_TIMEOUT
+                )
+            }) || custom_error::<reqwest::Error>(err)
+                .is_some_and(reqwest::Error::is_timeout)
+        }
+    };
+    // `-32005` is the code of a request exceeding the limits of the node
+    responses.iter().any(|response| {
+        matches!(&response.payload, ResponsePayload::Failure(err) if matches!(err.code, 429 | -32005))
+    })
+}
+
+//==============================================================================
+// Throttle
+//==============================================================================
+
+#[derive(Debug)]
+struct ThrottleInner {
+    config: ThrottleConfig,
+    rate: Option<RateLimiter>,
+    concurrency: ConcurrencyLimiter,
+}
+
+/// The limits on the requests sent to the RPC node, shared by its clones
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    inner: Arc<ThrottleInner>,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            inner: Arc::new(ThrottleInner {
+                rate: config.requests_per_second.map(RateLimiter::new),
+                concurrency: ConcurrencyLimiter::new(config.max_concurrency),
+                config,
+            }),
+        }
+    }
+
+    /// Returns the maximum number of requests sent in one JSON-RPC batch
+    pub fn batch_size(&self) -> usize {
+        self.inner.config.batch_size
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(ThrottleConfig::default())
+    }
+}
+
+/// ThrottleLayer
+pub struct ThrottleLayer {
+    throttle: Throttle,
+}
+
+impl ThrottleLayer {
+    pub const fn new(throttle: Throttle) -> Self {
+        Self { throttle }
+    }
+}
+
+impl<S> Layer<S> for ThrottleLayer {
+    type Service = ThrottleService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ThrottleService {
+            inner,
+            throttle: self.throttle.clone(),
+        }
+    }
+}
+
+/// ThrottleService
+#[derive(Debug, Clone)]
+pub struct ThrottleService<S> {
+    inner: S,
+    throttle: Throttle,
+}
+
+impl<S> Service<RequestPacket> for ThrottleService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + 'static
+        + Clone,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let throttle = self.throttle.clone();
+
+        Box::pin(async move {
+            let throttle = &throttle.inner;
+            if let Some(rate) = &throttle.rate {
+                let n = match &req {
+                    RequestPacket::Single(_) => 1,
+                    RequestPacket::Batch(requests) => requests.len(),
+                };
+                rate.acquire(n).await;
+            }
+
+            let _permit = throttle.concurrency.acquire().await;
+            let res = inner.call(req).await;
+            if is_overloaded(&res) {
+                throttle.concurrency.back_off();
+            } else if res.is_ok() {
+                throttle.concurrency.succeeded();
+            }
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns how long acquiring `n` tokens waited
+    async fn wait_for(rate: &RateLimiter, n: usize) -> Duration {
+        let start = Instant::now();
+        rate.acquire(n).await;
+        start.elapsed()
+    }
+
+    /// Asserts that the wait is within the millisecond resolution of the
+    /// timer
+    fn assert_waited(waited: Duration, expected: Duration) {
+        assert!(
+            waited.abs_diff(expected) <= Duration::from_millis(1),
+            "waited {waited:?} rather than {expected:?}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_the_requests() {
+        let rate = RateLimiter::new(10);
+        // The bucket starts full
+        assert_eq!(wait_for(&rate, 10).await, Duration::ZERO);
+        assert_waited(wait_for(&rate, 5).await, Duration::from_millis(500));
+        assert_waited(wait_for(&rate, 1).await, Duration::from_millis(100));
+
+        // The bucket holds at most one second worth of tokens
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(wait_for(&rate, 10).await, Duration::ZERO);
+        assert_waited(wait_for(&rate, 1).await, Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reserves_the_tokens_in_order() {
+        let rate = RateLimiter::new(10);
+        rate.acquire(10).await;
+        let (first, second) = tokio::join!(wait_for(&rate, 5), wait_for(&rate, 5));
+        assert_waited(first, Duration::from_millis(500));
+        assert_waited(second, Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn grows_the_concurrency_back() {
+        let concurrency = ConcurrencyLimiter::new(4);
+        concurrency.back_off();
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+
+        // The limit grows by one once the current limit of requests succeeded
+        concurrency.succeeded();
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+        concurrency.succeeded();
+        assert_eq!(concurrency.semaphore.available_permits(), 3);
+        for _ in 0..10 {
+            concurrency.succeeded();
+        }
+        assert_eq!(concurrency.semaphore.available_permits(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_once_per_cooldown() {
+        let concurrency = ConcurrencyLimiter::new(8);
+        concurrency.back_off();
+        concurrency.back_off();
+        assert_eq!(concurrency.semaphore.available_permits(), 4);
+
+        tokio::time::advance(BACK_OFF_COOLDOWN).await;
+        concurrency.back_off();
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn removes_the_permits_in_flight_as_they_are_released() {
+        let concurrency = ConcurrencyLimiter::new(4);
+        let mut permits = Vec::new();
+        for _ in 0..4 {
+            permits.push(concurrency.acquire().await);
+        }
+
+        concurrency.back_off();
+        assert_eq!(concurrency.semaphore.available_permits(), 0);
+        // The first two permits released are removed
+        permits.truncate(1);
+        assert_eq!(concurrency.semaphore.available_permits(), 1);
+        drop(permits);
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+
+        // Growing the limit back does not overshoot it
+        concurrency.succeeded();
+        concurrency.succeeded();
+        assert_eq!(concurrency.semaphore.available_permits(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn grows_back_before_the_permits_in_flight_are_released() {
+        let concurrency = ConcurrencyLimiter::new(4);
+        let mut permits = Vec::new();
+        for _ in 0..4 {
+            permits.push(concurrency.acquire().await);
+        }
+
+        concurrency.back_off();
+        // Growing back to 3 keeps one of the two permits to remove
+        concurrency.succeeded();
+        concurrency.succeeded();
+        drop(permits);
+        assert_eq!(concurrency.semaphore.available_permits(), 3);
+    }
+}
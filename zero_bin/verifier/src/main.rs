@@ -1,11 +1,10 @@
-use std::fs::File;
-
 use anyhow::Result;
 use clap::Parser;
 use dotenvy::dotenv;
 use proof_gen::proof_types::GeneratedBlockProof;
 use serde_json::Deserializer;
 use tracing::info;
+use zero_bin_common::proof_format::{decode_block_proofs, is_binary};
 
 mod cli;
 mod init;
@@ -15,14 +14,23 @@ fn main() -> Result<()> {
     init::tracing();
 
     let args = cli::Cli::parse();
-    let file = File::open(args.file_path)?;
-    let des = &mut Deserializer::from_reader(&file);
-    let input_proofs: Vec<GeneratedBlockProof> = serde_path_to_error::deserialize(des)?;
+    let bytes = std::fs::read(args.file_path)?;
+
+    let prover_state_manager = args.prover_state_config.into_prover_state_manager();
+    let verifier = prover_state_manager.verifier()?;
 
-    let verifier = args
-        .prover_state_config
-        .into_prover_state_manager()
-        .verifier()?;
+    // The proofs are either a JSON list of proofs or binary proof envelopes
+    let input_proofs: Vec<GeneratedBlockProof> = if is_binary(&bytes) {
+        info!("Reading binary proofs");
+        decode_block_proofs(
+            &bytes,
+            &verifier.state.common,
+            Some(&prover_state_manager.circuit_config),
+        )?
+    } else {
+        let des = &mut Deserializer::from_slice(&bytes);
+        serde_path_to_error::deserialize(des)?
+    };
 
     if input_proofs.into_iter().all(|block_proof| {
         verifier
@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -10,7 +11,7 @@ use paladin::runtime::WorkerRuntime;
 use tokio::select;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::task;
-use zero_bin_common::prover_state::cli::CliProverStateConfig;
+use zero_bin_common::{metrics, prover_state::cli::CliProverStateConfig};
 
 mod init;
 
@@ -26,6 +27,10 @@ struct Cli {
     paladin: paladin::config::Config,
     #[clap(flatten)]
     prover_state_config: CliProverStateConfig,
+    /// If provided, the address on which the Prometheus metrics are served at
+    /// `/metrics`.
+    #[arg(long, env = "ZERO_BIN_METRICS_ADDR")]
+    metrics_addr: Option<SocketAddr>,
 }
 
 #[tokio::main]
@@ -38,6 +43,16 @@ async fn main() -> Result<()> {
 
     let args = Cli::parse();
 
+    metrics::init();
+    if let Some(metrics_addr) = args.metrics_addr {
+        info!("serving metrics on {}", metrics_addr);
+        task::spawn(async move {
+            if let Err(err) = metrics::serve(metrics_addr).await {
+                error!("Failed to serve the metrics: {}", err);
+            }
+        });
+    }
+
     args.prover_state_config
         .into_prover_state_manager()
         .initialize()?;